@@ -7,10 +7,9 @@ use std::{
     cell::Cell,
     fmt::{self, Debug, Formatter},
     io::{Error, ErrorKind},
-    rc::Rc,
 };
 
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex};
 
 use crate::{
     channels::shared_channel::{self, *},
@@ -252,7 +251,7 @@ pub type PartialMesh<T> = MeshBuilder<T, Partial>;
 pub struct MeshBuilder<T: Send, A: MeshAdapter> {
     nr_peers: usize,
     channel_size: usize,
-    peers: Rc<RwLock<Vec<Peer>>>,
+    peers: Arc<Mutex<Vec<Peer>>>,
     channels: Arc<SharedChannels<T>>,
     adapter: A,
 }
@@ -309,7 +308,7 @@ impl<T: 'static + Send, A: MeshAdapter> MeshBuilder<T, A> {
         MeshBuilder {
             nr_peers,
             channel_size,
-            peers: Rc::new(RwLock::new(Vec::new())),
+            peers: Arc::new(Mutex::new(Vec::new())),
             channels: Arc::new(Self::placeholder(nr_peers)),
             adapter,
         }
@@ -331,7 +330,7 @@ impl<T: 'static + Send, A: MeshAdapter> MeshBuilder<T, A> {
     }
 
     fn register(&self, role: Role) -> Result<RegisterResult<bool>, ()> {
-        let mut peers = self.peers.write().unwrap();
+        let mut peers = self.peers.lock().unwrap();
 
         if peers.len() == self.nr_peers {
             return Err(GlommioError::IoError(Error::new(
@@ -384,7 +383,7 @@ impl<T: 'static + Send, A: MeshAdapter> MeshBuilder<T, A> {
         }
 
         let (peer_id, role_id) = {
-            let peers = self.peers.read().unwrap();
+            let peers = self.peers.lock().unwrap();
             let peer_id = peers
                 .binary_search_by(|n| n.executor_id.cmp(&crate::executor().id()))
                 .unwrap();
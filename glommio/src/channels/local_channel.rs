@@ -3,8 +3,8 @@
 //
 // This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
 //
-use crate::{channels::ChannelCapacity, GlommioError, ResourceType};
-use futures_lite::{stream::Stream, Future};
+use crate::{channels::ChannelCapacity, executor::coop, GlommioError, ResourceType};
+use futures_lite::{ready, stream::Stream, Future};
 use std::{
     cell::RefCell,
     pin::Pin,
@@ -651,6 +651,7 @@ impl<'a, T> Stream for ChannelStream<'a, T> {
 
     #[inline]
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let coop = ready!(coop::poll_proceed(cx));
         let result = self.channel.state.borrow_mut().recv_one();
 
         let this = unsafe { self.get_unchecked_mut() };
@@ -668,6 +669,7 @@ impl<'a, T> Stream for ChannelStream<'a, T> {
                 Poll::Pending
             }
             PollResult::Ready(result) => {
+                coop.made_progress();
                 remove_from_the_waiting_queue(
                     this.node.as_mut(),
                     &mut this.channel.state.borrow_mut(),
@@ -722,7 +724,7 @@ impl<T> LocalReceiver<T> {
     /// [`next`]: https://docs.rs/futures-lite/1.11.2/futures_lite/stream/trait.StreamExt.html#method.next
     /// [`Rc`]: https://doc.rust-lang.org/std/rc/struct.Rc.html
     pub async fn recv(&self) -> Option<T> {
-        Waiter::new(|| self.recv_one(), &self.channel).await
+        coop::budgeted(Waiter::new(|| self.recv_one(), &self.channel)).await
     }
 
     /// Converts receiver into the ['Stream'] instance.
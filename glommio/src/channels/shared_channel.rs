@@ -7,11 +7,12 @@
 use crate::{
     channels::spsc_queue::{make, BufferHalf, Consumer, Producer},
     enclose,
+    executor::coop,
     reactor::Reactor,
     sys::{self, SleepNotifier},
     GlommioError, ResourceType,
 };
-use futures_lite::{future, stream::Stream};
+use futures_lite::{future, ready, stream::Stream};
 use std::{
     fmt,
     future::Future,
@@ -399,7 +400,12 @@ impl<T: Send + Sized> ConnectedReceiver<T> {
     }
 
     fn recv_one(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
-        self.do_recv_one(cx, false)
+        let coop = ready!(coop::poll_proceed(cx));
+        let res = self.do_recv_one(cx, false);
+        if res.is_ready() {
+            coop.made_progress();
+        }
+        res
     }
 
     fn do_recv_one(&self, cx: &mut Context<'_>, disconnected: bool) -> Poll<Option<T>> {
@@ -45,6 +45,10 @@ pub enum ResourceType<T> {
     /// Gate variant used for reporting errors for the
     /// [`Gate`](crate::sync::Gate) type.
     Gate,
+
+    /// TaskGroup variant used for reporting errors for the
+    /// [`TaskGroup`](crate::sync::TaskGroup) type.
+    TaskGroup,
 }
 
 /// Error variants for executor queues.
@@ -117,7 +121,9 @@ pub enum BuilderErrorKind {
         cpu: usize,
     },
     /// Error type for using a [`Placement`](crate::PoolPlacement) that requires
-    /// more CPUs than available.
+    /// more CPUs than available. Unless disabled with
+    /// [`allowed_cpus_only`](crate::LocalExecutorBuilder::allowed_cpus_only),
+    /// only the CPUs the process is allowed to run on are available.
     InsufficientCpus {
         /// The number of CPUs required for success.
         required: usize,
@@ -140,6 +146,13 @@ pub enum BuilderErrorKind {
     /// for threads that panicked.  The contained error is forwarded from
     /// [`JoinHandle`](std::thread::JoinHandle).
     ThreadPanic(Box<dyn std::any::Any + Send>),
+    /// Error type for a
+    /// [`configure_shards`](crate::LocalExecutorPoolBuilder::configure_shards)
+    /// closure that changed the placement of a shard, which the pool decides.
+    ShardPlacement {
+        /// The index of the shard
+        index: usize,
+    },
 }
 
 impl fmt::Display for BuilderErrorKind {
@@ -157,6 +170,12 @@ impl fmt::Display for BuilderErrorKind {
                 "requested {minimum} shards but a minimum of {shards} is required"
             ),
             Self::ThreadPanic(_) => write!(f, "thread panicked"),
+            Self::ShardPlacement { index } => {
+                write!(
+                    f,
+                    "the configuration of shard {index} changed its placement"
+                )
+            }
         }
     }
 }
@@ -268,6 +287,7 @@ impl<T> fmt::Display for GlommioError<T> {
                 // TODO: look at what this format string should be as per bug report..
                 ResourceType::File(msg) => write!(f, "File is closed ({msg})"),
                 ResourceType::Gate => write!(f, "Gate is closed"),
+                ResourceType::TaskGroup => write!(f, "TaskGroup is closed"),
             },
             GlommioError::CanNotBeClosed(_, s) => write!(
                 f,
@@ -286,6 +306,7 @@ impl<T> fmt::Display for GlommioError<T> {
                 ResourceType::Channel(_) => write!(f, "Channel operation would block"),
                 ResourceType::File(msg) => write!(f, "File operation would block ({msg})"),
                 ResourceType::Gate => write!(f, "Gate operation would block"),
+                ResourceType::TaskGroup => write!(f, "TaskGroup operation would block"),
             },
             GlommioError::ReactorError(err) => write!(f, "Reactor error: {err}"),
             GlommioError::TimedOut(dur) => write!(f, "Operation timed out after {dur:#?}"),
@@ -379,6 +400,7 @@ impl<T> fmt::Display for ResourceType<T> {
             ResourceType::Channel(_) => "Channel",
             ResourceType::File(_) => "File",
             ResourceType::Gate => "Gate",
+            ResourceType::TaskGroup => "TaskGroup",
         })
     }
 }
@@ -404,6 +426,7 @@ impl<T> Debug for GlommioError<T> {
                 ResourceType::Channel(_) => write!(f, "Channel is closed {{ .. }}"),
                 ResourceType::File(msg) => write!(f, r#"File is closed ("{msg}")"#),
                 ResourceType::Gate => write!(f, "Gate is closed"),
+                ResourceType::TaskGroup => write!(f, "TaskGroup is closed"),
             },
             GlommioError::CanNotBeClosed(resource, str) => match resource {
                 ResourceType::RwLock => write!(f, r#"RwLock can not be closed ("{str}")"#),
@@ -412,6 +435,7 @@ impl<T> Debug for GlommioError<T> {
                     write!(f, r#"File can not be closed : ("{str}"). ("{msg}")"#)
                 }
                 ResourceType::Gate => write!(f, "Gate can not be closed: {str}"),
+                ResourceType::TaskGroup => write!(f, "TaskGroup can not be closed: {str}"),
                 ResourceType::Semaphore {
                     requested,
                     available,
@@ -434,6 +458,7 @@ impl<T> Debug for GlommioError<T> {
                 ResourceType::Channel(_) => write!(f, "Channel operation  would block {{ .. }}"),
                 ResourceType::File(msg) => write!(f, "File operation would block (\"{msg}\")"),
                 ResourceType::Gate => write!(f, "Gate operation would block {{ .. }}"),
+                ResourceType::TaskGroup => write!(f, "TaskGroup operation would block {{ .. }}"),
             },
             GlommioError::ExecutorError(kind) => match kind {
                 ExecutorErrorKind::QueueError { index, kind } => {
@@ -458,6 +483,9 @@ impl<T> Debug for GlommioError<T> {
                     write!(f, "NrShards {{ minimum: {minimum}, shards: {shards} }}")
                 }
                 BuilderErrorKind::ThreadPanic(_) => write!(f, "Thread panicked {{ .. }}"),
+                BuilderErrorKind::ShardPlacement { index } => {
+                    write!(f, "ShardPlacement {{ index: {index} }}")
+                }
             },
             GlommioError::EnhancedIoError {
                 source,
@@ -508,10 +536,13 @@ impl<T> From<GlommioError<T>> for io::Error {
             GlommioError::BuilderError(BuilderErrorKind::NonExistentCpus { .. })
             | GlommioError::BuilderError(BuilderErrorKind::InsufficientCpus { .. })
             | GlommioError::BuilderError(BuilderErrorKind::NrShards { .. })
-            | GlommioError::BuilderError(BuilderErrorKind::ThreadPanic(_)) => io::Error::new(
-                io::ErrorKind::Other,
-                format!("Executor builder error: {display_err}"),
-            ),
+            | GlommioError::BuilderError(BuilderErrorKind::ThreadPanic(_))
+            | GlommioError::BuilderError(BuilderErrorKind::ShardPlacement { .. }) => {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Executor builder error: {display_err}"),
+                )
+            }
             GlommioError::EnhancedIoError { source, .. } => {
                 io::Error::new(source.kind(), display_err)
             }
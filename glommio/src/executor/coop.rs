@@ -0,0 +1,153 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2020 Datadog, Inc.
+//
+//! Cooperative budget of the polls of a task.
+//!
+//! Preemption only happens between task queues: a task that keeps finding
+//! its I/O ready could otherwise run for a whole preemption slice before the
+//! other tasks of its queue get a turn. Each poll of a task is given the poll
+//! budget of its task queue, which leaf futures (socket reads, channel
+//! receives, file reads) consume every time they are polled and ready. Once
+//! the budget is exhausted they return `Pending` after waking the task up,
+//! which puts it at the back of its queue.
+
+use std::{
+    cell::Cell,
+    future::{poll_fn, Future},
+    task::{ready, Context, Poll},
+};
+
+/// The number of leaf futures a task can poll in a single poll, unless set
+/// otherwise with [`ExecutorProxy::set_task_queue_poll_budget`].
+///
+/// [`ExecutorProxy::set_task_queue_poll_budget`]: crate::ExecutorProxy::set_task_queue_poll_budget
+pub(crate) const DEFAULT_POLL_BUDGET: u32 = 128;
+
+thread_local! {
+    // `None` outside of polls, and in polls of tasks whose queue has no budget
+    static BUDGET: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Runs `f`, a poll of a task, with `budget`.
+pub(crate) fn with_budget<R>(budget: Option<u32>, f: impl FnOnce() -> R) -> R {
+    struct ResetGuard(Option<u32>);
+
+    impl Drop for ResetGuard {
+        fn drop(&mut self) {
+            BUDGET.with(|b| b.set(self.0));
+        }
+    }
+
+    // polls can nest, for instance when a task is spawned and run right away
+    let _guard = ResetGuard(BUDGET.with(|b| b.replace(budget)));
+    f()
+}
+
+/// A unit of the budget of the current poll, given back when dropped unless
+/// the future that took it made progress: futures that end up `Pending`
+/// shouldn't eat into the budget of the others.
+#[derive(Debug)]
+#[must_use = "dropping the unit gives it back to the budget"]
+pub(crate) struct RestoreOnPending(Cell<bool>);
+
+impl RestoreOnPending {
+    /// Keeps the unit of budget, to be called when the future is ready.
+    pub(crate) fn made_progress(&self) {
+        self.0.set(false);
+    }
+}
+
+impl Drop for RestoreOnPending {
+    fn drop(&mut self) {
+        if self.0.get() {
+            BUDGET.with(|b| b.set(b.get().map(|left| left + 1)));
+        }
+    }
+}
+
+/// Takes one unit of the budget of the current poll, or wakes the task up
+/// and returns `Pending` if there's none left.
+pub(crate) fn poll_proceed(cx: &Context<'_>) -> Poll<RestoreOnPending> {
+    BUDGET.with(|b| match b.get() {
+        None => Poll::Ready(RestoreOnPending(Cell::new(false))),
+        Some(0) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(left) => {
+            b.set(Some(left - 1));
+            Poll::Ready(RestoreOnPending(Cell::new(true)))
+        }
+    })
+}
+
+/// Like [`poll_proceed`], for async functions: polls `future` only while the
+/// current poll has budget left, and consumes one unit once it's ready.
+pub(crate) async fn budgeted<F: Future>(future: F) -> F::Output {
+    futures_lite::pin!(future);
+    poll_fn(|cx| {
+        let coop = ready!(poll_proceed(cx));
+        let res = future.as_mut().poll(cx);
+        if res.is_ready() {
+            coop.made_progress();
+        }
+        res
+    })
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures_lite::future;
+
+    #[test]
+    fn budget_is_consumed() {
+        let waker = futures::task::noop_waker();
+        let cx = Context::from_waker(&waker);
+
+        let proceed = || match poll_proceed(&cx) {
+            Poll::Ready(coop) => {
+                coop.made_progress();
+                true
+            }
+            Poll::Pending => false,
+        };
+
+        assert!(proceed());
+        with_budget(Some(2), || {
+            assert!(proceed());
+            with_budget(None, || assert!(proceed()));
+            assert!(proceed());
+            assert!(!proceed());
+            assert!(!proceed());
+        });
+        assert!(proceed());
+        with_budget(Some(0), || {
+            assert!(future::block_on(future::poll_once(budgeted(async {}))).is_none())
+        });
+    }
+
+    #[test]
+    fn budget_is_restored_on_pending() {
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        with_budget(Some(1), || {
+            for _ in 0..3 {
+                drop(poll_proceed(&cx));
+            }
+            let mut pending = Box::pin(budgeted(future::pending::<()>()));
+            for _ in 0..3 {
+                assert!(pending.as_mut().poll(&mut cx).is_pending());
+            }
+            assert_eq!(BUDGET.with(|b| b.get()), Some(1));
+            let mut ready = Box::pin(budgeted(future::ready(())));
+            assert!(ready.as_mut().poll(&mut cx).is_ready());
+            assert_eq!(BUDGET.with(|b| b.get()), Some(0));
+        });
+    }
+}
@@ -0,0 +1,248 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2020 Datadog, Inc.
+//
+//! The executors that are members of a pool, which changes as the pool is
+//! resized.
+
+use crate::channels::channel_mesh::{FullMesh, MeshBuilder, PartialMesh};
+use ahash::AHashMap;
+use std::{
+    any::Any,
+    fmt,
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+};
+
+/// The executors that are members of a pool.
+///
+/// The membership starts out with every executor spawned by
+/// [`LocalExecutorPoolBuilder::on_all_shards`], and changes whenever an
+/// executor is added to the pool with [`PoolThreadHandles::add_shard`] or
+/// retired from it with [`PoolThreadHandles::retire`], and whenever an
+/// executor exits, for instance because its future returned or panicked.
+/// Every change starts a new generation of the membership.
+///
+/// Channel meshes can't change their peers once built, so sharded services
+/// rebalance by building a new mesh for every generation: the mesh builders
+/// returned by [`full_mesh`] and [`partial_mesh`] are shared by all the
+/// members of a generation, and expect exactly those members to join. Since
+/// peers in a mesh are ordered by executor id, the peer id of an executor is
+/// its position in [`executors`].
+///
+/// # Examples
+///
+/// ```
+/// use glommio::{LocalExecutorPoolBuilder, PoolPlacement};
+/// use std::time::Duration;
+///
+/// let mut handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+///     .on_all_shards(|| async move {
+///         let membership = glommio::executor().pool_membership().unwrap();
+///         let token = glommio::executor().shutdown_token().unwrap();
+///         while !token.is_shutdown() {
+///             let (generation, mesh) = membership.full_mesh::<usize>("shards", 16);
+///             let generation_over = || {
+///                 futures_lite::future::or(
+///                     async {
+///                         membership.changed_since(generation).await;
+///                     },
+///                     token.wait(),
+///                 )
+///             };
+///             let joined = futures_lite::future::or(async { Some(mesh.join().await.unwrap()) }, async {
+///                 generation_over().await;
+///                 None
+///             });
+///             if let Some((_senders, _receivers)) = joined.await {
+///                 // serve requests, routing them to their shard with
+///                 // `_senders`, until the generation is over
+///                 generation_over().await;
+///             }
+///         }
+///     })
+///     .unwrap();
+///
+/// handles.add_shard().unwrap();
+/// handles.shutdown(Duration::from_secs(1));
+/// ```
+///
+/// [`LocalExecutorPoolBuilder::on_all_shards`]: crate::LocalExecutorPoolBuilder::on_all_shards
+/// [`PoolThreadHandles::add_shard`]: crate::PoolThreadHandles::add_shard
+/// [`PoolThreadHandles::retire`]: crate::PoolThreadHandles::retire
+/// [`full_mesh`]: PoolMembership::full_mesh
+/// [`partial_mesh`]: PoolMembership::partial_mesh
+/// [`executors`]: PoolMembership::executors
+#[derive(Clone, Default)]
+pub struct PoolMembership {
+    inner: Arc<MembershipState>,
+}
+
+#[derive(Default)]
+struct MembershipState {
+    members: Mutex<Members>,
+    waiters: Mutex<Vec<Waker>>,
+}
+
+#[derive(Default)]
+struct Members {
+    generation: u64,
+    // sorted, like the peers of a mesh
+    executors: Vec<usize>,
+    // the meshes of the current generation, by name
+    meshes: AHashMap<&'static str, Box<dyn Any + Send>>,
+}
+
+impl PoolMembership {
+    pub(crate) fn new() -> Self {
+        Default::default()
+    }
+
+    pub(crate) fn join(&self, id: usize) {
+        self.change(|executors| match executors.binary_search(&id) {
+            Ok(_) => false,
+            Err(idx) => {
+                executors.insert(idx, id);
+                true
+            }
+        });
+    }
+
+    pub(crate) fn leave(&self, id: usize) {
+        self.change(|executors| match executors.binary_search(&id) {
+            Ok(idx) => {
+                executors.remove(idx);
+                true
+            }
+            Err(_) => false,
+        });
+    }
+
+    /// Returns a guard that makes the executor `id` leave the membership when
+    /// dropped, unless it already left.
+    pub(crate) fn member_guard(&self, id: usize) -> impl Drop {
+        struct Member(PoolMembership, usize);
+
+        impl Drop for Member {
+            fn drop(&mut self) {
+                self.0.leave(self.1);
+            }
+        }
+
+        Member(self.clone(), id)
+    }
+
+    /// Applies `f` to the members, and starts a new generation if it reports
+    /// a change.
+    fn change(&self, f: impl FnOnce(&mut Vec<usize>) -> bool) {
+        {
+            let mut members = self.inner.members.lock().unwrap();
+            if !f(&mut members.executors) {
+                return;
+            }
+            members.generation += 1;
+            members.meshes.clear();
+        }
+        let waiters = std::mem::take(&mut *self.inner.waiters.lock().unwrap());
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    /// Returns the current generation of the membership
+    pub fn generation(&self) -> u64 {
+        self.inner.members.lock().unwrap().generation
+    }
+
+    /// Returns the ids of the executors that are members of the pool, sorted
+    pub fn executors(&self) -> Vec<usize> {
+        self.inner.members.lock().unwrap().executors.clone()
+    }
+
+    /// Waits until the membership moves past `generation`, and returns the
+    /// new generation
+    pub async fn changed_since(&self, generation: u64) -> u64 {
+        futures_lite::future::poll_fn(|cx| {
+            let current = self.generation();
+            if current != generation {
+                return Poll::Ready(current);
+            }
+            let mut waiters = self.inner.waiters.lock().unwrap();
+            // the generation changes before the waiters are woken up, so
+            // check again now that nobody else can touch them
+            let current = self.generation();
+            if current != generation {
+                return Poll::Ready(current);
+            }
+            if !waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                waiters.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Returns the current generation, along with the builder of the full
+    /// mesh called `name` for the members of that generation.
+    ///
+    /// The first member to ask for the mesh of a generation creates it, with
+    /// room for `channel_size` messages in each channel, and the others get
+    /// the same builder. Members joining the mesh should also watch
+    /// [`changed_since`], as the mesh never completes if the membership
+    /// changes before all members joined.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mesh was created with another type of message.
+    ///
+    /// [`changed_since`]: PoolMembership::changed_since
+    pub fn full_mesh<T: Send + 'static>(
+        &self,
+        name: &'static str,
+        channel_size: usize,
+    ) -> (u64, FullMesh<T>) {
+        self.mesh(name, |nr_peers| MeshBuilder::full(nr_peers, channel_size))
+    }
+
+    /// Like [`full_mesh`], for a partial mesh.
+    ///
+    /// [`full_mesh`]: PoolMembership::full_mesh
+    pub fn partial_mesh<T: Send + 'static>(
+        &self,
+        name: &'static str,
+        channel_size: usize,
+    ) -> (u64, PartialMesh<T>) {
+        self.mesh(name, |nr_peers| {
+            MeshBuilder::partial(nr_peers, channel_size)
+        })
+    }
+
+    fn mesh<M: Clone + Send + 'static>(
+        &self,
+        name: &'static str,
+        create: impl FnOnce(usize) -> M,
+    ) -> (u64, M) {
+        let mut members = self.inner.members.lock().unwrap();
+        let nr_peers = members.executors.len();
+        let mesh = members
+            .meshes
+            .entry(name)
+            .or_insert_with(|| Box::new(create(nr_peers)))
+            .downcast_ref::<M>()
+            .unwrap_or_else(|| panic!("mesh `{name}` was created with another type of message"))
+            .clone();
+        (members.generation, mesh)
+    }
+}
+
+impl fmt::Debug for PoolMembership {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let members = self.inner.members.lock().unwrap();
+        f.debug_struct("PoolMembership")
+            .field("generation", &members.generation)
+            .field("executors", &members.executors)
+            .finish_non_exhaustive()
+    }
+}
@@ -33,21 +33,27 @@
 #![warn(missing_docs, missing_debug_implementations)]
 
 use crate::{
-    error::BuilderErrorKind,
-    executor::stall::StallDetector,
+    error::{BuilderErrorKind, ExecutorErrorKind},
+    executor::stall::{StallDetector, StalledTask, TaskQueueStallStats},
     io::DmaBuffer,
+    metrics::{ExecutorMetrics, TaskQueueMetrics},
     parking, reactor,
-    sys::{self, blocking::BlockingThreadPool},
-    task::{self, waker_fn::dummy_waker},
-    GlommioError, IoRequirements, IoStats, Latency, Reactor, Shares,
+    sys::{self, blocking::BlockingThreadPool, hardware_topology::CpuLocation},
+    task::{self, waker_fn::dummy_waker, TaskDump, TaskPanic},
+    timer::TestClock,
+    BlockingPoolStats, BlockingQueuePolicy, GlommioError, IoMemoryStats, IoRequirements, IoStats,
+    Latency, Reactor, Shares,
 };
 use ahash::AHashMap;
 use futures_lite::pin;
 use latch::{Latch, LatchState};
 use log::warn;
 pub use placement::{CpuSet, Placement, PoolPlacement};
+use simulation::Simulation;
+use sketches_ddsketch::DDSketch;
 use std::{
-    cell::RefCell,
+    any::Any,
+    cell::{Cell, RefCell},
     collections::{hash_map::Entry, BinaryHeap},
     fmt,
     future::Future,
@@ -55,24 +61,51 @@ use std::{
     marker::PhantomData,
     mem::MaybeUninit,
     ops::{Deref, DerefMut},
+    panic::Location,
     pin::Pin,
     rc::Rc,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     task::{Context, Poll},
     thread::{Builder, JoinHandle},
     time::{Duration, Instant},
 };
-use tracing::trace;
+use stealing::{QueueClass, StealableQueue, StealableSpawn, StealableTask};
+use tracing::{trace, trace_span, Instrument};
+use watchdog::{Heartbeat, Watchdog, WatchdogConfig};
 
+pub(crate) mod coop;
 mod latch;
+mod membership;
 mod multitask;
 mod placement;
+mod shutdown;
+mod simulation;
 pub mod stall;
+mod stealing;
+mod watchdog;
+
+pub use membership::PoolMembership;
+pub use shutdown::{ShutdownReport, ShutdownToken};
+pub use watchdog::ExecutorLiveness;
 
 pub(crate) const DEFAULT_EXECUTOR_NAME: &str = "unnamed";
 pub(crate) const DEFAULT_PREEMPT_TIMER: Duration = Duration::from_millis(100);
 pub(crate) const DEFAULT_IO_MEMORY: usize = 10 << 20;
 pub(crate) const DEFAULT_RING_SUBMISSION_DEPTH: usize = 128;
+pub(crate) const DEFAULT_BLOCKING_QUEUE_CAPACITY: usize = 4 << 10;
+
+// The weight the tasks spawned directly into a task queue that has children
+// get when competing with those children for the queue's share. This is the
+// same as the default shares of a task queue.
+const OWN_TASKS_SHARES: u64 = 1000;
+
+// The most tasks an executor takes from the pool's stealable queue each time
+// it processes its external events, so a backlog drains quickly without
+// starving the executor's own tasks.
+const STEAL_BATCH: usize = 16;
 
 /// Result type alias that removes the need to specify a type parameter
 /// that's only valid in the channel variants of the error. Otherwise, it
@@ -124,7 +157,6 @@ impl TaskQueueHandle {
     }
 }
 
-#[derive(Debug)]
 pub(crate) struct TaskQueue {
     pub(crate) ex: Rc<multitask::LocalExecutor>,
     active: bool,
@@ -132,53 +164,103 @@ pub(crate) struct TaskQueue {
     vruntime: u64,
     io_requirements: IoRequirements,
     name: String,
+    parent: Option<TaskQueueHandle>,
+    children: Vec<TaskQueueHandle>,
+    // for the earliest deadline first class: how long after becoming runnable
+    // the queue has to be run, and the resulting absolute deadline while the
+    // queue is active.
+    relative_deadline: Option<Duration>,
+    deadline: Option<Instant>,
     last_adjustment: Instant,
     // for dynamic shares classes
     yielded: bool,
+    // how many leaf futures a task can poll in one poll, if limited
+    poll_budget: Option<u32>,
     stats: TaskQueueStats,
+    // kept apart from the stats, so those stay `Copy`
+    scheduling_delay_us: DDSketch,
+}
+
+impl fmt::Debug for TaskQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskQueue")
+            .field("ex", &self.ex)
+            .field("active", &self.active)
+            .field("shares", &self.shares)
+            .field("vruntime", &self.vruntime)
+            .field("io_requirements", &self.io_requirements)
+            .field("name", &self.name)
+            .field("parent", &self.parent)
+            .field("children", &self.children)
+            .field("relative_deadline", &self.relative_deadline)
+            .field("deadline", &self.deadline)
+            .field("last_adjustment", &self.last_adjustment)
+            .field("yielded", &self.yielded)
+            .field("poll_budget", &self.poll_budget)
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
 }
 
-// Impl a custom order so we use a min-heap
+// Impl a custom order so we use a min-heap. Task queues with a deadline always
+// go first, earliest deadline first, followed by the others in vruntime order.
 impl Ord for TaskQueue {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.vruntime.cmp(&self.vruntime)
+        match (self.deadline, other.deadline) {
+            (Some(x), Some(y)) => y.cmp(&x),
+            (Some(_), None) => std::cmp::Ordering::Greater,
+            (None, Some(_)) => std::cmp::Ordering::Less,
+            (None, None) => other.vruntime.cmp(&self.vruntime),
+        }
     }
 }
 
 impl PartialOrd for TaskQueue {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(other.vruntime.cmp(&self.vruntime))
+        Some(self.cmp(other))
     }
 }
 
 impl PartialEq for TaskQueue {
     fn eq(&self, other: &Self) -> bool {
-        self.vruntime == other.vruntime
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
 impl Eq for TaskQueue {}
 
+pub(crate) fn new_scheduling_delay_sketch() -> DDSketch {
+    DDSketch::new(sketches_ddsketch::Config::new(0.01, 2048, 1.0e-9))
+}
+
 impl TaskQueue {
     fn new<S>(
         index: TaskQueueHandle,
         name: S,
         shares: Shares,
         ioreq: IoRequirements,
+        parent: Option<TaskQueueHandle>,
+        live_tasks: Rc<multitask::LiveTasks>,
     ) -> Rc<RefCell<Self>>
     where
         S: Into<String>,
     {
         Rc::new(RefCell::new(TaskQueue {
-            ex: Rc::new(multitask::LocalExecutor::new()),
+            ex: Rc::new(multitask::LocalExecutor::new(live_tasks)),
             active: false,
-            stats: TaskQueueStats::new(index, shares.reciprocal_shares()),
+            stats: TaskQueueStats::new(index, parent, shares.reciprocal_shares()),
             shares,
             vruntime: 0,
             io_requirements: ioreq,
             name: name.into(),
+            parent,
+            children: Vec::new(),
+            relative_deadline: None,
+            deadline: None,
             last_adjustment: Instant::now(),
             yielded: false,
+            poll_budget: Some(coop::DEFAULT_POLL_BUDGET),
+            scheduling_delay_us: new_scheduling_delay_sketch(),
         }))
     }
 
@@ -186,6 +268,22 @@ impl TaskQueue {
         self.active
     }
 
+    fn class(&self) -> QueueClass {
+        QueueClass {
+            name: self.name.clone(),
+            latency: self.io_requirements.latency_req,
+            shares: match self.shares {
+                Shares::Static(shares) => Some(shares),
+                Shares::Dynamic(_) => None,
+            },
+        }
+    }
+
+    /// Returns the scheduling delays recorded since the last call.
+    fn take_scheduling_delays(&mut self) -> DDSketch {
+        std::mem::replace(&mut self.scheduling_delay_us, new_scheduling_delay_sketch())
+    }
+
     fn get_task(&mut self) -> Option<multitask::Runnable> {
         self.ex.get_task()
     }
@@ -204,11 +302,15 @@ impl TaskQueue {
         }
     }
 
-    fn account_vruntime(&mut self, delta: Duration) -> Option<u64> {
-        let delta_scaled = (self.stats.reciprocal_shares * (delta.as_nanos() as u64)) >> 12;
+    fn account_vruntime(&mut self, delta: Duration, reciprocal_shares: u64) -> Option<u64> {
+        let delta_scaled = (reciprocal_shares * (delta.as_nanos() as u64)) >> 12;
         self.stats.runtime += delta;
+        self.stats.subtree_runtime += delta;
         self.stats.queue_selected += 1;
         self.active = self.ex.is_active();
+        if !self.active {
+            self.deadline = None;
+        }
 
         let vruntime = self.vruntime.checked_add(delta_scaled);
         if let Some(x) = vruntime {
@@ -279,6 +381,13 @@ impl ExecutorStats {
     pub fn tasks_executed(&self) -> u64 {
         self.tasks_executed
     }
+
+    pub(crate) fn accumulate(&mut self, other: &ExecutorStats) {
+        self.executor_runtime += other.executor_runtime;
+        self.total_runtime += other.total_runtime;
+        self.scheduler_runs += other.scheduler_runs;
+        self.tasks_executed += other.tasks_executed;
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -286,18 +395,26 @@ impl ExecutorStats {
 /// consumed by applications.
 pub struct TaskQueueStats {
     index: TaskQueueHandle,
+    parent: Option<TaskQueueHandle>,
     // so we can easily produce a handle
     reciprocal_shares: u64,
     queue_selected: u64,
     runtime: Duration,
+    subtree_runtime: Duration,
 }
 
 impl TaskQueueStats {
-    fn new(index: TaskQueueHandle, reciprocal_shares: u64) -> Self {
+    fn new(
+        index: TaskQueueHandle,
+        parent: Option<TaskQueueHandle>,
+        reciprocal_shares: u64,
+    ) -> Self {
         Self {
             index,
+            parent,
             reciprocal_shares,
             runtime: Duration::from_nanos(0),
+            subtree_runtime: Duration::from_nanos(0),
             queue_selected: 0,
         }
     }
@@ -307,6 +424,12 @@ impl TaskQueueStats {
         self.index
     }
 
+    /// Returns the handle of the parent of this task queue, if it was created
+    /// with [`ExecutorProxy::create_child_task_queue`]
+    pub fn parent(&self) -> Option<TaskQueueHandle> {
+        self.parent
+    }
+
     /// Returns the current number of shares in this task queue.
     ///
     /// If the task queue is configured to use static shares this will never
@@ -323,6 +446,13 @@ impl TaskQueueStats {
         self.runtime
     }
 
+    /// Returns the accumulated runtime of this task queue plus that of all
+    /// its descendants. For task queues without children, this is the same
+    /// as [`TaskQueueStats::runtime`].
+    pub fn subtree_runtime(&self) -> Duration {
+        self.subtree_runtime
+    }
+
     /// Returns the number of times this queue was selected to be executed. In
     /// conjunction with the runtime, you can extract an average of the
     /// amount of time this queue tends to run for
@@ -330,16 +460,28 @@ impl TaskQueueStats {
         self.queue_selected
     }
 
+    pub(crate) fn accumulate(&mut self, other: &TaskQueueStats) {
+        // shares are sampled, not accumulated
+        self.reciprocal_shares = other.reciprocal_shares;
+        self.queue_selected += other.queue_selected;
+        self.runtime += other.runtime;
+        self.subtree_runtime += other.subtree_runtime;
+    }
+
+    /// Returns the stats of the same task queue, with nothing accumulated
+    pub(crate) fn empty(&self) -> Self {
+        Self {
+            index: self.index,
+            parent: self.parent,
+            reciprocal_shares: self.reciprocal_shares,
+            queue_selected: Default::default(),
+            runtime: Default::default(),
+            subtree_runtime: Default::default(),
+        }
+    }
+
     pub(crate) fn take(&mut self) -> Self {
-        std::mem::replace(
-            self,
-            Self {
-                index: self.index,
-                reciprocal_shares: self.reciprocal_shares,
-                queue_selected: Default::default(),
-                runtime: Default::default(),
-            },
-        )
+        std::mem::replace(self, self.empty())
     }
 }
 
@@ -348,6 +490,7 @@ struct ExecutorQueues {
     active_executors: BinaryHeap<Rc<RefCell<TaskQueue>>>,
     available_executors: AHashMap<usize, Rc<RefCell<TaskQueue>>>,
     active_executing: Option<Rc<RefCell<TaskQueue>>>,
+    live_tasks: Rc<multitask::LiveTasks>,
     executor_index: usize,
     default_vruntime: u64,
     preempt_timer_duration: Duration,
@@ -362,6 +505,7 @@ impl ExecutorQueues {
             active_executors: BinaryHeap::new(),
             available_executors: AHashMap::new(),
             active_executing: None,
+            live_tasks: Default::default(),
             executor_index: 1, // 0 is the default
             default_vruntime: 0,
             preempt_timer_duration,
@@ -383,15 +527,109 @@ impl ExecutorQueues {
             .unwrap_or(self.default_preempt_timer_duration)
     }
 
-    fn maybe_activate(&mut self, queue: Rc<RefCell<TaskQueue>>) {
+    fn is_subtree_active(&self, queue: &TaskQueue) -> bool {
+        queue.is_active()
+            || queue.children.iter().any(|child| {
+                self.available_executors
+                    .get(&child.index)
+                    .map_or(false, |child| self.is_subtree_active(&child.borrow()))
+            })
+    }
+
+    /// The sum of the shares of everything competing for `queue`'s share of
+    /// the CPU: its active children and, if runnable, its own tasks.
+    fn active_weight(&self, queue: &TaskQueue) -> u64 {
+        let own = if queue.is_active() {
+            OWN_TASKS_SHARES
+        } else {
+            0
+        };
+        own + queue
+            .children
+            .iter()
+            .filter_map(|child| self.available_executors.get(&child.index))
+            .map(|child| child.borrow())
+            .filter(|child| self.is_subtree_active(child))
+            .map(|child| child.stats.current_shares() as u64)
+            .sum::<u64>()
+    }
+
+    /// The reciprocal of the fraction of the CPU `queue` and its descendants
+    /// are entitled to. Top-level task queues compete with each other
+    /// according to their shares, and nested ones divide their parent's
+    /// fraction among their active siblings according to theirs.
+    fn subtree_reciprocal_shares(&self, queue: &TaskQueue) -> u64 {
+        match queue
+            .parent
+            .and_then(|parent| self.available_executors.get(&parent.index))
+        {
+            None => queue.stats.reciprocal_shares,
+            Some(parent) => {
+                let parent = parent.borrow();
+                let shares = queue.stats.current_shares().max(1) as u64;
+                let weight = self.active_weight(&parent).max(shares);
+                self.subtree_reciprocal_shares(&parent) * weight / shares
+            }
+        }
+    }
+
+    /// The reciprocal shares used to account the runtime of `queue`'s own
+    /// tasks, which share the queue's fraction with its active children.
+    fn effective_reciprocal_shares(&self, queue: &TaskQueue) -> u64 {
+        let reciprocal_shares = self.subtree_reciprocal_shares(queue);
+        if queue.children.is_empty() {
+            reciprocal_shares
+        } else {
+            let weight = self.active_weight(queue).max(OWN_TASKS_SHARES);
+            reciprocal_shares * weight / OWN_TASKS_SHARES
+        }
+    }
+
+    fn account_subtree_runtime(&self, mut parent: Option<TaskQueueHandle>, delta: Duration) {
+        while let Some(queue) = parent.and_then(|p| self.available_executors.get(&p.index)) {
+            let mut queue = queue.borrow_mut();
+            queue.stats.subtree_runtime += delta;
+            parent = queue.parent;
+        }
+    }
+
+    /// Activates `queue` if it isn't yet. Returns whether the task queue that
+    /// is currently executing should be preempted in favor of `queue`, which
+    /// is the case if `queue` has an earlier deadline.
+    fn maybe_activate(&mut self, queue: Rc<RefCell<TaskQueue>>) -> bool {
         let mut state = queue.borrow_mut();
         if !state.is_active() {
             state.vruntime = self.default_vruntime + 1;
             state.active = true;
+            state.deadline = state.relative_deadline.map(|d| Instant::now() + d);
+            let deadline = state.deadline;
             drop(state);
             self.active_executors.push(queue);
             self.reevaluate_preempt_timer();
+
+            if let (Some(deadline), Some(executing)) = (deadline, &self.active_executing) {
+                return executing
+                    .borrow()
+                    .deadline
+                    .map_or(true, |current| deadline < current);
+            }
+        }
+        false
+    }
+
+    // The smallest vruntime out of all the active task queues that are
+    // scheduled according to their shares.
+    fn min_vruntime(&self) -> Option<u64> {
+        let first = self.active_executors.peek()?.borrow();
+        if first.deadline.is_none() {
+            return Some(first.vruntime);
         }
+        self.active_executors
+            .iter()
+            .map(|x| x.borrow())
+            .filter(|x| x.deadline.is_none())
+            .map(|x| x.vruntime)
+            .min()
     }
 }
 
@@ -418,6 +656,53 @@ impl<T: Send + 'static> ExecutorJoinHandle<T> {
     }
 }
 
+/// What a [`LocalExecutor`] does when one of its tasks panics.
+///
+/// Whatever the policy, the hook installed with
+/// [`LocalExecutorBuilder::on_task_panic`] is called first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Abort the process.
+    Abort,
+    /// Let the panic unwind through the executor, tearing down the thread
+    /// running it. This is the default.
+    #[default]
+    Propagate,
+    /// Cancel the task and keep the executor running. The panic can be
+    /// retrieved with [`Task::join`] or [`JoinHandle::join`].
+    ///
+    /// [`JoinHandle::join`]: crate::task::JoinHandle::join
+    Catch,
+}
+
+/// The hook called when a task panics.
+#[derive(Clone)]
+pub(crate) struct PanicHook(Arc<dyn Fn(&TaskPanic) + Send + Sync>);
+
+impl fmt::Debug for PanicHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("PanicHook { .. }")
+    }
+}
+
+/// The hooks called as the loop of an executor runs.
+#[derive(Clone, Default)]
+pub(crate) struct LoopHooks {
+    before_park: Option<Arc<dyn Fn() + Send + Sync>>,
+    after_unpark: Option<Arc<dyn Fn(Duration) + Send + Sync>>,
+    scheduler_pass: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl fmt::Debug for LoopHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoopHooks")
+            .field("before_park", &self.before_park.is_some())
+            .field("after_unpark", &self.after_unpark.is_some())
+            .field("scheduler_pass", &self.scheduler_pass.is_some())
+            .finish()
+    }
+}
+
 /// A factory that can be used to configure and create a [`LocalExecutor`].
 ///
 /// Methods can be chained on it in order to configure it.
@@ -454,6 +739,9 @@ impl<T: Send + 'static> ExecutorJoinHandle<T> {
 pub struct LocalExecutorBuilder {
     /// The placement policy for the [`LocalExecutor`] to create
     placement: Placement,
+    /// Whether the placement only selects CPUs the process is allowed to run
+    /// on
+    allowed_cpus_only: bool,
     /// Spin for duration before parking a reactor
     spin_before_park: Option<Duration>,
     /// A name for the thread-to-be (if any), for identification in panic
@@ -464,6 +752,10 @@ pub struct LocalExecutorBuilder {
     /// that, but it will come from the standard allocator and performance
     /// will suffer. Defaults to 10 MiB.
     io_memory: usize,
+    /// Whether to back the memory reserved for storage I/O with huge pages
+    io_memory_huge_pages: bool,
+    /// Whether to allocate memory on the NUMA node of the executor
+    numa_local_memory: bool,
     /// The depth of the IO rings to create. This influences the level of IO
     /// concurrency. A higher ring depth allows a shard to submit a
     /// greater number of IO requests to the kernel at once.
@@ -472,14 +764,39 @@ pub struct LocalExecutorBuilder {
     preempt_timer_duration: Duration,
     /// Whether to record the latencies of individual IO requests
     record_io_latencies: bool,
+    /// Whether to record the scheduling delays of tasks
+    record_scheduling_delays: bool,
+    /// Whether to record the poll statistics of individual tasks
+    record_task_stats: bool,
     /// The placement policy of the blocking thread pool
     /// Defaults to one thread using the same placement strategy as the host
     /// executor
     blocking_thread_pool_placement: PoolPlacement,
+    /// The placement policy of the threads running glommio's own file
+    /// operations, if not the threads of the blocking thread pool
+    internal_blocking_thread_pool_placement: Option<PoolPlacement>,
+    /// How many operations the queue of the blocking thread pool holds
+    blocking_queue_capacity: usize,
+    /// What to do with operations when the queue of the blocking thread pool
+    /// is full
+    blocking_queue_policy: BlockingQueuePolicy,
     /// Whether to detect stalls in unyielding tasks.
     /// [`stall::DefaultStallDetectionHandler`] installs a signal handler for
     /// [`nix::libc::SIGUSR1`], so is disabled by default.
     detect_stalls: Option<Box<dyn stall::StallDetectionHandler + 'static>>,
+    /// Whether `detect_stalls` was called, in which case the shards of a pool
+    /// don't get a handler from the pool
+    detect_stalls_set: bool,
+    /// What to do when a task panics
+    panic_policy: PanicPolicy,
+    /// Called whenever a task panics
+    panic_hook: Option<PanicHook>,
+    /// Called as the executor loop parks, unparks and runs the task queues
+    loop_hooks: LoopHooks,
+    /// The seed of the simulation, if the executor is simulated
+    simulation_seed: Option<u64>,
+    /// The clock timers run on, if not on real time
+    clock: Option<TestClock>,
 }
 
 impl LocalExecutorBuilder {
@@ -491,17 +808,45 @@ impl LocalExecutorBuilder {
     pub fn new(placement: Placement) -> LocalExecutorBuilder {
         LocalExecutorBuilder {
             placement: placement.clone(),
+            allowed_cpus_only: true,
             spin_before_park: None,
             name: String::from(DEFAULT_EXECUTOR_NAME),
             io_memory: DEFAULT_IO_MEMORY,
+            io_memory_huge_pages: false,
+            numa_local_memory: true,
             ring_depth: DEFAULT_RING_SUBMISSION_DEPTH,
             preempt_timer_duration: DEFAULT_PREEMPT_TIMER,
             record_io_latencies: false,
-            blocking_thread_pool_placement: PoolPlacement::from(placement),
+            record_scheduling_delays: false,
+            record_task_stats: false,
+            blocking_thread_pool_placement: PoolPlacement::from(placement).for_blocking_pool(),
+            internal_blocking_thread_pool_placement: None,
+            blocking_queue_capacity: DEFAULT_BLOCKING_QUEUE_CAPACITY,
+            blocking_queue_policy: BlockingQueuePolicy::default(),
             detect_stalls: None,
+            detect_stalls_set: false,
+            panic_policy: PanicPolicy::default(),
+            panic_hook: None,
+            loop_hooks: LoopHooks::default(),
+            simulation_seed: None,
+            clock: None,
         }
     }
 
+    /// Whether the [`Placement`] only selects CPUs the process is allowed to
+    /// run on, according to its affinity mask and the effective cpuset of its
+    /// cgroup (see [`CpuSet::allowed`]). Defaults to `true`.
+    ///
+    /// Turning this off lets the placement select any CPU that is online,
+    /// which is only useful if the process is allowed to run on more CPUs by
+    /// the time the executor binds to them: binding to CPUs the process is
+    /// not allowed to run on fails.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn allowed_cpus_only(mut self, enabled: bool) -> LocalExecutorBuilder {
+        self.allowed_cpus_only = enabled;
+        self
+    }
+
     /// Spin for duration before parking a reactor
     #[must_use = "The builder must be built to be useful"]
     pub fn spin_before_park(mut self, spin: Duration) -> LocalExecutorBuilder {
@@ -530,6 +875,37 @@ impl LocalExecutorBuilder {
         self
     }
 
+    /// Whether to back the memory reserved for storage I/O with huge pages,
+    /// which saves the kernel from walking page tables on every I/O. The
+    /// memory is then rounded up to a multiple of 2 MiB. If the kernel has no
+    /// huge pages reserved, transparent huge pages are used instead, where
+    /// enabled. Disabled by default.
+    ///
+    /// See [`ExecutorProxy::io_memory_stats`] to know whether huge pages
+    /// were used.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn io_memory_huge_pages(mut self, enabled: bool) -> LocalExecutorBuilder {
+        self.io_memory_huge_pages = enabled;
+        self
+    }
+
+    /// Whether to allocate the memory of the executor on the NUMA node of the
+    /// CPUs it is bound to, if they are all on the same node. This covers the
+    /// memory reserved for storage I/O as well as the buffers the executor
+    /// allocates with [`allocate_dma_buffer_global`] and the standard
+    /// allocator. The kernel falls back to other nodes when the node runs out
+    /// of free memory. Enabled by default.
+    ///
+    /// The memory policy of the thread the executor runs on is restored when
+    /// the executor is dropped. Processes that are not allowed to change their
+    /// memory policy, like those in containers without the `CAP_SYS_NICE`
+    /// capability, leave the memory wherever the kernel decides.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn numa_local_memory(mut self, enabled: bool) -> LocalExecutorBuilder {
+        self.numa_local_memory = enabled;
+        self
+    }
+
     /// The depth of the IO rings to create. This influences the level of IO
     /// concurrency. A higher ring depth allows a shard to submit a
     /// greater number of IO requests to the kernel at once.
@@ -567,9 +943,32 @@ impl LocalExecutorBuilder {
         self
     }
 
+    /// Whether to record the time between tasks being woken up and being
+    /// polled, per task queue. See
+    /// [`ExecutorProxy::task_queue_scheduling_delay_us`]. Recording delays
+    /// reads the clock every time a task is scheduled and again before each of
+    /// its polls, and adds a sample to a distribution for each poll. That is
+    /// measurable on executors running many short polls. Disabled by default.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn record_scheduling_delays(mut self, enabled: bool) -> LocalExecutorBuilder {
+        self.record_scheduling_delays = enabled;
+        self
+    }
+
+    /// Whether to record how long and how often each task is polled, as
+    /// returned by [`Task::stats`]. Recording requires reading the clock every
+    /// time a task is woken up and around every poll. Disabled by default, in
+    /// which case the stats of all tasks stay empty.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn record_task_stats(mut self, enabled: bool) -> LocalExecutorBuilder {
+        self.record_task_stats = enabled;
+        self
+    }
+
     /// The placement policy of the blocking thread pool.
     /// Defaults to one thread using the same placement strategy as the host
-    /// executor.
+    /// executor, or [`PoolPlacement::housekeeping`] if the executor is placed
+    /// on isolated CPUs.
     #[must_use = "The builder must be built to be useful"]
     pub fn blocking_thread_pool_placement(
         mut self,
@@ -579,6 +978,59 @@ impl LocalExecutorBuilder {
         self
     }
 
+    /// Runs glommio's own blocking file operations, such as
+    /// [`rename`](crate::io::rename), [`remove`](crate::io::remove)
+    /// and truncations, on threads and a queue of their own, placed according
+    /// to `placement`, rather than on the blocking thread pool along with the
+    /// closures passed to [`spawn_blocking`]. A burst of slow file operations
+    /// then doesn't hold up those closures, and vice versa.
+    ///
+    /// By default, both share the blocking thread pool.
+    ///
+    /// [`spawn_blocking`]: ExecutorProxy::spawn_blocking
+    #[must_use = "The builder must be built to be useful"]
+    pub fn internal_blocking_thread_pool_placement(
+        mut self,
+        placement: PoolPlacement,
+    ) -> LocalExecutorBuilder {
+        self.internal_blocking_thread_pool_placement = Some(placement);
+        self
+    }
+
+    /// How many operations may wait in the queue of the blocking thread pool
+    /// for a thread to pick them up. Once the queue is full, what happens to
+    /// new operations depends on the [`blocking_queue_policy`]. With a
+    /// capacity of zero, operations are only handed over to idle threads.
+    /// Defaults to 4096.
+    ///
+    /// If glommio's file operations have [their own
+    /// threads](LocalExecutorBuilder::internal_blocking_thread_pool_placement),
+    /// they also have their own queue, of the same capacity.
+    ///
+    /// [`blocking_queue_policy`]: LocalExecutorBuilder::blocking_queue_policy
+    #[must_use = "The builder must be built to be useful"]
+    pub fn blocking_queue_capacity(mut self, capacity: usize) -> LocalExecutorBuilder {
+        self.blocking_queue_capacity = capacity;
+        self
+    }
+
+    /// What to do with blocking operations when the queue of the blocking
+    /// thread pool is full. By default, the task issuing them waits for room
+    /// in the queue.
+    ///
+    /// With [`BlockingQueuePolicy::Reject`], glommio's file operations fail
+    /// with an error of kind [`std::io::ErrorKind::WouldBlock`], and so does
+    /// [`try_spawn_blocking`]. [`spawn_blocking`] can't fail, so it always
+    /// waits.
+    ///
+    /// [`try_spawn_blocking`]: ExecutorProxy::try_spawn_blocking
+    /// [`spawn_blocking`]: ExecutorProxy::spawn_blocking
+    #[must_use = "The builder must be built to be useful"]
+    pub fn blocking_queue_policy(mut self, policy: BlockingQueuePolicy) -> LocalExecutorBuilder {
+        self.blocking_queue_policy = policy;
+        self
+    }
+
     /// Whether to detect stalls in unyielding tasks.
     /// [`stall::DefaultStallDetectionHandler`] installs a signal handler for
     /// [`nix::libc::SIGUSR1`], so is disabled by default.
@@ -598,6 +1050,162 @@ impl LocalExecutorBuilder {
         handler: Option<Box<dyn stall::StallDetectionHandler + 'static>>,
     ) -> Self {
         self.detect_stalls = handler;
+        self.detect_stalls_set = true;
+        self
+    }
+
+    /// What to do when a task panics. Defaults to [`PanicPolicy::Propagate`],
+    /// which tears down the executor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{LocalExecutorBuilder, PanicPolicy};
+    ///
+    /// let local_ex = LocalExecutorBuilder::default()
+    ///     .panic_policy(PanicPolicy::Catch)
+    ///     .make()
+    ///     .unwrap();
+    /// local_ex.run(async {
+    ///     let res = glommio::spawn_local(async { panic!("oops") }).join().await;
+    ///     assert_eq!(res.unwrap_err().message(), Some("oops"));
+    /// });
+    /// ```
+    #[must_use = "The builder must be built to be useful"]
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Installs a hook called whenever a task panics, before the
+    /// [`PanicPolicy`] is applied. Useful to log or count panics.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn on_task_panic(mut self, hook: impl Fn(&TaskPanic) + Send + Sync + 'static) -> Self {
+        self.panic_hook = Some(PanicHook(Arc::new(hook)));
+        self
+    }
+
+    /// Installs a hook called whenever the executor runs out of tasks to run
+    /// and is about to sleep until there is I/O, a timer or a wake up from
+    /// another thread. Useful to flush batched metrics or buffered writes.
+    ///
+    /// The hook runs on the executor thread, so it can spawn tasks; if it
+    /// does, the executor runs them rather than sleeping. Parking is often
+    /// preceded by [`spin_before_park`], and doesn't happen while the
+    /// executor has work to do, so hooks can't rely on being called
+    /// regularly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::LocalExecutorBuilder;
+    /// use std::{
+    ///     sync::{
+    ///         atomic::{AtomicUsize, Ordering},
+    ///         Arc,
+    ///     },
+    ///     time::Duration,
+    /// };
+    ///
+    /// let parks = Arc::new(AtomicUsize::new(0));
+    /// let local_ex = LocalExecutorBuilder::default()
+    ///     .on_park({
+    ///         let parks = parks.clone();
+    ///         move || {
+    ///             parks.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     })
+    ///     .on_unpark(|parked| println!("slept for {parked:?}"))
+    ///     .make()
+    ///     .unwrap();
+    /// local_ex.run(glommio::timer::sleep(Duration::from_millis(10)));
+    /// assert!(parks.load(Ordering::Relaxed) > 0);
+    /// ```
+    ///
+    /// [`spin_before_park`]: LocalExecutorBuilder::spin_before_park
+    #[must_use = "The builder must be built to be useful"]
+    pub fn on_park(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.loop_hooks.before_park = Some(Arc::new(hook));
+        self
+    }
+
+    /// Installs a hook called whenever the executor wakes up after parking,
+    /// with the time it was parked for. See [`on_park`] for details.
+    ///
+    /// [`on_park`]: LocalExecutorBuilder::on_park
+    #[must_use = "The builder must be built to be useful"]
+    pub fn on_unpark(mut self, hook: impl Fn(Duration) + Send + Sync + 'static) -> Self {
+        self.loop_hooks.after_unpark = Some(Arc::new(hook));
+        self
+    }
+
+    /// Installs a hook called at the end of every pass of the scheduler, once
+    /// the task queues that were runnable ran until they were out of tasks
+    /// or preempted. It runs on the executor thread, often enough that it
+    /// must be cheap.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn on_scheduler_pass(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.loop_hooks.scheduler_pass = Some(Arc::new(hook));
+        self
+    }
+
+    /// Runs the timers of the executor on `clock` rather than on real time,
+    /// so tests can pause and advance time. See [`TestClock`] for details.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn test_clock(mut self, clock: TestClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Runs the executor in simulation mode, where the scheduler is driven by
+    /// `seed`.
+    ///
+    /// What the scheduler decides based on real time is decided by a
+    /// pseudo-random sequence derived from the seed instead: which task queue
+    /// runs next, which of its runnable tasks is polled next, and when the
+    /// running task queue is preempted. The candidates are sorted before one
+    /// is picked, so the order in which they were woken up doesn't matter.
+    ///
+    /// Timers run on virtual time, which stands still while tasks run and
+    /// jumps to the next timer whenever the executor would otherwise wait
+    /// for it, so a test can sleep for hours in no time. That is a paused
+    /// [`TestClock`] with auto-advance enabled, unless another one is
+    /// installed with [`LocalExecutorBuilder::test_clock`].
+    ///
+    /// I/O completions are held back rather than waking tasks up as they
+    /// happen. Once no task is runnable, the executor waits for the I/O in
+    /// flight to complete, and releases some of the completions, picked with
+    /// the seed. I/O completes in an order that only depends on the seed,
+    /// however long it takes.
+    ///
+    /// Running the tasks again with the same seed therefore interleaves them
+    /// the same way, which makes a failing seed replayable. The exceptions
+    /// are tasks that depend on the outside world: I/O over the network can
+    /// only be released once the peer made it complete, and messages from
+    /// other executors wake tasks up whenever they arrive. It is meant for
+    /// testing, as it gives up on shares, latency requirements, and the
+    /// efficiency of the scheduler and of I/O.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{timer::sleep, LocalExecutorBuilder};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let local_ex = LocalExecutorBuilder::default()
+    ///     .simulation(42)
+    ///     .make()
+    ///     .unwrap();
+    ///
+    /// let start = Instant::now();
+    /// local_ex.run(async {
+    ///     sleep(Duration::from_secs(3600)).await;
+    /// });
+    /// assert!(start.elapsed() < Duration::from_secs(3600));
+    /// ```
+    #[must_use = "The builder must be built to be useful"]
+    pub fn simulation(mut self, seed: u64) -> Self {
+        self.simulation_seed = Some(seed);
         self
     }
 
@@ -612,18 +1220,37 @@ impl LocalExecutorBuilder {
     /// ```
     pub fn make(self) -> Result<LocalExecutor> {
         let notifier = sys::new_sleep_notifier()?;
-        let mut cpu_set_gen = placement::CpuSetGenerator::one(self.placement)?;
+        let mut cpu_set_gen =
+            placement::CpuSetGenerator::one(self.placement, self.allowed_cpus_only)?;
+        let cpus = cpu_set_gen.next();
+        let numa_node = cpus.numa_node();
         let mut le = LocalExecutor::new(
             notifier,
-            cpu_set_gen.next().cpu_binding(),
+            cpus.cpu_binding(),
             LocalExecutorConfig {
                 io_memory: self.io_memory,
+                io_memory_huge_pages: self.io_memory_huge_pages,
+                numa_node: numa_node.filter(|_| self.numa_local_memory),
                 ring_depth: self.ring_depth,
                 preempt_timer: self.preempt_timer_duration,
                 record_io_latencies: self.record_io_latencies,
+                record_scheduling_delays: self.record_scheduling_delays,
+                record_task_stats: self.record_task_stats,
                 spin_before_park: self.spin_before_park,
                 thread_pool_placement: self.blocking_thread_pool_placement,
+                internal_thread_pool_placement: self.internal_blocking_thread_pool_placement,
+                blocking_queue_capacity: self.blocking_queue_capacity,
+                blocking_queue_policy: self.blocking_queue_policy,
                 detect_stalls: self.detect_stalls,
+                stealable_tasks: None,
+                shutdown: None,
+                membership: None,
+                panic_policy: self.panic_policy,
+                panic_hook: self.panic_hook,
+                loop_hooks: self.loop_hooks,
+                heartbeat: None,
+                simulation_seed: self.simulation_seed,
+                clock: self.clock,
             },
         )?;
         le.init();
@@ -682,29 +1309,60 @@ impl LocalExecutorBuilder {
     {
         let notifier = sys::new_sleep_notifier()?;
         let name = format!("{}-{}", self.name, notifier.id());
-        let mut cpu_set_gen = placement::CpuSetGenerator::one(self.placement)?;
+        let mut cpu_set_gen =
+            placement::CpuSetGenerator::one(self.placement, self.allowed_cpus_only)?;
         let io_memory = self.io_memory;
+        let io_memory_huge_pages = self.io_memory_huge_pages;
+        let numa_local_memory = self.numa_local_memory;
         let ring_depth = self.ring_depth;
         let preempt_timer_duration = self.preempt_timer_duration;
         let spin_before_park = self.spin_before_park;
         let detect_stalls = self.detect_stalls;
         let record_io_latencies = self.record_io_latencies;
+        let record_scheduling_delays = self.record_scheduling_delays;
+        let record_task_stats = self.record_task_stats;
         let blocking_thread_pool_placement = self.blocking_thread_pool_placement;
+        let internal_blocking_thread_pool_placement = self.internal_blocking_thread_pool_placement;
+        let blocking_queue_capacity = self.blocking_queue_capacity;
+        let blocking_queue_policy = self.blocking_queue_policy;
+        let panic_policy = self.panic_policy;
+        let panic_hook = self.panic_hook;
+        let loop_hooks = self.loop_hooks;
+        let simulation_seed = self.simulation_seed;
+        let clock = self.clock;
 
         Builder::new()
             .name(name)
             .spawn(move || {
+                let cpus = cpu_set_gen.next();
+                let numa_node = cpus.numa_node();
                 let mut le = LocalExecutor::new(
                     notifier,
-                    cpu_set_gen.next().cpu_binding(),
+                    cpus.cpu_binding(),
                     LocalExecutorConfig {
                         io_memory,
+                        io_memory_huge_pages,
+                        numa_node: numa_node.filter(|_| numa_local_memory),
                         ring_depth,
                         preempt_timer: preempt_timer_duration,
                         record_io_latencies,
+                        record_scheduling_delays,
+                        record_task_stats,
                         spin_before_park,
                         thread_pool_placement: blocking_thread_pool_placement,
+                        internal_thread_pool_placement: internal_blocking_thread_pool_placement,
+                        blocking_queue_capacity,
+                        blocking_queue_policy,
                         detect_stalls,
+                        stealable_tasks: None,
+                        shutdown: None,
+                        membership: None,
+                        panic_policy,
+                        panic_hook,
+                        loop_hooks,
+                        heartbeat: None,
+                        simulation_seed,
+                        clock,
                     },
                 )?;
                 le.init();
@@ -755,6 +1413,10 @@ pub struct LocalExecutorPoolBuilder {
     /// that, but it will come from the standard allocator and performance
     /// will suffer. Defaults to 10 MiB.
     io_memory: usize,
+    /// Whether to back the memory reserved for storage I/O with huge pages
+    io_memory_huge_pages: bool,
+    /// Whether to allocate memory on the NUMA node of each executor
+    numa_local_memory: bool,
     /// The depth of the IO rings to create. This influences the level of IO
     /// concurrency. A higher ring depth allows a shard to submit a
     /// greater number of IO requests to the kernel at once.
@@ -763,31 +1425,76 @@ pub struct LocalExecutorPoolBuilder {
     preempt_timer_duration: Duration,
     /// Indicates a policy by which [`LocalExecutor`]s are bound to CPUs.
     placement: PoolPlacement,
+    /// Whether the placement only selects CPUs the process is allowed to run
+    /// on
+    allowed_cpus_only: bool,
     /// Whether to record the latencies of individual IO requests
     record_io_latencies: bool,
+    /// Whether to record the scheduling delays of tasks
+    record_scheduling_delays: bool,
+    /// Whether to record the poll statistics of individual tasks
+    record_task_stats: bool,
     /// The placement policy of the blocking thread pools. Each executor has
     /// its own pool. Defaults to 1 thread per pool, bound using the same
     /// placement strategy as its host executor
     blocking_thread_pool_placement: PoolPlacement,
+    /// The placement policy of the threads running glommio's own file
+    /// operations, if not the threads of the blocking thread pools
+    internal_blocking_thread_pool_placement: Option<PoolPlacement>,
+    /// How many operations the queue of each blocking thread pool holds
+    blocking_queue_capacity: usize,
+    /// What to do with operations when the queue of a blocking thread pool is
+    /// full
+    blocking_queue_policy: BlockingQueuePolicy,
     /// Factory function to generate the stall detection handler.
     /// [`DefaultStallDetectionHandler installs`] a signal handler for
     /// [`nix::libc::SIGUSR1`], so is disabled by default.
-    handler_gen: Option<Box<dyn Fn() -> Box<dyn stall::StallDetectionHandler + 'static>>>,
+    handler_gen: Option<Box<dyn Fn() -> Box<dyn stall::StallDetectionHandler + 'static> + Send>>,
+    /// What to do when a task panics
+    panic_policy: PanicPolicy,
+    /// Called whenever a task panics
+    panic_hook: Option<PanicHook>,
+    /// Called as the executor loop parks, unparks and runs the task queues
+    loop_hooks: LoopHooks,
+    /// Watches the liveness of the executors of the pool
+    watchdog: Option<WatchdogConfig>,
+    /// The clock timers run on, if not on real time
+    clock: Option<TestClock>,
+    /// Adjusts the configuration of each shard
+    shard_config: Option<Box<ShardConfig>>,
 }
 
+type ShardConfig =
+    dyn Fn(usize, &[CpuLocation], LocalExecutorBuilder) -> LocalExecutorBuilder + Send;
+
 impl fmt::Debug for LocalExecutorPoolBuilder {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("LocalExecutorPoolBuilder")
             .field("spin_before_park", &self.spin_before_park)
             .field("name", &self.name)
             .field("io_memory", &self.io_memory)
+            .field("io_memory_huge_pages", &self.io_memory_huge_pages)
+            .field("numa_local_memory", &self.numa_local_memory)
             .field("ring_depth", &self.ring_depth)
             .field("preempt_timer_duration", &self.preempt_timer_duration)
+            .field("allowed_cpus_only", &self.allowed_cpus_only)
             .field("record_io_latencies", &self.record_io_latencies)
+            .field("record_scheduling_delays", &self.record_scheduling_delays)
+            .field("record_task_stats", &self.record_task_stats)
             .field(
                 "blocking_thread_pool_placement",
                 &self.blocking_thread_pool_placement,
             )
+            .field(
+                "internal_blocking_thread_pool_placement",
+                &self.internal_blocking_thread_pool_placement,
+            )
+            .field("blocking_queue_capacity", &self.blocking_queue_capacity)
+            .field("blocking_queue_policy", &self.blocking_queue_policy)
+            .field("panic_policy", &self.panic_policy)
+            .field("watchdog", &self.watchdog)
+            .field("clock", &self.clock)
+            .field("shard_config", &self.shard_config.is_some())
             .finish_non_exhaustive()
     }
 }
@@ -803,15 +1510,38 @@ impl LocalExecutorPoolBuilder {
             spin_before_park: None,
             name: String::from(DEFAULT_EXECUTOR_NAME),
             io_memory: DEFAULT_IO_MEMORY,
+            io_memory_huge_pages: false,
+            numa_local_memory: true,
             ring_depth: DEFAULT_RING_SUBMISSION_DEPTH,
             preempt_timer_duration: DEFAULT_PREEMPT_TIMER,
             placement: placement.clone(),
+            allowed_cpus_only: true,
             record_io_latencies: false,
-            blocking_thread_pool_placement: placement.shrink_to(1),
+            record_scheduling_delays: false,
+            record_task_stats: false,
+            blocking_thread_pool_placement: placement.for_blocking_pool(),
+            internal_blocking_thread_pool_placement: None,
+            blocking_queue_capacity: DEFAULT_BLOCKING_QUEUE_CAPACITY,
+            blocking_queue_policy: BlockingQueuePolicy::default(),
             handler_gen: None,
+            panic_policy: PanicPolicy::default(),
+            panic_hook: None,
+            loop_hooks: LoopHooks::default(),
+            watchdog: None,
+            clock: None,
+            shard_config: None,
         }
     }
 
+    /// Please see documentation under
+    /// [`LocalExecutorBuilder::allowed_cpus_only`] for details. The setting
+    /// also applies to [`PoolThreadHandles::add_shard_on`].
+    #[must_use = "The builder must be built to be useful"]
+    pub fn allowed_cpus_only(mut self, enabled: bool) -> Self {
+        self.allowed_cpus_only = enabled;
+        self
+    }
+
     /// Please see documentation under
     /// [`LocalExecutorBuilder::spin_before_park`] for details.  The setting
     /// is applied to all executors in the pool.
@@ -840,6 +1570,24 @@ impl LocalExecutorPoolBuilder {
         self
     }
 
+    /// Please see documentation under
+    /// [`LocalExecutorBuilder::io_memory_huge_pages`] for details. The
+    /// setting is applied to all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn io_memory_huge_pages(mut self, enabled: bool) -> Self {
+        self.io_memory_huge_pages = enabled;
+        self
+    }
+
+    /// Please see documentation under
+    /// [`LocalExecutorBuilder::numa_local_memory`] for details. The setting
+    /// is applied to all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn numa_local_memory(mut self, enabled: bool) -> Self {
+        self.numa_local_memory = enabled;
+        self
+    }
+
     /// Please see documentation under [`LocalExecutorBuilder::ring_depth`] for
     /// details.  The setting is applied to all executors in the pool.
     #[must_use = "The builder must be built to be useful"]
@@ -865,20 +1613,73 @@ impl LocalExecutorPoolBuilder {
         self
     }
 
+    /// Please see documentation under
+    /// [`LocalExecutorBuilder::record_scheduling_delays`] for details. The
+    /// setting is applied to all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn record_scheduling_delays(mut self, enabled: bool) -> Self {
+        self.record_scheduling_delays = enabled;
+        self
+    }
+
+    /// Please see documentation under
+    /// [`LocalExecutorBuilder::record_task_stats`] for details. The setting is
+    /// applied to all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn record_task_stats(mut self, enabled: bool) -> Self {
+        self.record_task_stats = enabled;
+        self
+    }
+
     /// The placement policy of the blocking thread pool.
     /// Defaults to one thread using the same placement strategy as the host
-    /// executor.
+    /// executor, or [`PoolPlacement::housekeeping`] if the executor is placed
+    /// on isolated CPUs.
     #[must_use = "The builder must be built to be useful"]
     pub fn blocking_thread_pool_placement(mut self, placement: PoolPlacement) -> Self {
         self.blocking_thread_pool_placement = placement;
         self
     }
 
+    /// Please see documentation under
+    /// [`LocalExecutorBuilder::internal_blocking_thread_pool_placement`] for
+    /// details. Each executor in the pool gets its own threads, placed
+    /// according to `placement`.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn internal_blocking_thread_pool_placement(mut self, placement: PoolPlacement) -> Self {
+        self.internal_blocking_thread_pool_placement = Some(placement);
+        self
+    }
+
+    /// Please see documentation under
+    /// [`LocalExecutorBuilder::blocking_queue_capacity`] for details. The
+    /// setting is applied to all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn blocking_queue_capacity(mut self, capacity: usize) -> Self {
+        self.blocking_queue_capacity = capacity;
+        self
+    }
+
+    /// Please see documentation under
+    /// [`LocalExecutorBuilder::blocking_queue_policy`] for details. The
+    /// setting is applied to all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn blocking_queue_policy(mut self, policy: BlockingQueuePolicy) -> Self {
+        self.blocking_queue_policy = policy;
+        self
+    }
+
     /// Whether to detect stalls in unyielding tasks.
     /// This method takes a closure of `handler_gen`, which will be called on
     /// each new thread to generate the stall detection handler to be used in
     /// that executor. [`stall::DefaultStallDetectionHandler`] installs a signal
     /// handler for [`nix::libc::SIGUSR1`], so is disabled by default.
+    ///
+    /// The closure has to be `Send`, which it didn't before pools could be
+    /// resized: [`PoolThreadHandles::add_shard`] calls it for the executors
+    /// added later on, from whichever thread holds the handles. A closure
+    /// capturing state that isn't `Send` has to create that state on each call
+    /// instead.
     /// # Examples
     ///
     /// ```
@@ -901,41 +1702,195 @@ impl LocalExecutorPoolBuilder {
     #[must_use = "The builder must be built to be useful"]
     pub fn detect_stalls(
         mut self,
-        handler_gen: Option<Box<dyn Fn() -> Box<dyn stall::StallDetectionHandler + 'static>>>,
+        handler_gen: Option<
+            Box<dyn Fn() -> Box<dyn stall::StallDetectionHandler + 'static> + Send>,
+        >,
     ) -> Self {
         self.handler_gen = handler_gen;
         self
     }
 
-    /// Spawn a pool of [`LocalExecutor`]s in a new thread according to the
-    /// [`PoolPlacement`] policy, which is `Unbound` by default.
-    ///
-    /// This method is the pool equivalent of [`LocalExecutorBuilder::spawn`].
-    ///
-    /// The method takes a closure `fut_gen` which will be called on each new
-    /// thread to obtain the [`Future`] to be executed there.
-    ///
-    /// # Panics
-    ///
-    /// The newly spawned thread panics if creating the executor fails. If you
-    /// need more fine-grained error handling consider initializing those
-    /// entities manually.
-    #[must_use = "This spawns executors on multiple threads; threads may fail to spawn or you may \
-                  need to call `PoolThreadHandles::join_all()` to keep the main thread alive"]
-    pub fn on_all_shards<G, F, T>(self, fut_gen: G) -> Result<PoolThreadHandles<T>>
-    where
-        G: FnOnce() -> F + Clone + Send + 'static,
+    /// Please see documentation under [`LocalExecutorBuilder::panic_policy`]
+    /// for details. The setting is applied to all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Please see documentation under [`LocalExecutorBuilder::on_task_panic`]
+    /// for details. The hook is shared by all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn on_task_panic(mut self, hook: impl Fn(&TaskPanic) + Send + Sync + 'static) -> Self {
+        self.panic_hook = Some(PanicHook(Arc::new(hook)));
+        self
+    }
+
+    /// Please see documentation under [`LocalExecutorBuilder::on_park`] for
+    /// details. The hook is shared by all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn on_park(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.loop_hooks.before_park = Some(Arc::new(hook));
+        self
+    }
+
+    /// Please see documentation under [`LocalExecutorBuilder::on_unpark`] for
+    /// details. The hook is shared by all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn on_unpark(mut self, hook: impl Fn(Duration) + Send + Sync + 'static) -> Self {
+        self.loop_hooks.after_unpark = Some(Arc::new(hook));
+        self
+    }
+
+    /// Please see documentation under
+    /// [`LocalExecutorBuilder::on_scheduler_pass`] for details. The hook is
+    /// shared by all executors in the pool.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn on_scheduler_pass(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.loop_hooks.scheduler_pass = Some(Arc::new(hook));
+        self
+    }
+
+    /// Watches the executors of the pool from a separate thread, and calls
+    /// `handler` with the [`ExecutorLiveness`] of any of them that is
+    /// unresponsive for longer than `threshold`.
+    ///
+    /// Every executor publishes a heartbeat at the end of each pass of its
+    /// scheduler. An executor is unresponsive if either:
+    /// * it is busy and hasn't completed a pass for `threshold`: one of its
+    ///   tasks is most likely blocking the thread, in a long computation or a
+    ///   blocking system call;
+    /// * it is parked, and wake ups or spawns sent to it from other threads
+    ///   have been waiting for `threshold`: it missed a wake up.
+    ///
+    /// An executor parked with nothing to do is never unresponsive, no matter
+    /// how long it sleeps. The handler is called once per episode: an
+    /// executor is reported again only after it completed a pass. It is
+    /// called from the watchdog thread, so it shouldn't block. `threshold`
+    /// should be comfortably longer than the
+    /// [`preempt_timer`](LocalExecutorPoolBuilder::preempt_timer), which
+    /// bounds how long a healthy pass takes.
+    ///
+    /// The liveness of the executors can also be queried at any time with
+    /// [`PoolThreadHandles::liveness`].
+    #[must_use = "The builder must be built to be useful"]
+    pub fn watchdog(
+        mut self,
+        threshold: Duration,
+        handler: impl Fn(&ExecutorLiveness) + Send + Sync + 'static,
+    ) -> Self {
+        self.watchdog = Some(WatchdogConfig {
+            threshold,
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    /// Please see documentation under [`LocalExecutorBuilder::test_clock`]
+    /// for details. The clock is shared by all executors in the pool, and
+    /// any of them being idle is enough for it to auto-advance.
+    #[must_use = "The builder must be built to be useful"]
+    pub fn test_clock(mut self, clock: TestClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Adjusts the configuration of each executor of the pool before it is
+    /// made, so shards can be configured differently from one another.
+    ///
+    /// `configure` is called for every shard with the index of the shard, the
+    /// CPUs it is bound to (none if it is unbound), and a
+    /// [`LocalExecutorBuilder`] holding the configuration of the pool. The
+    /// executor of the shard is made from the builder it returns: any
+    /// setting can be changed, except for the placement and
+    /// [`allowed_cpus_only`](LocalExecutorBuilder::allowed_cpus_only), which
+    /// are decided by the pool. Changing them fails with
+    /// [`BuilderErrorKind::ShardPlacement`]. The stall detection handler of
+    /// the pool (see [`detect_stalls`](Self::detect_stalls)) is only
+    /// generated for shards whose builder was not given one by `configure`.
+    ///
+    /// Shards are indexed in the order they are spawned: from `0` for the
+    /// shards of [`on_all_shards`](Self::on_all_shards), and on for those
+    /// added with [`PoolThreadHandles::add_shard`] later on. `configure` runs
+    /// on the thread that spawns the shard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{LocalExecutorPoolBuilder, PoolPlacement};
+    ///
+    /// let handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(3))
+    ///     .configure_shards(|index, _cpus, builder| match index {
+    ///         // shard 0 handles networking and doesn't need much I/O memory
+    ///         0 => builder.name("network").io_memory(1 << 20),
+    ///         _ => builder.name("storage").ring_depth(512),
+    ///     })
+    ///     .on_all_shards(|| async move { std::thread::current().name().unwrap().to_string() })
+    ///     .unwrap();
+    ///
+    /// let names: Vec<_> = handles.join_all().into_iter().map(Result::unwrap).collect();
+    /// assert_eq!(names.iter().filter(|n| n.starts_with("network-")).count(), 1);
+    /// assert_eq!(names.iter().filter(|n| n.starts_with("storage-")).count(), 2);
+    /// ```
+    #[must_use = "The builder must be built to be useful"]
+    pub fn configure_shards(
+        mut self,
+        configure: impl Fn(usize, &[CpuLocation], LocalExecutorBuilder) -> LocalExecutorBuilder
+            + Send
+            + 'static,
+    ) -> Self {
+        self.shard_config = Some(Box::new(configure));
+        self
+    }
+
+    /// Spawn a pool of [`LocalExecutor`]s in a new thread according to the
+    /// [`PoolPlacement`] policy, which is `Unbound` by default.
+    ///
+    /// This method is the pool equivalent of [`LocalExecutorBuilder::spawn`].
+    ///
+    /// The method takes a closure `fut_gen` which will be called on each new
+    /// thread to obtain the [`Future`] to be executed there.
+    ///
+    /// # Panics
+    ///
+    /// The newly spawned thread panics if creating the executor fails. If you
+    /// need more fine-grained error handling consider initializing those
+    /// entities manually.
+    #[must_use = "This spawns executors on multiple threads; threads may fail to spawn or you may \
+                  need to call `PoolThreadHandles::join_all()` to keep the main thread alive"]
+    pub fn on_all_shards<G, F, T>(self, fut_gen: G) -> Result<PoolThreadHandles<T>>
+    where
+        G: FnOnce() -> F + Clone + Send + 'static,
         F: Future<Output = T> + 'static,
         T: Send + 'static,
     {
-        let mut handles = PoolThreadHandles::new();
+        let stealable_tasks = StealableQueue::new();
+        let pool = PoolState {
+            watchdog: self
+                .watchdog
+                .clone()
+                .map(|config| Watchdog::spawn(config, stealable_tasks.clone()))
+                .transpose()?
+                .map(Arc::new),
+            stealable_tasks,
+            shutdown: ShutdownToken::new(),
+            membership: PoolMembership::new(),
+        };
+        let mut handles = PoolThreadHandles::new(
+            pool.shutdown.clone(),
+            pool.membership.clone(),
+            self.allowed_cpus_only,
+        );
+        handles.watchdog = pool.watchdog.clone();
         let nr_shards = self.placement.executor_count();
-        let mut cpu_set_gen = placement::CpuSetGenerator::pool(self.placement.clone())?;
+        let mut cpu_set_gen =
+            placement::CpuSetGenerator::pool(self.placement.clone(), self.allowed_cpus_only)?;
         let latch = Latch::new(nr_shards);
 
-        for _ in 0..nr_shards {
-            match self.spawn_thread(&mut cpu_set_gen, &latch, fut_gen.clone()) {
-                Ok(handle) => handles.push(handle),
+        for index in 0..nr_shards {
+            let cpus = cpu_set_gen.next();
+            match self.spawn_thread(index, cpus.clone(), &latch, &pool, fut_gen.clone()) {
+                Ok((id, handle)) => handles.push(id, cpus, handle),
                 Err(err) => {
                     handles.join_all();
                     return Err(err);
@@ -943,37 +1898,149 @@ impl LocalExecutorPoolBuilder {
             }
         }
 
+        // shards added later on are spawned the same way, only on their own
+        let mut next_index = nr_shards;
+        let spawner: ShardSpawner<T> = Box::new(move |cpus| {
+            let cpus = match cpus {
+                Some(cpus) => cpus,
+                None => cpu_set_gen.try_next()?,
+            };
+            let (id, handle) = self.spawn_thread(
+                next_index,
+                cpus.clone(),
+                &Latch::new(1),
+                &pool,
+                fut_gen.clone(),
+            )?;
+            next_index += 1;
+            Ok((id, cpus, handle))
+        });
+        handles.spawner = Some(Mutex::new(spawner));
+
         Ok(handles)
     }
 
-    /// Spawns a thread
+    /// The configuration of the executor of the shard `index`: the one of the
+    /// pool, adjusted by [`configure_shards`](Self::configure_shards)
+    fn shard_builder(
+        &self,
+        index: usize,
+        cpus: &placement::CpuIter,
+    ) -> Result<LocalExecutorBuilder> {
+        let builder = LocalExecutorBuilder {
+            // decided by the pool
+            placement: Placement::Unbound,
+            allowed_cpus_only: self.allowed_cpus_only,
+            spin_before_park: self.spin_before_park,
+            name: self.name.clone(),
+            io_memory: self.io_memory,
+            io_memory_huge_pages: self.io_memory_huge_pages,
+            numa_local_memory: self.numa_local_memory,
+            ring_depth: self.ring_depth,
+            preempt_timer_duration: self.preempt_timer_duration,
+            record_io_latencies: self.record_io_latencies,
+            record_scheduling_delays: self.record_scheduling_delays,
+            record_task_stats: self.record_task_stats,
+            blocking_thread_pool_placement: self.blocking_thread_pool_placement.clone(),
+            internal_blocking_thread_pool_placement: self
+                .internal_blocking_thread_pool_placement
+                .clone(),
+            blocking_queue_capacity: self.blocking_queue_capacity,
+            blocking_queue_policy: self.blocking_queue_policy,
+            detect_stalls: None,
+            detect_stalls_set: false,
+            panic_policy: self.panic_policy,
+            panic_hook: self.panic_hook.clone(),
+            loop_hooks: self.loop_hooks.clone(),
+            simulation_seed: None,
+            clock: self.clock.clone(),
+        };
+        let mut builder = match &self.shard_config {
+            Some(configure) => {
+                let mut locations: Vec<_> = cpus.clone().collect();
+                locations.sort_by_key(|l| l.cpu);
+                configure(index, &locations, builder)
+            }
+            None => builder,
+        };
+        if builder.placement != Placement::Unbound
+            || builder.allowed_cpus_only != self.allowed_cpus_only
+        {
+            return Err(GlommioError::BuilderError(
+                BuilderErrorKind::ShardPlacement { index },
+            ));
+        }
+        if !builder.detect_stalls_set {
+            builder.detect_stalls = self.handler_gen.as_ref().map(|x| (*x.deref())());
+        }
+        Ok(builder)
+    }
+
+    /// Spawns a thread, and returns the id of the executor running on it
     fn spawn_thread<G, F, T>(
         &self,
-        cpu_set_gen: &mut placement::CpuSetGenerator,
+        index: usize,
+        cpus: placement::CpuIter,
         latch: &Latch,
+        pool: &PoolState,
         fut_gen: G,
-    ) -> Result<JoinHandle<Result<T>>>
+    ) -> Result<(usize, JoinHandle<Result<T>>)>
     where
         G: FnOnce() -> F + Clone + Send + 'static,
         F: Future<Output = T> + 'static,
         T: Send + 'static,
     {
-        // NOTE: `self.placement` was `std::mem::take`en in `Self::on_all_shards`; you
-        // should no longer rely on its value at this point
-        let cpu_binding = cpu_set_gen.next().cpu_binding();
+        let shard = match self.shard_builder(index, &cpus) {
+            Ok(shard) => shard,
+            Err(err) => {
+                // the shards spawned so far must not wait for this one
+                latch.cancel().expect("unreachable: latch was ready");
+                return Err(err);
+            }
+        };
+        let numa_node = cpus.numa_node().filter(|_| shard.numa_local_memory);
+        let cpu_binding = cpus.cpu_binding();
         let notifier = sys::new_sleep_notifier()?;
-        let name = format!("{}-{}", self.name, notifier.id());
+        let id = notifier.id();
+        let name = format!("{}-{}", shard.name, id);
+        // dropped along with the executor, or the closure if it never runs
+        let heartbeat = pool
+            .watchdog
+            .as_ref()
+            .map(|watchdog| watchdog.register(notifier.clone()));
+        // the executor is a member before it runs anything, so it finds itself
+        // in the membership
+        pool.membership.join(id);
         let handle = Builder::new().name(name).spawn({
-            let io_memory = self.io_memory;
-            let ring_depth = self.ring_depth;
-            let preempt_timer_duration = self.preempt_timer_duration;
-            let spin_before_park = self.spin_before_park;
-            let record_io_latencies = self.record_io_latencies;
-            let blocking_thread_pool_placement = self.blocking_thread_pool_placement.clone();
-            let detect_stalls = self.handler_gen.as_ref().map(|x| (*x.deref())());
+            let io_memory = shard.io_memory;
+            let io_memory_huge_pages = shard.io_memory_huge_pages;
+            let ring_depth = shard.ring_depth;
+            let preempt_timer_duration = shard.preempt_timer_duration;
+            let spin_before_park = shard.spin_before_park;
+            let record_io_latencies = shard.record_io_latencies;
+            let record_scheduling_delays = shard.record_scheduling_delays;
+            let record_task_stats = shard.record_task_stats;
+            let blocking_thread_pool_placement = shard.blocking_thread_pool_placement;
+            let internal_blocking_thread_pool_placement =
+                shard.internal_blocking_thread_pool_placement;
+            let blocking_queue_capacity = shard.blocking_queue_capacity;
+            let blocking_queue_policy = shard.blocking_queue_policy;
+            let detect_stalls = shard.detect_stalls;
+            let panic_policy = shard.panic_policy;
+            let panic_hook = shard.panic_hook;
+            let loop_hooks = shard.loop_hooks;
+            let simulation_seed = shard.simulation_seed;
+            let clock = shard.clock;
             let latch = Latch::clone(latch);
+            let stealable_tasks = Arc::clone(&pool.stealable_tasks);
+            let shutdown = pool.shutdown.for_shard(id);
+            let membership = pool.membership.clone();
 
             move || {
+                let _exited = shutdown.shard_guard();
+                // dropped first, so the executor left the membership by the
+                // time it counts as exited
+                let _member = membership.member_guard(id);
                 // only allow the thread to create the `LocalExecutor` if all other threads that
                 // are supposed to be created by the pool builder were successfully spawned
                 if latch.arrive_and_wait() == LatchState::Ready {
@@ -982,16 +2049,34 @@ impl LocalExecutorPoolBuilder {
                         cpu_binding,
                         LocalExecutorConfig {
                             io_memory,
+                            io_memory_huge_pages,
+                            numa_node,
                             ring_depth,
                             preempt_timer: preempt_timer_duration,
                             record_io_latencies,
+                            record_scheduling_delays,
+                            record_task_stats,
                             spin_before_park,
                             thread_pool_placement: blocking_thread_pool_placement,
+                            internal_thread_pool_placement: internal_blocking_thread_pool_placement,
+                            blocking_queue_capacity,
+                            blocking_queue_policy,
                             detect_stalls,
+                            stealable_tasks: Some(stealable_tasks),
+                            shutdown: Some(shutdown.clone()),
+                            membership: Some(membership.clone()),
+                            panic_policy,
+                            panic_hook,
+                            loop_hooks,
+                            heartbeat,
+                            simulation_seed,
+                            clock,
                         },
                     )?;
                     le.init();
-                    le.run(async move { Ok(fut_gen().await) })
+                    let id = le.id();
+                    let live_tasks = le.queues.borrow().live_tasks.clone();
+                    le.run(async move { shutdown.run_shard(id, live_tasks, fut_gen()).await })
                 } else {
                     // this `Err` isn't visible to the user; the pool builder directly returns an
                     // `Err` from the `std::thread::Builder`
@@ -1001,12 +2086,13 @@ impl LocalExecutorPoolBuilder {
         });
 
         match handle {
-            Ok(h) => Ok(h),
+            Ok(h) => Ok((id, h)),
             Err(e) => {
                 // The `std::thread::Builder` was unable to spawn the thread and retuned an
                 // `Err`, so we notify other threads to let them know they
                 // should not proceed with constructing their `LocalExecutor`s
                 latch.cancel().expect("unreachable: latch was ready");
+                pool.membership.leave(id);
 
                 Err(e.into())
             }
@@ -1014,23 +2100,78 @@ impl LocalExecutorPoolBuilder {
     }
 }
 
+// What the executors of a pool share
+struct PoolState {
+    stealable_tasks: Arc<StealableQueue>,
+    shutdown: ShutdownToken,
+    membership: PoolMembership,
+    watchdog: Option<Arc<Watchdog>>,
+}
+
+type ShardSpawner<T> = Box<
+    dyn FnMut(
+            Option<placement::CpuIter>,
+        ) -> Result<(usize, placement::CpuIter, JoinHandle<Result<T>>)>
+        + Send,
+>;
+
 /// Holds a collection of [`JoinHandle`]s.
 ///
 /// This struct is returned by [`LocalExecutorPoolBuilder::on_all_shards`].
-#[derive(Debug)]
+/// Besides waiting for the executors of the pool, it resizes the pool: see
+/// [`add_shard`] and [`retire`].
+///
+/// [`add_shard`]: PoolThreadHandles::add_shard
+/// [`retire`]: PoolThreadHandles::retire
 pub struct PoolThreadHandles<T> {
     handles: Vec<JoinHandle<Result<T>>>,
+    // the id of the executor of every handle, and the CPUs it is bound to
+    shards: Vec<(usize, placement::CpuIter)>,
+    // the retired executors and their CPUs, handed to the next executors added
+    // once the retired ones exited
+    retiring: Vec<(usize, placement::CpuIter)>,
+    // the number of executors retired so far
+    retired: usize,
+    token: ShutdownToken,
+    membership: PoolMembership,
+    // whether placements only select CPUs the process is allowed to run on
+    allowed_cpus_only: bool,
+    // stops when the pool and the spawner holding it are dropped
+    watchdog: Option<Arc<Watchdog>>,
+    // only ever used through `&mut self`, the mutex is there so the handles
+    // stay `Sync`
+    spawner: Option<Mutex<ShardSpawner<T>>>,
+}
+
+impl<T> fmt::Debug for PoolThreadHandles<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolThreadHandles")
+            .field("handles", &self.handles)
+            .field("executor_ids", &self.executor_ids())
+            .field("token", &self.token)
+            .field("membership", &self.membership)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<T> PoolThreadHandles<T> {
-    fn new() -> Self {
+    fn new(token: ShutdownToken, membership: PoolMembership, allowed_cpus_only: bool) -> Self {
         Self {
             handles: Vec::new(),
+            shards: Vec::new(),
+            retiring: Vec::new(),
+            retired: 0,
+            token,
+            membership,
+            allowed_cpus_only,
+            watchdog: None,
+            spawner: None,
         }
     }
 
-    fn push(&mut self, handle: JoinHandle<Result<T>>) {
-        self.handles.push(handle)
+    fn push(&mut self, id: usize, cpus: placement::CpuIter, handle: JoinHandle<Result<T>>) {
+        self.handles.push(handle);
+        self.shards.push((id, cpus));
     }
 
     /// Obtain a reference to the `JoinHandle`s.
@@ -1038,6 +2179,100 @@ impl<T> PoolThreadHandles<T> {
         &self.handles
     }
 
+    /// Returns the ids of the executors of the pool, in the same order as
+    /// [`handles`](PoolThreadHandles::handles)
+    pub fn executor_ids(&self) -> Vec<usize> {
+        self.shards.iter().map(|(id, _)| *id).collect()
+    }
+
+    /// Returns the membership of the pool, which is also available to its
+    /// executors with [`ExecutorProxy::pool_membership`]
+    pub fn membership(&self) -> PoolMembership {
+        self.membership.clone()
+    }
+
+    /// Returns the liveness of the executors of the pool, sorted by executor
+    /// id, or nothing if the pool has no watchdog.
+    ///
+    /// Retired executors are included until they exit. See
+    /// [`LocalExecutorPoolBuilder::watchdog`].
+    pub fn liveness(&self) -> Vec<ExecutorLiveness> {
+        self.watchdog
+            .as_ref()
+            .map(|watchdog| watchdog.liveness())
+            .unwrap_or_default()
+    }
+
+    /// Adds an executor to the running pool, and returns its id.
+    ///
+    /// The executor is configured like the others, by the same
+    /// [`LocalExecutorPoolBuilder`], and runs a future obtained from the same
+    /// closure. It is bound to the CPUs of an executor retired earlier that
+    /// has since exited if there is one, and otherwise to the next CPUs of the
+    /// [`PoolPlacement`] of the pool. The membership of the pool includes the executor by the
+    /// time its future starts.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the thread can't be spawned, or if every CPU of the placement
+    /// of the pool already has an executor: executors added to a
+    /// [`PoolPlacement::MaxSpread`] or [`PoolPlacement::MaxPack`] pool don't
+    /// share a CPU with another one, and a [`PoolPlacement::Custom`] pool
+    /// only has so many CPU sets.
+    pub fn add_shard(&mut self) -> Result<usize> {
+        let cpus = self
+            .retiring
+            .iter()
+            .position(|(id, _)| self.token.has_exited(*id))
+            .map(|idx| self.retiring.remove(idx).1);
+        self.spawn(cpus)
+    }
+
+    /// Like [`add_shard`], but binds the new executor according to
+    /// `placement` rather than to the placement of the pool. This is how
+    /// executors make use of CPUs the pool wasn't created with, such as CPUs
+    /// added to the cgroup of the process since.
+    ///
+    /// [`add_shard`]: PoolThreadHandles::add_shard
+    pub fn add_shard_on(&mut self, placement: Placement) -> Result<usize> {
+        let cpus = placement::CpuSetGenerator::one(placement, self.allowed_cpus_only)?.next();
+        self.spawn(Some(cpus))
+    }
+
+    fn spawn(&mut self, cpus: Option<placement::CpuIter>) -> Result<usize> {
+        let spawner = self
+            .spawner
+            .as_mut()
+            .expect("the pool was created by on_all_shards")
+            .get_mut()
+            .unwrap();
+        let (id, cpus, handle) = spawner(cpus)?;
+        self.push(id, cpus, handle);
+        Ok(id)
+    }
+
+    /// Retires the executor `executor_id` from the pool, and returns its
+    /// handle, or `None` if the pool has no such executor.
+    ///
+    /// The executor leaves the membership of the pool right away, and its
+    /// [`ShutdownToken`] is signaled: it is expected to stop taking new work
+    /// and return, like it would when the whole pool shuts down. It then keeps
+    /// running until all its tasks complete, so the work it has in flight is
+    /// drained. Unlike [`shutdown`](PoolThreadHandles::shutdown), there is no
+    /// deadline: join the returned handle to wait for the executor to exit.
+    ///
+    /// Once the executor exited, the CPUs it is bound to are reused by the
+    /// next executor added with [`add_shard`](PoolThreadHandles::add_shard).
+    pub fn retire(&mut self, executor_id: usize) -> Option<JoinHandle<Result<T>>> {
+        let idx = self.shards.iter().position(|(id, _)| *id == executor_id)?;
+        let shard = self.shards.remove(idx);
+        self.retiring.push(shard);
+        self.retired += 1;
+        self.membership.leave(executor_id);
+        self.token.retire(executor_id);
+        Some(self.handles.remove(idx))
+    }
+
     /// Calls [`JoinHandle::join`] on all handles.
     pub fn join_all(self) -> Vec<Result<T>> {
         self.handles
@@ -1055,34 +2290,193 @@ impl<T> PoolThreadHandles<T> {
             })
             .collect::<Vec<_>>()
     }
+
+    /// Shuts the pool down, and waits for all the shards to exit.
+    ///
+    /// This signals the [`ShutdownToken`] of the pool, which the future of
+    /// every shard is expected to watch and return upon. Each shard then keeps
+    /// running until all its tasks complete, so the work they have in flight
+    /// is drained.
+    ///
+    /// Shards that are still running after `timeout` have their tasks
+    /// canceled, including the future of the shard itself if it hasn't
+    /// returned yet, and are listed in [`ShutdownReport::missed_deadline`].
+    /// Tasks are only canceled in between polls: a shard stuck inside a poll
+    /// delays this call until that poll returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{LocalExecutorPoolBuilder, PoolPlacement};
+    /// use std::time::Duration;
+    ///
+    /// let handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+    ///     .on_all_shards(|| async move {
+    ///         let token = glommio::executor().shutdown_token().unwrap();
+    ///         token.wait().await;
+    ///         glommio::executor().id()
+    ///     })
+    ///     .unwrap();
+    ///
+    /// let report = handles.shutdown(Duration::from_secs(1));
+    /// assert!(report.missed_deadline.is_empty());
+    /// assert!(report.results.iter().all(|res| res.is_ok()));
+    /// ```
+    pub fn shutdown(self, timeout: Duration) -> ShutdownReport<T> {
+        let token = self.token.clone();
+        // retired shards count as exited too once they are
+        token.shutdown(self.handles.len() + self.retired, timeout);
+        let results = self.join_all();
+        ShutdownReport {
+            results,
+            missed_deadline: token.missed_deadline(),
+        }
+    }
 }
 
 pub(crate) fn maybe_activate(tq: Rc<RefCell<TaskQueue>>) {
     #[cfg(not(feature = "native-tls"))]
     LOCAL_EX.with(|local_ex| {
         let mut queues = local_ex.queues.borrow_mut();
-        queues.maybe_activate(tq);
+        if queues.maybe_activate(tq) {
+            local_ex.preempt_requested.set(true);
+        }
     });
 
     #[cfg(feature = "native-tls")]
     unsafe {
-        let mut queues = LOCAL_EX
+        let local_ex = LOCAL_EX
             .as_ref()
-            .expect("this thread doesn't have a LocalExecutor running")
-            .queues
-            .borrow_mut();
-        queues.maybe_activate(tq);
+            .expect("this thread doesn't have a LocalExecutor running");
+        let mut queues = local_ex.queues.borrow_mut();
+        if queues.maybe_activate(tq) {
+            local_ex.preempt_requested.set(true);
+        }
+    };
+}
+
+/// Called when a task panics. Returns the panic if the executor catches it, or
+/// the payload to resume unwinding with otherwise.
+pub(crate) fn task_panicked(
+    payload: Box<dyn Any + Send>,
+    label: Option<&'static str>,
+) -> std::result::Result<TaskPanic, Box<dyn Any + Send>> {
+    #[cfg(not(feature = "native-tls"))]
+    return if LOCAL_EX.is_set() {
+        LOCAL_EX.with(|local_ex| local_ex.task_panicked(payload, label))
+    } else {
+        Err(payload)
+    };
+
+    #[cfg(feature = "native-tls")]
+    return unsafe {
+        match LOCAL_EX.as_ref() {
+            Some(local_ex) => local_ex.task_panicked(payload, label),
+            None => Err(payload),
+        }
+    };
+}
+
+pub(crate) fn process_stealable_tasks() -> usize {
+    #[cfg(not(feature = "native-tls"))]
+    return if LOCAL_EX.is_set() {
+        LOCAL_EX.with(|local_ex| local_ex.process_stealable_tasks())
+    } else {
+        0
+    };
+
+    #[cfg(feature = "native-tls")]
+    return unsafe {
+        LOCAL_EX
+            .as_ref()
+            .map(|local_ex| local_ex.process_stealable_tasks())
+            .unwrap_or_default()
     };
 }
 
+/// Makes every poll of `future` enter the span current when this is called, so
+/// that what it does shows up in the trace of whatever spawned it. Spans are
+/// disabled without a subscriber interested in them, in which case `future` is
+/// polled as is.
+pub(crate) fn in_current_span<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let span = tracing::Span::current();
+    async move {
+        if span.is_disabled() {
+            future.await
+        } else {
+            future.instrument(span).await
+        }
+    }
+}
+
+/// Wraps a future sent from another thread into a closure that spawns it on
+/// whichever executor runs the closure. See [`LocalExecutor::spawn_foreign`].
+fn foreign_spawn<T, F>(
+    future: F,
+    handle: TaskQueueHandle,
+    sender: flume::Sender<Result<T>>,
+    location: &'static Location<'static>,
+) -> sys::ForeignSpawn
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let spawn = stealable_spawn(future, sender, location);
+    Box::new(move || spawn(handle))
+}
+
+/// Like [`foreign_spawn`], but the task queue is picked by the executor that
+/// ends up running the task.
+fn stealable_spawn<T, F>(
+    future: F,
+    sender: flume::Sender<Result<T>>,
+    location: &'static Location<'static>,
+) -> StealableSpawn
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    // capture the span here, as the executor the future is sent to has no
+    // idea where it comes from
+    let future = in_current_span(future);
+    Box::new(move |handle| {
+        #[cfg(not(feature = "native-tls"))]
+        LOCAL_EX.with(|local_ex| local_ex.spawn_foreign(future, handle, sender, location));
+
+        #[cfg(feature = "native-tls")]
+        unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .spawn_foreign(future, handle, sender, location)
+        };
+    })
+}
+
 pub struct LocalExecutorConfig {
     pub io_memory: usize,
+    pub io_memory_huge_pages: bool,
+    pub numa_node: Option<usize>,
     pub ring_depth: usize,
     pub preempt_timer: Duration,
     pub record_io_latencies: bool,
+    pub record_scheduling_delays: bool,
+    pub record_task_stats: bool,
     pub spin_before_park: Option<Duration>,
     pub thread_pool_placement: PoolPlacement,
+    pub internal_thread_pool_placement: Option<PoolPlacement>,
+    pub blocking_queue_capacity: usize,
+    pub blocking_queue_policy: BlockingQueuePolicy,
     pub detect_stalls: Option<Box<dyn stall::StallDetectionHandler + 'static>>,
+    pub stealable_tasks: Option<Arc<StealableQueue>>,
+    pub shutdown: Option<ShutdownToken>,
+    pub membership: Option<PoolMembership>,
+    pub panic_policy: PanicPolicy,
+    pub panic_hook: Option<PanicHook>,
+    pub loop_hooks: LoopHooks,
+    pub heartbeat: Option<Arc<Heartbeat>>,
+    pub simulation_seed: Option<u64>,
+    pub clock: Option<TestClock>,
 }
 
 /// Single-threaded executor.
@@ -1116,6 +2510,26 @@ pub struct LocalExecutor {
     id: usize,
     reactor: Rc<reactor::Reactor>,
     stall_detector: RefCell<Option<StallDetector>>,
+    stealable_tasks: Option<Arc<StealableQueue>>,
+    shutdown: Option<ShutdownToken>,
+    membership: Option<PoolMembership>,
+    record_scheduling_delays: bool,
+    // what to measure about the tasks spawned on this executor
+    task_timing: task::Timing,
+    panic_policy: PanicPolicy,
+    panic_hook: Option<PanicHook>,
+    loop_hooks: LoopHooks,
+    // the liveness published to the watchdog of the pool, if it has one
+    heartbeat: Option<Arc<Heartbeat>>,
+    // the stats the application read, which reset them, so that snapshots
+    // can add them back
+    read_stats: RefCell<ExecutorMetrics>,
+    simulation: Option<Simulation>,
+    // set when a task queue with an earlier deadline than the one executing
+    // becomes runnable
+    preempt_requested: Cell<bool>,
+    // restores the memory policy of the thread once everything else is gone
+    _preferred_node: Option<sys::numa::PreferredNode>,
 }
 
 impl LocalExecutor {
@@ -1125,13 +2539,17 @@ impl LocalExecutor {
 
     fn init(&mut self) {
         let io_requirements = IoRequirements::new(Latency::NotImportant, 0);
-        self.queues.borrow_mut().available_executors.insert(
+        let mut queues = self.queues.borrow_mut();
+        let live_tasks = queues.live_tasks.clone();
+        queues.available_executors.insert(
             0,
             TaskQueue::new(
                 Default::default(),
                 "default",
                 Shares::Static(1000),
                 io_requirements,
+                None,
+                live_tasks,
             ),
         );
     }
@@ -1141,8 +2559,13 @@ impl LocalExecutor {
         cpu_binding: Option<impl IntoIterator<Item = usize>>,
         mut config: LocalExecutorConfig,
     ) -> Result<LocalExecutor> {
-        let blocking_thread =
-            BlockingThreadPool::new(config.thread_pool_placement, notifier.clone())?;
+        let blocking_thread = BlockingThreadPool::new(
+            config.thread_pool_placement,
+            config.internal_thread_pool_placement,
+            config.blocking_queue_capacity,
+            config.blocking_queue_policy,
+            notifier.clone(),
+        )?;
 
         // Linux's default memory policy is "local allocation" which allocates memory
         // on the NUMA node containing the CPU where the allocation takes place.
@@ -1156,19 +2579,35 @@ impl LocalExecutor {
             Some(cpu_set) => bind_to_cpu_set(cpu_set)?,
             None => config.spin_before_park = None,
         }
+        // Local allocation only goes as far as the CPU the memory happens to be
+        // touched from, so settle the node of the CPU set for as long as the
+        // executor runs on this thread when there is one.
+        let preferred_node = config.numa_node.and_then(sys::numa::PreferredNode::set);
         let p = parking::Parker::new();
         let queues = ExecutorQueues::new(config.preempt_timer, config.spin_before_park);
         let id = notifier.id();
         trace!(id = id, "Creating executor");
+        if let Some(stealable_tasks) = &config.stealable_tasks {
+            stealable_tasks.register(&notifier);
+        }
+        let clock = config
+            .clock
+            .or_else(|| config.simulation_seed.map(|_| TestClock::auto_advancing()));
         Ok(LocalExecutor {
             queues: Rc::new(RefCell::new(queues)),
             parker: p,
             id,
             reactor: Rc::new(reactor::Reactor::new(
                 notifier,
-                config.io_memory,
-                config.ring_depth,
+                sys::ReactorConfig {
+                    io_memory: config.io_memory,
+                    io_memory_huge_pages: config.io_memory_huge_pages,
+                    numa_node: config.numa_node,
+                    ring_depth: config.ring_depth,
+                    hold_completions: config.simulation_seed.is_some(),
+                },
                 config.record_io_latencies,
+                clock,
                 blocking_thread,
             )?),
             stall_detector: RefCell::new(
@@ -1177,9 +2616,53 @@ impl LocalExecutor {
                     .map(|x| StallDetector::new(id, x))
                     .transpose()?,
             ),
+            stealable_tasks: config.stealable_tasks,
+            shutdown: config.shutdown,
+            membership: config.membership,
+            record_scheduling_delays: config.record_scheduling_delays,
+            task_timing: task::Timing {
+                poll_stats: config.record_task_stats,
+                scheduled_at: config.record_task_stats || config.record_scheduling_delays,
+            },
+            panic_policy: config.panic_policy,
+            panic_hook: config.panic_hook,
+            loop_hooks: config.loop_hooks,
+            heartbeat: config.heartbeat,
+            read_stats: RefCell::new(ExecutorMetrics::new(id)),
+            simulation: config.simulation_seed.map(Simulation::new),
+            preempt_requested: Cell::new(false),
+            _preferred_node: preferred_node,
         })
     }
 
+    /// Reports a task panic to the hook, and applies the panic policy to it.
+    fn task_panicked(
+        &self,
+        payload: Box<dyn Any + Send>,
+        label: Option<&'static str>,
+    ) -> std::result::Result<TaskPanic, Box<dyn Any + Send>> {
+        let task_queue_name = self
+            .queues
+            .borrow()
+            .active_executing
+            .as_ref()
+            .map(|queue| queue.borrow().name.clone())
+            .unwrap_or_default();
+        let panic = TaskPanic::new(payload, label.map(String::from), task_queue_name, self.id);
+        if let Some(hook) = &self.panic_hook {
+            (hook.0)(&panic);
+        }
+
+        match self.panic_policy {
+            PanicPolicy::Abort => {
+                log::error!("{panic}, aborting");
+                std::process::abort()
+            }
+            PanicPolicy::Propagate => Err(panic.into_payload()),
+            PanicPolicy::Catch => Ok(panic),
+        }
+    }
+
     /// Enable or disable task stall detection at runtime
     ///
     /// # Examples
@@ -1218,42 +2701,118 @@ impl LocalExecutor {
     where
         S: Into<String>,
     {
-        let index = {
-            let mut ex = self.queues.borrow_mut();
-            let index = ex.executor_index;
-            ex.executor_index += 1;
-            index
+        self.create_task_queue_in(None, shares, latency, name)
+            .expect("task queues without a parent can always be created")
+    }
+
+    fn create_edf_task_queue<S>(
+        &self,
+        deadline: Duration,
+        latency: Latency,
+        name: S,
+    ) -> TaskQueueHandle
+    where
+        S: Into<String>,
+    {
+        let handle = self.create_task_queue(Shares::default(), latency, name);
+        self.get_queue(&handle)
+            .expect("the task queue was just created")
+            .borrow_mut()
+            .relative_deadline = Some(deadline);
+        handle
+    }
+
+    fn create_task_queue_in<S>(
+        &self,
+        parent: Option<TaskQueueHandle>,
+        shares: Shares,
+        latency: Latency,
+        name: S,
+    ) -> Result<TaskQueueHandle>
+    where
+        S: Into<String>,
+    {
+        let mut ex = self.queues.borrow_mut();
+        let parent_queue = match parent {
+            Some(parent) => Some(
+                ex.available_executors
+                    .get(&parent.index)
+                    .cloned()
+                    .ok_or_else(|| GlommioError::queue_not_found(parent.index))?,
+            ),
+            None => None,
         };
 
+        let index = ex.executor_index;
+        ex.executor_index += 1;
+
         let io_requirements = IoRequirements::new(latency, index);
-        let tq = TaskQueue::new(TaskQueueHandle { index }, name, shares, io_requirements);
+        let tq = TaskQueue::new(
+            TaskQueueHandle { index },
+            name,
+            shares,
+            io_requirements,
+            parent,
+            ex.live_tasks.clone(),
+        );
 
-        self.queues
-            .borrow_mut()
-            .available_executors
-            .insert(index, tq);
-        TaskQueueHandle { index }
+        if let Some(parent_queue) = parent_queue {
+            parent_queue
+                .borrow_mut()
+                .children
+                .push(TaskQueueHandle { index });
+        }
+        ex.available_executors.insert(index, tq);
+        Ok(TaskQueueHandle { index })
     }
 
     /// Removes a task queue.
     ///
-    /// The task queue cannot be removed if there are still pending tasks.
+    /// The task queue cannot be removed if there are still pending tasks, or
+    /// if it still has children. Both cases are reported as
+    /// [`QueueErrorKind::StillActive`](crate::QueueErrorKind::StillActive).
     pub fn remove_task_queue(&self, handle: TaskQueueHandle) -> Result<()> {
         let mut queues = self.queues.borrow_mut();
 
         let queue_entry = queues.available_executors.entry(handle.index);
         if let Entry::Occupied(entry) = queue_entry {
-            let tq = entry.get();
-            if tq.borrow().is_active() {
+            let tq = entry.get().borrow();
+            if tq.is_active() {
+                return Err(GlommioError::queue_still_active(handle.index));
+            }
+            if !tq.children.is_empty() {
                 return Err(GlommioError::queue_still_active(handle.index));
             }
 
+            let parent = tq.parent;
+            drop(tq);
             entry.remove();
+            self.read_stats.borrow_mut().remove_task_queue(handle);
+            if let Some(parent) = parent.and_then(|p| queues.available_executors.get(&p.index)) {
+                parent
+                    .borrow_mut()
+                    .children
+                    .retain(|child| *child != handle);
+            }
             return Ok(());
         }
         Err(GlommioError::queue_not_found(handle.index))
     }
 
+    fn set_task_queue_poll_budget(
+        &self,
+        handle: TaskQueueHandle,
+        budget: Option<u32>,
+    ) -> Result<()> {
+        match self.get_queue(&handle) {
+            Some(tq) => {
+                tq.borrow_mut().poll_budget = budget;
+                Ok(())
+            }
+            None => Err(GlommioError::queue_not_found(handle.index)),
+        }
+    }
+
     fn get_queue(&self, handle: &TaskQueueHandle) -> Option<Rc<RefCell<TaskQueue>>> {
         self.queues
             .borrow()
@@ -1262,25 +2821,149 @@ impl LocalExecutor {
             .cloned()
     }
 
-    fn current_task_queue(&self) -> TaskQueueHandle {
-        self.queues
-            .borrow()
-            .active_executing
-            .as_ref()
-            .unwrap()
-            .borrow()
-            .stats
-            .index
+    fn dump_tasks(&self) -> Vec<TaskDump> {
+        let live_tasks = self.queues.borrow().live_tasks.clone();
+        let now = Instant::now();
+        let mut dump = Vec::with_capacity(live_tasks.count());
+        live_tasks.for_each(|tracked| {
+            // not allocated yet
+            let task = match tracked.task {
+                Some(task) => task,
+                None => return,
+            };
+            dump.push(TaskDump {
+                executor_id: self.id,
+                task_queue: tracked.task_queue,
+                task_queue_name: self
+                    .get_queue(&tracked.task_queue)
+                    .map(|tq| tq.borrow().name.clone())
+                    .unwrap_or_default(),
+                label: task.label(),
+                state: task.state(),
+                detached: task.is_detached(),
+                spawn_location: tracked.spawn_location,
+                age: now.saturating_duration_since(tracked.spawned_at),
+                last_poll: task.last_poll(),
+                stats: task.stats(),
+            });
+        });
+        // oldest first
+        dump.sort_by_key(|t| std::cmp::Reverse(t.age));
+        dump
     }
 
-    fn mark_me_for_yield(&self) {
+    fn metrics(&self) -> ExecutorMetrics {
+        let reactor = self.get_reactor();
         let queues = self.queues.borrow();
-        let mut me = queues.active_executing.as_ref().unwrap().borrow_mut();
-        me.yielded = true;
+        let mut task_queues: Vec<_> = queues
+            .available_executors
+            .values()
+            .map(|tq| {
+                let tq = tq.borrow();
+                TaskQueueMetrics {
+                    name: tq.name.clone(),
+                    io: reactor.peek_task_queue_io_stats(&tq.stats.index),
+                    stats: tq.stats,
+                    scheduling_delay_us: tq.scheduling_delay_us.clone(),
+                }
+            })
+            .collect();
+        task_queues.sort_by_key(|tq| tq.stats.index.index);
+
+        let mut metrics = self.read_stats.borrow().clone();
+        metrics.accumulate(&queues.stats, &reactor.peek_io_stats(), task_queues);
+        metrics
     }
 
-    fn spawn<T>(&self, future: impl Future<Output = T>) -> multitask::Task<T> {
-        let tq = self
+    /// Takes the stats of the executor, remembering them for the snapshots
+    /// returned by [`Self::metrics`]. So do the other `take_*` methods.
+    fn take_executor_stats(&self) -> ExecutorStats {
+        let stats = std::mem::take(&mut self.queues.borrow_mut().stats);
+        self.read_stats.borrow_mut().executor.accumulate(&stats);
+        stats
+    }
+
+    fn take_io_stats(&self) -> IoStats {
+        let stats = self.get_reactor().io_stats();
+        self.read_stats.borrow_mut().io.accumulate(&stats);
+        stats
+    }
+
+    fn take_task_queue_stats(&self, tq: &RefCell<TaskQueue>) -> TaskQueueStats {
+        let mut tq = tq.borrow_mut();
+        let stats = tq.stats.take();
+        self.read_stats
+            .borrow_mut()
+            .task_queue_mut(&tq.name, &stats)
+            .stats
+            .accumulate(&stats);
+        stats
+    }
+
+    fn take_task_queue_io_stats(&self, handle: TaskQueueHandle) -> Option<IoStats> {
+        let stats = self.get_reactor().task_queue_io_stats(&handle)?;
+        if let Some(tq) = self.get_queue(&handle) {
+            let tq = tq.borrow();
+            self.read_stats
+                .borrow_mut()
+                .task_queue_mut(&tq.name, &tq.stats)
+                .io
+                .get_or_insert_with(Default::default)
+                .accumulate(&stats);
+        }
+        Some(stats)
+    }
+
+    fn take_scheduling_delays(&self, tq: &RefCell<TaskQueue>) -> DDSketch {
+        let mut tq = tq.borrow_mut();
+        let delays = tq.take_scheduling_delays();
+        self.read_stats
+            .borrow_mut()
+            .task_queue_mut(&tq.name, &tq.stats)
+            .scheduling_delay_us
+            .merge(&delays)
+            .unwrap();
+        delays
+    }
+
+    fn stall_stats(&self) -> Vec<TaskQueueStallStats> {
+        self.stall_detector
+            .borrow()
+            .as_ref()
+            .map(|detector| detector.stall_stats())
+            .unwrap_or_default()
+    }
+
+    fn pool_executor_ids(&self) -> Vec<usize> {
+        match &self.stealable_tasks {
+            Some(stealable_tasks) => stealable_tasks.member_ids(),
+            None => vec![self.id],
+        }
+    }
+
+    fn current_task_queue(&self) -> TaskQueueHandle {
+        self.queues
+            .borrow()
+            .active_executing
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .stats
+            .index
+    }
+
+    fn mark_me_for_yield(&self) {
+        let queues = self.queues.borrow();
+        let mut me = queues.active_executing.as_ref().unwrap().borrow_mut();
+        me.yielded = true;
+    }
+
+    fn spawn<T>(
+        &self,
+        future: impl Future<Output = T>,
+        location: &'static Location<'static>,
+    ) -> multitask::Task<T> {
+        let tq = self
             .queues
             .borrow()
             .active_executing
@@ -1290,10 +2973,15 @@ impl LocalExecutor {
 
         let id = self.id;
         let ex = tq.borrow().ex.clone();
-        ex.spawn_and_run(id, tq, future)
+        ex.spawn_and_run(id, self.task_timing, tq, future, location)
     }
 
-    fn spawn_into<T, F>(&self, future: F, handle: TaskQueueHandle) -> Result<multitask::Task<T>>
+    fn spawn_into<T, F>(
+        &self,
+        future: F,
+        handle: TaskQueueHandle,
+        location: &'static Location<'static>,
+    ) -> Result<multitask::Task<T>>
     where
         F: Future<Output = T>,
     {
@@ -1304,20 +2992,163 @@ impl LocalExecutor {
         let id = self.id;
 
         // can't run right away, because we need to cross into a different task queue
-        Ok(ex.spawn_and_schedule(id, tq, future))
+        Ok(ex.spawn_and_schedule(id, self.task_timing, tq, future, location))
+    }
+
+    /// Spawns a future that was sent from another thread, reporting its
+    /// output (or the failure to find its task queue) through `sender`.
+    ///
+    /// This runs from within the reactor, so it must not drop the future:
+    /// if the queue is gone, the future is handed to the default queue and
+    /// dropped from there.
+    fn spawn_foreign<T, F>(
+        &self,
+        future: F,
+        handle: TaskQueueHandle,
+        sender: flume::Sender<Result<T>>,
+        location: &'static Location<'static>,
+    ) where
+        F: Future<Output = T> + 'static,
+        T: 'static,
+    {
+        let task = if self.get_queue(&handle).is_some() {
+            self.spawn_into(
+                async move {
+                    let _ = sender.send(Ok(future.await));
+                },
+                handle,
+                location,
+            )
+        } else {
+            self.spawn_into(
+                async move {
+                    drop(future);
+                    let _ = sender.send(Err(GlommioError::queue_not_found(handle.index)));
+                },
+                TaskQueueHandle::default(),
+                location,
+            )
+        };
+
+        if let Ok(task) = task {
+            task.detach();
+        }
+    }
+
+    fn spawn_stealable<T, F>(
+        &self,
+        future: F,
+        handle: TaskQueueHandle,
+        location: &'static Location<'static>,
+    ) -> Result<RemoteJoinHandle<T>>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let class = match self.get_queue(&handle) {
+            Some(queue) => queue.borrow().class(),
+            None => return Err(GlommioError::queue_not_found(handle.index)),
+        };
+
+        let (sender, receiver) = flume::bounded(1);
+        let executor_id = Arc::new(AtomicUsize::new(self.id));
+        match &self.stealable_tasks {
+            Some(stealable_tasks) => stealable_tasks.push(StealableTask {
+                origin: self.id,
+                handle,
+                class,
+                executor_id: executor_id.clone(),
+                spawn: stealable_spawn(future, sender, location),
+                rejected_by: Vec::new(),
+            }),
+            // not part of a pool, so there is nobody to steal from us
+            None => self.spawn_foreign(future, handle, sender, location),
+        }
+
+        Ok(RemoteJoinHandle {
+            executor_id,
+            receiver: receiver.into_recv_async(),
+        })
+    }
+
+    /// Takes up to [`STEAL_BATCH`] tasks from the pool's stealable queue and
+    /// spawns them. Returns the number of tasks spawned.
+    fn process_stealable_tasks(&self) -> usize {
+        let stealable_tasks = match &self.stealable_tasks {
+            Some(stealable_tasks) => stealable_tasks,
+            None => return 0,
+        };
+
+        let tasks = stealable_tasks.steal(self.id, STEAL_BATCH, |task| self.stealable_queue(task));
+        let spawned = tasks.len();
+        for (task, handle) in tasks {
+            task.executor_id.store(self.id, Ordering::Relaxed);
+            (task.spawn)(handle);
+        }
+        spawned
+    }
+
+    /// Returns the task queue a stealable task should be spawned into on this
+    /// executor: the one it was spawned into if we are the executor that
+    /// spawned it, otherwise the oldest task queue of the same class.
+    fn stealable_queue(&self, task: &StealableTask) -> Option<TaskQueueHandle> {
+        if task.origin == self.id && self.get_queue(&task.handle).is_some() {
+            return Some(task.handle);
+        }
+        self.queues
+            .borrow()
+            .available_executors
+            .iter()
+            .filter(|(_, queue)| queue.borrow().class().matches(&task.class))
+            .map(|(index, _)| *index)
+            .min()
+            .map(|index| TaskQueueHandle { index })
     }
 
     fn preempt_timer_duration(&self) -> Duration {
         self.queues.borrow().preempt_timer_duration
     }
 
+    // The output of the main future is `None` only if it panicked, as it can't
+    // be canceled. Resume the panic if the executor caught it.
+    fn unwrap_output<T>(output: Option<T>, future: &mut Pin<&mut task::JoinHandle<T>>) -> T {
+        match output {
+            Some(output) => output,
+            None => match future.take_panic() {
+                Some(panic) => std::panic::resume_unwind(panic.into_payload()),
+                None => unreachable!("the main future of an executor can't be canceled"),
+            },
+        }
+    }
+
     fn spin_before_park(&self) -> Option<Duration> {
         self.queues.borrow().spin_before_park
     }
 
     #[inline(always)]
     pub(crate) fn need_preempt(&self) -> bool {
-        self.reactor.need_preempt()
+        match &self.simulation {
+            // the preempt timer and deadlines are both real time
+            Some(simulation) => simulation.should_preempt(),
+            None => self.reactor.need_preempt() || self.preempt_requested.get(),
+        }
+    }
+
+    /// What the stall detector reports about `runnable` if it stalls while
+    /// being polled
+    fn stalled_task(&self, runnable: &multitask::Runnable) -> Option<StalledTask> {
+        let spawn_location = self
+            .queues
+            .borrow()
+            .live_tasks
+            .spawn_location(runnable.id())?;
+        Some(StalledTask {
+            id: runnable.id(),
+            label: runnable.label(),
+            spawn_location,
+            poll_start: Instant::now(),
+            poll_duration: Duration::ZERO,
+        })
     }
 
     fn run_task_queues(&self) -> bool {
@@ -1338,7 +3169,10 @@ impl LocalExecutor {
 
     fn run_one_task_queue(&self) -> bool {
         let mut tq = self.queues.borrow_mut();
-        let candidate = tq.active_executors.pop();
+        let candidate = match &self.simulation {
+            Some(simulation) => simulation.pick_task_queue(&mut tq.active_executors),
+            None => tq.active_executors.pop(),
+        };
         tq.stats.scheduler_runs += 1;
 
         if candidate.is_none() {
@@ -1349,18 +3183,30 @@ impl LocalExecutor {
         tq.active_executing = Some(queue.clone());
         drop(tq);
 
-        let time = {
+        let (time, span) = {
             let now = Instant::now();
             let mut queue_ref = queue.borrow_mut();
             queue_ref.prepare_to_run(now);
             self.reactor
                 .inform_io_requirements(queue_ref.io_requirements);
-            now
+            // only entered to emit events: tasks spawned while it's entered
+            // would capture it, and keep it open for as long as they live
+            let span = trace_span!(
+                "task_queue",
+                executor = self.id,
+                task_queue = queue_ref.stats.index.index,
+                name = %queue_ref.name,
+            );
+            span.in_scope(|| trace!("task queue selected"));
+            if let Some(heartbeat) = &self.heartbeat {
+                heartbeat.set_task_queue(Some(queue_ref.stats.index));
+            }
+            (now, span)
         };
 
         let (runtime, tasks_executed_this_loop) = {
             let detector = self.stall_detector.borrow();
-            let guard = detector.as_ref().map(|x| {
+            let mut guard = detector.as_ref().and_then(|x| {
                 let queue = queue.borrow_mut();
                 x.enter_task_queue(
                     queue.stats.index,
@@ -1371,31 +3217,66 @@ impl LocalExecutor {
             });
 
             let mut tasks_executed_this_loop = 0;
+            let mut preempted;
             loop {
                 let mut queue_ref = queue.borrow_mut();
-                if self.need_preempt() || queue_ref.yielded() {
+                preempted = self.need_preempt();
+                if preempted || queue_ref.yielded() {
                     break;
                 }
 
-                if let Some(r) = queue_ref.get_task() {
+                let runnable = match &self.simulation {
+                    Some(simulation) => queue_ref.ex.pick_task(simulation),
+                    None => queue_ref.get_task(),
+                };
+                if let Some(r) = runnable {
+                    if self.record_scheduling_delays {
+                        if let Some(scheduled_at) = r.scheduled_at() {
+                            let now = Instant::now();
+                            let delay = now.saturating_duration_since(scheduled_at);
+                            queue_ref.scheduling_delay_us.add(delay.as_micros() as f64);
+                            r.set_last_poll(now);
+                        }
+                    }
+                    let poll_budget = queue_ref.poll_budget;
                     drop(queue_ref);
-                    r.run();
+                    let polled = guard.as_ref().and_then(|_| self.stalled_task(&r));
+                    coop::with_budget(poll_budget, || r.run());
+                    if let (Some(guard), Some(task)) = (guard.as_mut(), polled) {
+                        guard.task_polled(task);
+                    }
                     tasks_executed_this_loop += 1;
                 } else {
                     break;
                 }
             }
+            // the queue that asked for it will be picked next
+            self.preempt_requested.set(false);
             let elapsed = time.elapsed();
+            span.in_scope(|| {
+                trace!(
+                    tasks = tasks_executed_this_loop,
+                    runtime = ?elapsed,
+                    preempted,
+                    "task queue ran"
+                )
+            });
             drop(guard);
             (elapsed, tasks_executed_this_loop)
         };
 
         let (need_repush, vruntime) = {
+            let queues = self.queues.borrow();
+            let reciprocal_shares = queues.effective_reciprocal_shares(&queue.borrow());
             let mut state = queue.borrow_mut();
-            let last_vruntime = state.account_vruntime(runtime);
+            let last_vruntime = state.account_vruntime(runtime, reciprocal_shares);
+            queues.account_subtree_runtime(state.parent, runtime);
             (state.is_active(), last_vruntime)
         };
 
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.set_task_queue(None);
+        }
         let mut tq = self.queues.borrow_mut();
         tq.active_executing = None;
         tq.stats.executor_runtime += runtime;
@@ -1420,15 +3301,72 @@ impl LocalExecutor {
         // Compute the smallest vruntime out of all the active task queues
         // This value is used to set the vruntime of deactivated task queues when they
         // are woken up.
-        tq.default_vruntime = tq
-            .active_executors
-            .peek()
-            .map(|x| x.borrow().vruntime)
-            .unwrap_or(vruntime);
+        tq.default_vruntime = tq.min_vruntime().unwrap_or(vruntime);
 
         true
     }
 
+    /// In simulation, time only passes when there is nothing to run: I/O
+    /// completes, in an order that depends on the seed, then timers fire as
+    /// soon as the executor would otherwise wait for them, and it only parks
+    /// to wait for I/O that may never complete when there are none.
+    fn simulate_idle(&self, simulation: &Simulation) {
+        if !self.queues.borrow().active_executors.is_empty() {
+            // preempted, not idle
+            return;
+        }
+        if self.reactor.settle_io().unwrap() {
+            return;
+        }
+        if simulation.release_io(&self.reactor) > 0 {
+            return;
+        }
+        if !self.auto_advance_clock() {
+            self.park();
+        }
+    }
+
+    /// Sleeps until there is I/O, a timer or a wake up from another thread,
+    /// calling the park hooks around it.
+    fn park(&self) {
+        // tasks pushed to the pool's stealable queue only wake up executors
+        // that are already asleep, so pick them up rather than sleeping on
+        // them
+        if self.process_stealable_tasks() > 0 {
+            return;
+        }
+        if let Some(hook) = &self.loop_hooks.before_park {
+            hook();
+            // the hook may have woken tasks up, and they'd wait for the next
+            // event if we parked now
+            if !self.queues.borrow().active_executors.is_empty() {
+                return;
+            }
+        }
+        let start = Instant::now();
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.park();
+        }
+        trace_span!("park", executor = self.id).in_scope(|| {
+            self.parker
+                .park()
+                .expect("Failed to park! This is actually pretty bad!");
+            trace!(parked = ?start.elapsed(), "executor unparked");
+        });
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.unpark(Instant::now());
+        }
+        if let Some(hook) = &self.loop_hooks.after_unpark {
+            hook(start.elapsed());
+        }
+    }
+
+    /// Moves an auto-advancing test clock forward to the next timer if there
+    /// is nothing to run, as waiting for it would. Returns whether it did.
+    fn auto_advance_clock(&self) -> bool {
+        self.queues.borrow().active_executors.is_empty() && self.reactor.advance_to_next_timer()
+    }
+
     /// Runs the executor until the given future completes.
     ///
     /// # Examples
@@ -1445,7 +3383,9 @@ impl LocalExecutor {
     ///
     /// assert_eq!(res, 6);
     /// ```
+    #[track_caller]
     pub fn run<T>(&self, future: impl Future<Output = T>) -> T {
+        let location = Location::caller();
         let run = |this: &Self| {
             // this waker is never exposed in the public interface and is only used to check
             // whether the task's `JoinHandle` is `Ready`
@@ -1455,19 +3395,22 @@ impl LocalExecutor {
             let spin_before_park = self.spin_before_park().unwrap_or_default();
 
             let future = this
-                .spawn_into(future, TaskQueueHandle::default())
+                .spawn_into(future, TaskQueueHandle::default(), location)
                 .unwrap()
                 .detach();
             pin!(future);
 
             let mut pre_time = Instant::now();
+            if let Some(heartbeat) = &this.heartbeat {
+                heartbeat.start(pre_time);
+            }
             loop {
                 if let Poll::Ready(t) = future.as_mut().poll(cx) {
                     // can't be canceled, and join handle is None only upon
                     // cancellation or panic. So in case of panic this just propagates
                     let cur_time = Instant::now();
                     this.queues.borrow_mut().stats.total_runtime += cur_time - pre_time;
-                    break t.unwrap();
+                    break Self::unwrap_output(t, &mut future);
                 }
 
                 // We want to do I/O before we call run_task_queues,
@@ -1481,25 +3424,35 @@ impl LocalExecutor {
 
                 // run user code
                 let run = this.run_task_queues();
+                if let Some(hook) = &this.loop_hooks.scheduler_pass {
+                    hook();
+                }
 
                 // account for runtime and poll/sleep if possible
                 let cur_time = Instant::now();
                 this.queues.borrow_mut().stats.total_runtime += cur_time - pre_time;
                 pre_time = cur_time;
+                if let Some(heartbeat) = &this.heartbeat {
+                    heartbeat.beat(cur_time);
+                }
                 if !run {
                     if let Poll::Ready(t) = future.as_mut().poll(cx) {
                         // It may be that we just became ready now that the task queue
                         // is exhausted. But if we sleep (park) we'll never know so we
                         // test again here. We can't test *just* here because the main
                         // future is probably the one setting up the task queues and etc.
-                        break t.unwrap();
+                        break Self::unwrap_output(t, &mut future);
                     } else {
-                        while !this.reactor.spin_poll_io().unwrap() {
-                            if pre_time.elapsed() > spin_before_park {
-                                this.parker
-                                    .park()
-                                    .expect("Failed to park! This is actually pretty bad!");
-                                break;
+                        if let Some(simulation) = &this.simulation {
+                            this.simulate_idle(simulation);
+                        } else {
+                            while !this.reactor.spin_poll_io().unwrap() {
+                                if pre_time.elapsed() > spin_before_park {
+                                    if !this.auto_advance_clock() {
+                                        this.park();
+                                    }
+                                    break;
+                                }
                             }
                         }
                         // reset the timer for deduct spin loop time
@@ -1568,8 +3521,10 @@ impl Default for LocalExecutor {
 /// cancel a task a bit more gracefully and wait until it stops running, use the
 /// [`cancel()`][`Task::cancel()`] method.
 ///
-/// Tasks that panic get immediately canceled. Awaiting a canceled task also
-/// causes a panic.
+/// Tasks that panic get immediately canceled. Unless the executor catches
+/// panics, as configured with [`PanicPolicy`], the panic then tears down the
+/// executor. Awaiting a canceled task also causes a panic; use
+/// [`join()`][`Task::join()`] to get the panic of a task instead.
 ///
 /// # Examples
 ///
@@ -1682,6 +3637,63 @@ impl<T> Task<T> {
     pub async fn cancel(self) -> Option<T> {
         self.0.cancel().await
     }
+
+    /// Waits for the task to complete, returning the panic it raised if it
+    /// panicked instead.
+    ///
+    /// Panics can only be observed when the executor catches them, as
+    /// configured with [`PanicPolicy::Catch`]. Otherwise, they tear down the
+    /// executor and this never returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{LocalExecutorBuilder, PanicPolicy};
+    ///
+    /// let ex = LocalExecutorBuilder::default()
+    ///     .panic_policy(PanicPolicy::Catch)
+    ///     .make()
+    ///     .unwrap();
+    ///
+    /// ex.run(async {
+    ///     let task = glommio::spawn_local(async { panic!("oops") });
+    ///     let panic = task.join().await.unwrap_err();
+    ///     assert_eq!(panic.message(), Some("oops"));
+    /// });
+    /// ```
+    pub async fn join(self) -> std::result::Result<T, TaskPanic> {
+        self.0.join().await
+    }
+
+    /// Returns statistics about the polls of the task so far, such as how
+    /// long it ran for. The stats are only recorded by executors built with
+    /// [`LocalExecutorBuilder::record_task_stats`], and are empty otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::LocalExecutorBuilder;
+    ///
+    /// let ex = LocalExecutorBuilder::default()
+    ///     .record_task_stats(true)
+    ///     .make()
+    ///     .unwrap();
+    ///
+    /// ex.run(async {
+    ///     let task = glommio::spawn_local(async {});
+    ///     glommio::executor().yield_task_queue_now().await;
+    ///     assert_eq!(task.stats().polls(), 1);
+    /// });
+    /// ```
+    pub fn stats(&self) -> task::TaskStats {
+        self.0.stats()
+    }
+
+    /// Sets a label identifying the task in the snapshots returned by
+    /// [`ExecutorProxy::dump_tasks`].
+    pub fn set_label(&self, label: &'static str) {
+        self.0.set_label(label)
+    }
 }
 
 impl<T> Future for Task<T> {
@@ -1838,6 +3850,12 @@ impl<T> Future for Task<T> {
 pub struct ScopedTask<'a, T>(multitask::Task<T>, PhantomData<&'a T>);
 
 impl<'a, T> ScopedTask<'a, T> {
+    /// Returns statistics about the polls of the task so far. See
+    /// [`Task::stats`] for details.
+    pub fn stats(&self) -> task::TaskStats {
+        self.0.stats()
+    }
+
     /// Cancels the task and waits for it to stop running.
     ///
     /// Returns the task's output if it was completed just before it got
@@ -1881,6 +3899,59 @@ impl<'a, T> Future for ScopedTask<'a, T> {
     }
 }
 
+/// A handle to a task that may run on another executor, spawned with
+/// [`ExecutorProxy::spawn_on`], [`ExecutorProxy::spawn_on_into`],
+/// [`ExecutorProxy::spawn_stealable`] or
+/// [`ExecutorProxy::spawn_stealable_into`].
+///
+/// The handle is a future that resolves to the output of the remote task. It
+/// can be awaited from any executor, and dropping it does not cancel the
+/// remote task.
+///
+/// If the executor running the task exits before the task completes, the
+/// handle resolves to an [`ExecutorErrorKind::InvalidId`] error. If the
+/// requested task queue doesn't exist on the remote executor, it resolves to
+/// a [`QueueErrorKind::NotFound`] error.
+///
+/// [`QueueErrorKind::NotFound`]: crate::QueueErrorKind::NotFound
+#[must_use = "remote tasks keep running when their handle is dropped, but their output is lost"]
+pub struct RemoteJoinHandle<T: 'static> {
+    // updated when a stealable task is picked up by another executor
+    executor_id: Arc<AtomicUsize>,
+    receiver: flume::r#async::RecvFut<'static, Result<T>>,
+}
+
+impl<T: 'static> RemoteJoinHandle<T> {
+    /// Returns the id of the executor the task runs on
+    ///
+    /// Stealable tasks report the executor that spawned them until another
+    /// executor of the pool picks them up.
+    pub fn executor_id(&self) -> usize {
+        self.executor_id.load(Ordering::Relaxed)
+    }
+}
+
+impl<T: 'static> fmt::Debug for RemoteJoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteJoinHandle")
+            .field("executor_id", &self.executor_id())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: 'static> Future for RemoteJoinHandle<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let executor_id = self.executor_id();
+        Pin::new(&mut self.receiver).poll(cx).map(|res| {
+            res.unwrap_or(Err(GlommioError::ExecutorError(
+                ExecutorErrorKind::InvalidId(executor_id),
+            )))
+        })
+    }
+}
+
 /// Conditionally yields the current task queue. The scheduler may then
 /// process other task queues according to their latency requirements.
 /// If a call to this function results in the current queue to yield,
@@ -1925,6 +3996,7 @@ pub async fn yield_if_needed() {
 ///     assert_eq!(task.await, 3);
 /// });
 /// ```
+#[track_caller]
 pub fn spawn_local<T>(future: impl Future<Output = T> + 'static) -> Task<T>
 where
     T: 'static,
@@ -1993,6 +4065,7 @@ pub fn allocate_dma_buffer_global(size: usize) -> DmaBuffer {
 /// assert_eq!(task.await, 3);
 /// # });
 /// ```
+#[track_caller]
 pub fn spawn_local_into<T>(
     future: impl Future<Output = T> + 'static,
     handle: TaskQueueHandle,
@@ -2029,6 +4102,7 @@ where
 ///     assert_eq!(task.await, 3);
 /// });
 /// ```
+#[track_caller]
 pub unsafe fn spawn_scoped_local<'a, T>(future: impl Future<Output = T> + 'a) -> ScopedTask<'a, T> {
     executor().spawn_scoped_local(future)
 }
@@ -2067,6 +4141,7 @@ pub unsafe fn spawn_scoped_local<'a, T>(future: impl Future<Output = T> + 'a) ->
 ///     assert_eq!(task.await, 3);
 /// })
 /// ```
+#[track_caller]
 pub unsafe fn spawn_scoped_local_into<'a, T>(
     future: impl Future<Output = T> + 'a,
     handle: TaskQueueHandle,
@@ -2248,6 +4323,57 @@ impl ExecutorProxy {
         };
     }
 
+    /// Returns the [`ShutdownToken`] of the pool this executor belongs to, or
+    /// `None` if it was not created by [`LocalExecutorPoolBuilder`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{LocalExecutorPoolBuilder, PoolPlacement};
+    /// use std::time::Duration;
+    ///
+    /// LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(1))
+    ///     .on_all_shards(|| async move {
+    ///         let token = glommio::executor().shutdown_token().unwrap();
+    ///         while !token.is_shutdown() {
+    ///             glommio::timer::sleep(Duration::from_millis(10)).await;
+    ///         }
+    ///     })
+    ///     .unwrap()
+    ///     .shutdown(Duration::from_secs(1));
+    /// ```
+    pub fn shutdown_token(&self) -> Option<ShutdownToken> {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.shutdown.clone());
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .shutdown
+                .clone()
+        };
+    }
+
+    /// Returns the membership of the pool this executor belongs to, if it was
+    /// created by [`LocalExecutorPoolBuilder::on_all_shards`]. See
+    /// [`PoolMembership`] for how to rebalance a sharded service when the pool
+    /// is resized.
+    pub fn pool_membership(&self) -> Option<PoolMembership> {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.membership.clone());
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .membership
+                .clone()
+        };
+    }
+
     /// Creates a new task queue, with a given latency hint and the provided
     /// name
     ///
@@ -2301,48 +4427,234 @@ impl ExecutorProxy {
         };
     }
 
-    /// Returns the [`TaskQueueHandle`] that represents the TaskQueue currently
-    /// running. This can be passed directly into [`crate::spawn_local_into`].
-    /// This must be run from a task that was generated through
-    /// [`crate::spawn_local`] or [`crate::spawn_local_into`]
+    /// Creates a new task queue in the earliest deadline first scheduling
+    /// class, with a given latency hint and the provided name
     ///
-    /// # Examples
-    /// ```
-    /// use glommio::{Latency, LocalExecutor, LocalExecutorBuilder, Shares};
+    /// Instead of being scheduled according to its [`Shares`], each time the
+    /// task queue becomes runnable it is given a deadline `deadline` into the
+    /// future, and it runs ahead of every task queue with no deadline or with
+    /// a later deadline until it runs out of tasks. When such a task queue
+    /// becomes runnable, the task queue currently executing is preempted as
+    /// if its preempt timer had fired, so long-running tasks that check
+    /// [`need_preempt`] or call [`yield_if_needed`] give way to it.
     ///
-    /// let ex = LocalExecutorBuilder::default()
-    ///     .spawn(|| async move {
-    ///         let original_tq = glommio::executor().current_task_queue();
-    ///         let new_tq = glommio::executor().create_task_queue(
-    ///             Shares::default(),
-    ///             Latency::NotImportant,
-    ///             "test",
-    ///         );
+    /// Task queues in this class are not subject to proportional share, so
+    /// they can starve the rest of the executor if they never run out of
+    /// work. They are meant for short request paths with hard deadlines.
     ///
-    ///         let task = glommio::spawn_local_into(
-    ///             async move {
-    ///                 glommio::spawn_local_into(
-    ///                     async move {
-    ///                         assert_eq!(glommio::executor().current_task_queue(), original_tq);
-    ///                     },
-    ///                     original_tq,
-    ///                 )
-    ///                 .unwrap();
-    ///             },
-    ///             new_tq,
-    ///         )
-    ///         .unwrap();
-    ///         task.await;
-    ///     })
-    ///     .unwrap();
+    /// # Examples
     ///
-    /// ex.join().unwrap();
     /// ```
-    pub fn current_task_queue(&self) -> TaskQueueHandle {
-        #[cfg(not(feature = "native-tls"))]
-        return LOCAL_EX.with(|local_ex| local_ex.current_task_queue());
-
-        #[cfg(feature = "native-tls")]
+    /// use glommio::{Latency, LocalExecutor};
+    /// use std::time::Duration;
+    ///
+    /// let local_ex = LocalExecutor::default();
+    /// local_ex.run(async move {
+    ///     let task_queue = glommio::executor().create_edf_task_queue(
+    ///         Duration::from_millis(5),
+    ///         Latency::Matters(Duration::from_millis(1)),
+    ///         "requests",
+    ///     );
+    ///     let task = glommio::spawn_local_into(
+    ///         async {
+    ///             println!("Hello world");
+    ///         },
+    ///         task_queue,
+    ///     )
+    ///     .expect("failed to spawn task");
+    /// });
+    /// ```
+    ///
+    /// [`Shares`]: enum.Shares.html
+    /// [`need_preempt`]: ExecutorProxy::need_preempt
+    /// [`yield_if_needed`]: ExecutorProxy::yield_if_needed
+    pub fn create_edf_task_queue(
+        &self,
+        deadline: Duration,
+        latency: Latency,
+        name: &str,
+    ) -> TaskQueueHandle {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.create_edf_task_queue(deadline, latency, name));
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .create_edf_task_queue(deadline, latency, name)
+        };
+    }
+
+    /// Creates a new task queue as a child of an existing one, with a given
+    /// latency hint and the provided name
+    ///
+    /// Task queues can be nested to build a hierarchy, similar to how cgroup
+    /// v2 CPU weights work: the [`Shares`] of a child task queue don't
+    /// compete with every other task queue in the executor, but only with its
+    /// siblings, for the fraction of the CPU its parent is entitled to. Tasks
+    /// spawned directly into a parent task queue compete with its children as
+    /// if they were in a child with default shares.
+    ///
+    /// [`TaskQueueStats::runtime`] reports the time spent running the tasks of
+    /// a queue itself, while [`TaskQueueStats::subtree_runtime`] includes that
+    /// of its descendants.
+    ///
+    /// Returns an error if `parent` doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{Latency, LocalExecutor, Shares};
+    ///
+    /// let local_ex = LocalExecutor::default();
+    /// local_ex.run(async move {
+    ///     // tenant_a gets 70% of the CPU when both tenants are busy
+    ///     let tenant_a = glommio::executor().create_task_queue(
+    ///         Shares::Static(700),
+    ///         Latency::NotImportant,
+    ///         "tenant_a",
+    ///     );
+    ///     let _tenant_b = glommio::executor().create_task_queue(
+    ///         Shares::Static(300),
+    ///         Latency::NotImportant,
+    ///         "tenant_b",
+    ///     );
+    ///     // and compaction gets 20% of what tenant_a gets
+    ///     let compaction = glommio::executor()
+    ///         .create_child_task_queue(
+    ///             tenant_a,
+    ///             Shares::Static(200),
+    ///             Latency::NotImportant,
+    ///             "tenant_a_compaction",
+    ///         )
+    ///         .unwrap();
+    ///     let _requests = glommio::executor()
+    ///         .create_child_task_queue(
+    ///             tenant_a,
+    ///             Shares::Static(800),
+    ///             Latency::NotImportant,
+    ///             "tenant_a_requests",
+    ///         )
+    ///         .unwrap();
+    ///
+    ///     glommio::spawn_local_into(async {}, compaction)
+    ///         .unwrap()
+    ///         .await;
+    /// });
+    /// ```
+    pub fn create_child_task_queue(
+        &self,
+        parent: TaskQueueHandle,
+        shares: Shares,
+        latency: Latency,
+        name: &str,
+    ) -> Result<TaskQueueHandle> {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX
+            .with(|local_ex| local_ex.create_task_queue_in(Some(parent), shares, latency, name));
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .create_task_queue_in(Some(parent), shares, latency, name)
+        };
+    }
+
+    /// Sets how many times the tasks of a task queue can poll socket reads,
+    /// channel receives and file reads in a single poll, or lifts the limit if
+    /// `budget` is `None`
+    ///
+    /// Preemption only happens between task queues, with [`need_preempt`]. A
+    /// task that keeps finding its I/O ready, such as one reading from a busy
+    /// socket, could otherwise run for a whole preemption slice before the
+    /// other tasks of its queue get a turn. Once a task exhausts its budget,
+    /// these operations return `Pending` and the task goes to the back of its
+    /// queue, as if it had called [`yield_now`]. The default budget is 128.
+    ///
+    /// Returns an error if there is no task queue with this handle.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{Latency, LocalExecutor, Shares};
+    ///
+    /// let local_ex = LocalExecutor::default();
+    /// local_ex.run(async move {
+    ///     let task_queue = glommio::executor().create_task_queue(
+    ///         Shares::default(),
+    ///         Latency::NotImportant,
+    ///         "connections",
+    ///     );
+    ///     glommio::executor()
+    ///         .set_task_queue_poll_budget(task_queue, Some(32))
+    ///         .unwrap();
+    /// });
+    /// ```
+    ///
+    /// [`need_preempt`]: ExecutorProxy::need_preempt
+    /// [`yield_now`]: ExecutorProxy::yield_now
+    pub fn set_task_queue_poll_budget(
+        &self,
+        handle: TaskQueueHandle,
+        budget: Option<u32>,
+    ) -> Result<()> {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.set_task_queue_poll_budget(handle, budget));
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .set_task_queue_poll_budget(handle, budget)
+        };
+    }
+
+    /// Returns the [`TaskQueueHandle`] that represents the TaskQueue currently
+    /// running. This can be passed directly into [`crate::spawn_local_into`].
+    /// This must be run from a task that was generated through
+    /// [`crate::spawn_local`] or [`crate::spawn_local_into`]
+    ///
+    /// # Examples
+    /// ```
+    /// use glommio::{Latency, LocalExecutor, LocalExecutorBuilder, Shares};
+    ///
+    /// let ex = LocalExecutorBuilder::default()
+    ///     .spawn(|| async move {
+    ///         let original_tq = glommio::executor().current_task_queue();
+    ///         let new_tq = glommio::executor().create_task_queue(
+    ///             Shares::default(),
+    ///             Latency::NotImportant,
+    ///             "test",
+    ///         );
+    ///
+    ///         let task = glommio::spawn_local_into(
+    ///             async move {
+    ///                 glommio::spawn_local_into(
+    ///                     async move {
+    ///                         assert_eq!(glommio::executor().current_task_queue(), original_tq);
+    ///                     },
+    ///                     original_tq,
+    ///                 )
+    ///                 .unwrap();
+    ///             },
+    ///             new_tq,
+    ///         )
+    ///         .unwrap();
+    ///         task.await;
+    ///     })
+    ///     .unwrap();
+    ///
+    /// ex.join().unwrap();
+    /// ```
+    pub fn current_task_queue(&self) -> TaskQueueHandle {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.current_task_queue());
+
+        #[cfg(feature = "native-tls")]
         return unsafe {
             LOCAL_EX
                 .as_ref()
@@ -2383,19 +4695,19 @@ impl ExecutorProxy {
     pub fn task_queue_stats(&self, handle: TaskQueueHandle) -> Result<TaskQueueStats> {
         #[cfg(not(feature = "native-tls"))]
         return LOCAL_EX.with(|local_ex| match local_ex.get_queue(&handle) {
-            Some(x) => Ok(x.borrow_mut().stats.take()),
+            Some(x) => Ok(local_ex.take_task_queue_stats(&x)),
             None => Err(GlommioError::queue_not_found(handle.index)),
         });
 
         #[cfg(feature = "native-tls")]
-        return match unsafe {
-            LOCAL_EX
+        return unsafe {
+            let local_ex = LOCAL_EX
                 .as_ref()
-                .expect("this thread doesn't have a LocalExecutor running")
-                .get_queue(&handle)
-        } {
-            Some(x) => Ok(x.borrow_mut().stats.take()),
-            None => Err(GlommioError::queue_not_found(handle.index)),
+                .expect("this thread doesn't have a LocalExecutor running");
+            match local_ex.get_queue(&handle) {
+                Some(x) => Ok(local_ex.take_task_queue_stats(&x)),
+                None => Err(GlommioError::queue_not_found(handle.index)),
+            }
         };
     }
 
@@ -2443,21 +4755,26 @@ impl ExecutorProxy {
                     .borrow()
                     .available_executors
                     .values()
-                    .map(|x| x.borrow_mut().stats.take()),
+                    .map(|x| local_ex.take_task_queue_stats(x)),
             );
         });
 
         #[cfg(feature = "native-tls")]
-        output.extend(unsafe {
-            LOCAL_EX
-                .as_ref()
-                .expect("this thread doesn't have a LocalExecutor running")
-                .queues
-                .borrow()
-                .available_executors
-                .values()
-                .map(|x| x.borrow_mut().stats.take())
-        });
+        {
+            let local_ex = unsafe {
+                LOCAL_EX
+                    .as_ref()
+                    .expect("this thread doesn't have a LocalExecutor running")
+            };
+            output.extend(
+                local_ex
+                    .queues
+                    .borrow()
+                    .available_executors
+                    .values()
+                    .map(|x| local_ex.take_task_queue_stats(x)),
+            );
+        }
 
         output
     }
@@ -2484,17 +4801,15 @@ impl ExecutorProxy {
     /// [`ExecutorStats`]: struct.ExecutorStats.html
     pub fn executor_stats(&self) -> ExecutorStats {
         #[cfg(not(feature = "native-tls"))]
-        return LOCAL_EX.with(|local_ex| std::mem::take(&mut local_ex.queues.borrow_mut().stats));
+        return LOCAL_EX.with(|local_ex| local_ex.take_executor_stats());
 
         #[cfg(feature = "native-tls")]
-        return std::mem::take(unsafe {
-            &mut LOCAL_EX
+        return unsafe {
+            LOCAL_EX
                 .as_ref()
                 .expect("this thread doesn't have a LocalExecutor running")
-                .queues
-                .borrow_mut()
-                .stats
-        });
+                .take_executor_stats()
+        };
     }
 
     /// Returns an [`IoStats`] struct with information about IO performed by
@@ -2517,7 +4832,109 @@ impl ExecutorProxy {
     /// [`IoStats`]: crate::IoStats
     pub fn io_stats(&self) -> IoStats {
         #[cfg(not(feature = "native-tls"))]
-        return LOCAL_EX.with(|local_ex| local_ex.get_reactor().io_stats());
+        return LOCAL_EX.with(|local_ex| local_ex.take_io_stats());
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .take_io_stats()
+        };
+    }
+
+    /// Returns a [`BlockingPoolStats`] struct with information about the
+    /// operations run by this executor's blocking thread pool, such as how
+    /// deep its queues are and how long operations waited in them
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use glommio::LocalExecutorBuilder;
+    ///
+    /// let ex = LocalExecutorBuilder::default()
+    ///     .spawn(|| async move {
+    ///         glommio::executor().spawn_blocking(|| {}).await;
+    ///         let stats = glommio::executor().blocking_pool_stats();
+    ///         assert_eq!(stats.user.completed(), 1);
+    ///     })
+    ///     .unwrap();
+    ///
+    /// ex.join().unwrap();
+    /// ```
+    ///
+    /// [`BlockingPoolStats`]: crate::BlockingPoolStats
+    pub fn blocking_pool_stats(&self) -> BlockingPoolStats {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.get_reactor().blocking_pool_stats());
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .get_reactor()
+                .blocking_pool_stats()
+        };
+    }
+
+    /// Returns statistics about the stalls of each task queue of this
+    /// executor, if its stall handler keeps any, such as
+    /// [`StallHistogramHandler`]. It's empty if stall detection is not
+    /// enabled with [`LocalExecutorBuilder::detect_stalls`].
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use glommio::{LocalExecutorBuilder, StallHistogramHandler};
+    ///
+    /// let ex = LocalExecutorBuilder::default()
+    ///     .detect_stalls(Some(Box::new(StallHistogramHandler::default())))
+    ///     .spawn(|| async move {
+    ///         assert!(glommio::executor().stall_stats().is_empty());
+    ///     })
+    ///     .unwrap();
+    ///
+    /// ex.join().unwrap();
+    /// ```
+    ///
+    /// [`StallHistogramHandler`]: crate::StallHistogramHandler
+    pub fn stall_stats(&self) -> Vec<TaskQueueStallStats> {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.stall_stats());
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .stall_stats()
+        };
+    }
+
+    /// Returns an [`IoMemoryStats`] struct with information about the memory
+    /// this executor reserved for storage I/O, such as the NUMA nodes it is
+    /// on
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use glommio::LocalExecutorBuilder;
+    ///
+    /// let ex = LocalExecutorBuilder::default()
+    ///     .spawn(|| async move {
+    ///         let stats = glommio::executor().io_memory_stats();
+    ///         assert!(stats.size() >= 10 << 20);
+    ///     })
+    ///     .unwrap();
+    ///
+    /// ex.join().unwrap();
+    /// ```
+    ///
+    /// [`IoMemoryStats`]: crate::IoMemoryStats
+    pub fn io_memory_stats(&self) -> IoMemoryStats {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.get_reactor().io_memory_stats());
 
         #[cfg(feature = "native-tls")]
         return unsafe {
@@ -2525,7 +4942,7 @@ impl ExecutorProxy {
                 .as_ref()
                 .expect("this thread doesn't have a LocalExecutor running")
                 .get_reactor()
-                .io_stats()
+                .io_memory_stats()
         };
     }
 
@@ -2557,11 +4974,9 @@ impl ExecutorProxy {
     /// [`IoStats`]: crate::IoStats
     pub fn task_queue_io_stats(&self, handle: TaskQueueHandle) -> Result<IoStats> {
         #[cfg(not(feature = "native-tls"))]
-        return LOCAL_EX.with(|local_ex| {
-            match local_ex.get_reactor().task_queue_io_stats(&handle) {
-                Some(x) => Ok(x),
-                None => Err(GlommioError::queue_not_found(handle.index)),
-            }
+        return LOCAL_EX.with(|local_ex| match local_ex.take_task_queue_io_stats(handle) {
+            Some(x) => Ok(x),
+            None => Err(GlommioError::queue_not_found(handle.index)),
         });
 
         #[cfg(feature = "native-tls")]
@@ -2569,23 +4984,69 @@ impl ExecutorProxy {
             LOCAL_EX
                 .as_ref()
                 .expect("this thread doesn't have a LocalExecutor running")
-                .get_reactor()
-                .task_queue_io_stats(&handle)
+                .take_task_queue_io_stats(handle)
         } {
             Some(x) => Ok(x),
             None => Err(GlommioError::queue_not_found(handle.index)),
         };
     }
 
-    /// Spawns a task onto the current single-threaded executor.
+    /// Returns the distribution of the scheduling delays of a task queue, in
+    /// microseconds, recorded since the last call.
     ///
-    /// If called from a [`LocalExecutor`], the task is spawned on it.
-    /// Otherwise, this method panics.
+    /// The scheduling delay of a task is the time between the moment it was
+    /// woken up and the moment it was polled. This is the latency tasks
+    /// actually experience, and it is only recorded if enabled with
+    /// [`LocalExecutorBuilder::record_scheduling_delays`]. Otherwise, the
+    /// distribution is always empty.
     ///
-    /// Note that there is no guarantee of when the spawned task is scheduled.
-    /// The current task can continue its execution or be preempted by the
-    /// newly spawned task immediately. See the documentation for the
-    /// top-level [`Task`] for examples.
+    /// # Examples:
+    ///
+    /// ```
+    /// use glommio::LocalExecutorBuilder;
+    ///
+    /// let ex = LocalExecutorBuilder::default()
+    ///     .record_scheduling_delays(true)
+    ///     .spawn(|| async move {
+    ///         glommio::spawn_local(async {}).await;
+    ///         let tq = glommio::executor().current_task_queue();
+    ///         let delays = glommio::executor()
+    ///             .task_queue_scheduling_delay_us(tq)
+    ///             .unwrap();
+    ///         println!("p99 scheduling delay: {:?}us", delays.quantile(0.99));
+    ///     })
+    ///     .unwrap();
+    ///
+    /// ex.join().unwrap();
+    /// ```
+    pub fn task_queue_scheduling_delay_us(&self, handle: TaskQueueHandle) -> Result<DDSketch> {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| match local_ex.get_queue(&handle) {
+            Some(x) => Ok(local_ex.take_scheduling_delays(&x)),
+            None => Err(GlommioError::queue_not_found(handle.index)),
+        });
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            let local_ex = LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running");
+            match local_ex.get_queue(&handle) {
+                Some(x) => Ok(local_ex.take_scheduling_delays(&x)),
+                None => Err(GlommioError::queue_not_found(handle.index)),
+            }
+        };
+    }
+
+    /// Spawns a task onto the current single-threaded executor.
+    ///
+    /// If called from a [`LocalExecutor`], the task is spawned on it.
+    /// Otherwise, this method panics.
+    ///
+    /// Note that there is no guarantee of when the spawned task is scheduled.
+    /// The current task can continue its execution or be preempted by the
+    /// newly spawned task immediately. See the documentation for the
+    /// top-level [`Task`] for examples.
     ///
     /// # Examples
     ///
@@ -2599,19 +5060,21 @@ impl ExecutorProxy {
     ///     assert_eq!(task.await, 3);
     /// });
     /// ```
+    #[track_caller]
     pub fn spawn_local<T>(&self, future: impl Future<Output = T> + 'static) -> Task<T>
     where
         T: 'static,
     {
+        let location = Location::caller();
         #[cfg(not(feature = "native-tls"))]
-        return LOCAL_EX.with(|local_ex| Task::<T>(local_ex.spawn(future)));
+        return LOCAL_EX.with(|local_ex| Task::<T>(local_ex.spawn(future, location)));
 
         #[cfg(feature = "native-tls")]
         return Task::<T>(unsafe {
             LOCAL_EX
                 .as_ref()
                 .expect("this thread doesn't have a LocalExecutor running")
-                .spawn(future)
+                .spawn(future, location)
         });
     }
 
@@ -2644,6 +5107,7 @@ impl ExecutorProxy {
     /// assert_eq!(task.await, 3);
     /// # });
     /// ```
+    #[track_caller]
     pub fn spawn_local_into<T>(
         &self,
         future: impl Future<Output = T> + 'static,
@@ -2652,15 +5116,17 @@ impl ExecutorProxy {
     where
         T: 'static,
     {
+        let location = Location::caller();
         #[cfg(not(feature = "native-tls"))]
-        return LOCAL_EX.with(|local_ex| local_ex.spawn_into(future, handle).map(Task::<T>));
+        return LOCAL_EX
+            .with(|local_ex| local_ex.spawn_into(future, handle, location).map(Task::<T>));
 
         #[cfg(feature = "native-tls")]
         return unsafe {
             LOCAL_EX
                 .as_ref()
                 .expect("this thread doesn't have a LocalExecutor running")
-                .spawn_into(future, handle)
+                .spawn_into(future, handle, location)
         }
         .map(Task::<T>);
     }
@@ -2689,19 +5155,22 @@ impl ExecutorProxy {
     ///     assert_eq!(task.await, 3);
     /// });
     /// ```
+    #[track_caller]
     pub unsafe fn spawn_scoped_local<'a, T>(
         &self,
         future: impl Future<Output = T> + 'a,
     ) -> ScopedTask<'a, T> {
+        let location = Location::caller();
         #[cfg(not(feature = "native-tls"))]
-        return LOCAL_EX.with(|local_ex| ScopedTask::<'a, T>(local_ex.spawn(future), PhantomData));
+        return LOCAL_EX
+            .with(|local_ex| ScopedTask::<'a, T>(local_ex.spawn(future, location), PhantomData));
 
         #[cfg(feature = "native-tls")]
         return ScopedTask::<'a, T>(
             LOCAL_EX
                 .as_ref()
                 .expect("this thread doesn't have a LocalExecutor running")
-                .spawn(future),
+                .spawn(future, location),
             PhantomData,
         );
     }
@@ -2739,15 +5208,17 @@ impl ExecutorProxy {
     ///     assert_eq!(task.await, 3);
     /// })
     /// ```
+    #[track_caller]
     pub unsafe fn spawn_scoped_local_into<'a, T>(
         &self,
         future: impl Future<Output = T> + 'a,
         handle: TaskQueueHandle,
     ) -> Result<ScopedTask<'a, T>> {
+        let location = Location::caller();
         #[cfg(not(feature = "native-tls"))]
         return LOCAL_EX.with(|local_ex| {
             local_ex
-                .spawn_into(future, handle)
+                .spawn_into(future, handle, location)
                 .map(|x| ScopedTask::<'a, T>(x, PhantomData))
         });
 
@@ -2755,10 +5226,345 @@ impl ExecutorProxy {
         return LOCAL_EX
             .as_ref()
             .expect("this thread doesn't have a LocalExecutor running")
-            .spawn_into(future, handle)
+            .spawn_into(future, handle, location)
             .map(|x| ScopedTask::<'a, T>(x, PhantomData));
     }
 
+    /// Spawns a task onto another executor, identified by its
+    /// [`id`](ExecutorProxy::id).
+    ///
+    /// The future is sent to the remote executor and spawned there in its
+    /// default task queue. The remote executor is woken up through the same
+    /// mechanism used to wake its tasks from other threads, so this works
+    /// even if it is currently sleeping.
+    ///
+    /// Returns a [`RemoteJoinHandle`] that resolves to the output of the
+    /// task, or an [`ExecutorErrorKind::InvalidId`] error if there is no
+    /// executor with that id.
+    ///
+    /// This method can be called from any thread, not only from within an
+    /// executor.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{LocalExecutorPoolBuilder, PoolPlacement};
+    ///
+    /// LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(1))
+    ///     .on_all_shards(|| async move {
+    ///         let me = glommio::executor().id();
+    ///         let id = glommio::executor()
+    ///             .spawn_on(me, async move { glommio::executor().id() })
+    ///             .unwrap()
+    ///             .await
+    ///             .unwrap();
+    ///         assert_eq!(id, me);
+    ///     })
+    ///     .unwrap()
+    ///     .join_all();
+    /// ```
+    #[track_caller]
+    pub fn spawn_on<T, F>(&self, executor_id: usize, future: F) -> Result<RemoteJoinHandle<T>>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_on_into(executor_id, future, TaskQueueHandle::default())
+    }
+
+    /// Spawns a task onto another executor, identified by its
+    /// [`id`](ExecutorProxy::id), in a particular task queue of that
+    /// executor.
+    ///
+    /// Task queue handles are only meaningful to the executor that created
+    /// them, so `handle` must have been obtained from a call to
+    /// [`create_task_queue`](ExecutorProxy::create_task_queue) made on the
+    /// remote executor. If no such queue exists there, the returned
+    /// [`RemoteJoinHandle`] resolves to a [`QueueErrorKind::NotFound`] error.
+    ///
+    /// See [`ExecutorProxy::spawn_on`] for details.
+    ///
+    /// [`QueueErrorKind::NotFound`]: crate::QueueErrorKind::NotFound
+    #[track_caller]
+    pub fn spawn_on_into<T, F>(
+        &self,
+        executor_id: usize,
+        future: F,
+        handle: TaskQueueHandle,
+    ) -> Result<RemoteJoinHandle<T>>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        // usize::MAX is the placeholder for disconnected executors
+        let notifier = match executor_id {
+            usize::MAX => None,
+            id => sys::get_sleep_notifier_for(id),
+        }
+        .ok_or(GlommioError::ExecutorError(ExecutorErrorKind::InvalidId(
+            executor_id,
+        )))?;
+
+        let (sender, receiver) = flume::bounded(1);
+        if !notifier.queue_spawn(foreign_spawn(future, handle, sender, Location::caller())) {
+            return Err(GlommioError::ExecutorError(ExecutorErrorKind::InvalidId(
+                executor_id,
+            )));
+        }
+
+        Ok(RemoteJoinHandle {
+            executor_id: Arc::new(AtomicUsize::new(executor_id)),
+            receiver: receiver.into_recv_async(),
+        })
+    }
+
+    /// Spawns a task that any executor of the current pool may run, in the
+    /// current task queue.
+    ///
+    /// Tasks spawned with [`spawn_local`](ExecutorProxy::spawn_local) always
+    /// run on the executor that spawned them. Stealable tasks, instead, are
+    /// placed in a queue shared by all the executors created by the same
+    /// [`LocalExecutorPoolBuilder::on_all_shards`] call, and are picked up by
+    /// whichever executor gets to them first. Executors that are busy look at
+    /// that queue less often than idle ones, so this helps spread load away
+    /// from hot shards. Because the task may move to another thread, the
+    /// future must be [`Send`].
+    ///
+    /// Executors that are not part of a pool have no one to share the work
+    /// with, and run stealable tasks themselves.
+    ///
+    /// See [`spawn_stealable_into`](ExecutorProxy::spawn_stealable_into) for
+    /// how task queues are handled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{LocalExecutorPoolBuilder, PoolPlacement};
+    ///
+    /// LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+    ///     .on_all_shards(|| async move {
+    ///         let handle = glommio::executor().spawn_stealable(async move { 1 + 2 });
+    ///         assert_eq!(handle.await.unwrap(), 3);
+    ///     })
+    ///     .unwrap()
+    ///     .join_all();
+    /// ```
+    #[track_caller]
+    pub fn spawn_stealable<T, F>(&self, future: F) -> RemoteJoinHandle<T>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_stealable_into(future, self.current_task_queue())
+            .expect("the current task queue always exists")
+    }
+
+    /// Spawns a task that any executor of the current pool may run, in a
+    /// particular task queue.
+    ///
+    /// If the current executor ends up running the task, it is spawned into
+    /// `handle`. Any other executor spawns it into its oldest task queue with
+    /// the same name, [`Latency`] and static [`Shares`] as `handle` (dynamic
+    /// shares only match other dynamic shares), and executors that don't
+    /// have such a queue never pick it up. Create the task queues that
+    /// stealable tasks are spawned into on every executor of the pool (for
+    /// instance at the start of the future passed to
+    /// [`LocalExecutorPoolBuilder::on_all_shards`]) so any of them can run
+    /// the tasks; the handles don't need to be the same.
+    ///
+    /// Returns an error if `handle` doesn't exist on the current executor.
+    ///
+    /// See [`ExecutorProxy::spawn_stealable`] for details.
+    #[track_caller]
+    pub fn spawn_stealable_into<T, F>(
+        &self,
+        future: F,
+        handle: TaskQueueHandle,
+    ) -> Result<RemoteJoinHandle<T>>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let location = Location::caller();
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.spawn_stealable(future, handle, location));
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .spawn_stealable(future, handle, location)
+        };
+    }
+
+    /// Returns a snapshot of the live tasks of the current executor, oldest
+    /// first.
+    ///
+    /// Unlike `TaskDebugger`, this is always available and doesn't need the
+    /// `debugging` feature. Tasks can be given a label to identify them with
+    /// [`Task::set_label`]. When each task was last polled is only known to
+    /// executors that time their polls, see [`TaskDump::last_poll`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{task::TaskState, LocalExecutor};
+    ///
+    /// let local_ex = LocalExecutor::default();
+    ///
+    /// local_ex.run(async {
+    ///     let task = glommio::spawn_local(futures_lite::future::pending::<()>());
+    ///     task.set_label("forever");
+    ///
+    ///     let dump = glommio::executor().dump_tasks();
+    ///     let forever = dump.iter().find(|t| t.label() == Some("forever")).unwrap();
+    ///     assert_eq!(forever.state(), TaskState::Idle);
+    ///     assert_eq!(forever.task_queue_name(), "default");
+    /// });
+    /// ```
+    pub fn dump_tasks(&self) -> Vec<TaskDump> {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.dump_tasks());
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .dump_tasks()
+        };
+    }
+
+    /// Takes a snapshot of the live tasks of another executor, identified by
+    /// its [`id`](ExecutorProxy::id).
+    ///
+    /// The snapshot is taken by a task spawned on that executor, so it shows
+    /// up in it. See [`ExecutorProxy::spawn_on`] for how the task is sent, and
+    /// [`ExecutorProxy::dump_tasks`] for what the snapshot contains.
+    ///
+    /// This method can be called from any thread, not only from within an
+    /// executor.
+    pub fn dump_tasks_on(&self, executor_id: usize) -> Result<RemoteJoinHandle<Vec<TaskDump>>> {
+        self.spawn_on(executor_id, async { crate::executor().dump_tasks() })
+    }
+
+    /// Takes a snapshot of the live tasks of every executor in the pool the
+    /// current executor belongs to, such as the ones created by
+    /// [`LocalExecutorPoolBuilder::on_all_shards`]. For executors that are not
+    /// part of a pool, this is the same as [`ExecutorProxy::dump_tasks`].
+    ///
+    /// Executors that exit before taking their snapshot are left out.
+    pub async fn dump_pool_tasks(&self) -> Vec<TaskDump> {
+        #[cfg(not(feature = "native-tls"))]
+        let (id, ids) = LOCAL_EX.with(|local_ex| (local_ex.id, local_ex.pool_executor_ids()));
+
+        #[cfg(feature = "native-tls")]
+        let (id, ids) = unsafe {
+            let local_ex = LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running");
+            (local_ex.id, local_ex.pool_executor_ids())
+        };
+
+        let remotes: Vec<_> = ids
+            .into_iter()
+            .filter(|&other| other != id)
+            .filter_map(|other| self.dump_tasks_on(other).ok())
+            .collect();
+        let mut dump = self.dump_tasks();
+        for remote in remotes {
+            if let Ok(remote) = remote.await {
+                dump.extend(remote);
+            }
+        }
+        dump
+    }
+
+    /// Takes a snapshot of the statistics of this executor, to be rendered
+    /// with [`metrics::encode`](crate::metrics::encode).
+    ///
+    /// The snapshot holds what [`ExecutorProxy::executor_stats`],
+    /// [`ExecutorProxy::io_stats`], [`ExecutorProxy::all_task_queue_stats`]
+    /// and [`ExecutorProxy::task_queue_io_stats`] return, accumulated since
+    /// the executor started. Taking it doesn't reset them, so applications
+    /// can use both.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{metrics, LocalExecutor};
+    ///
+    /// let local_ex = LocalExecutor::default();
+    ///
+    /// local_ex.run(async {
+    ///     let text = metrics::encode(&[glommio::executor().metrics()]);
+    ///     assert!(text.contains("glommio_executor_tasks_executed_total"));
+    /// });
+    /// ```
+    pub fn metrics(&self) -> ExecutorMetrics {
+        #[cfg(not(feature = "native-tls"))]
+        return LOCAL_EX.with(|local_ex| local_ex.metrics());
+
+        #[cfg(feature = "native-tls")]
+        return unsafe {
+            LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running")
+                .metrics()
+        };
+    }
+
+    /// Takes a snapshot of the statistics of another executor, identified by
+    /// its [`id`](ExecutorProxy::id).
+    ///
+    /// The snapshot is taken by a task spawned on that executor. See
+    /// [`ExecutorProxy::spawn_on`] for how the task is sent, and
+    /// [`ExecutorProxy::metrics`] for what the snapshot contains.
+    ///
+    /// This method can be called from any thread, not only from within an
+    /// executor.
+    pub fn metrics_on(&self, executor_id: usize) -> Result<RemoteJoinHandle<ExecutorMetrics>> {
+        self.spawn_on(executor_id, async { crate::executor().metrics() })
+    }
+
+    /// Takes a snapshot of the statistics of every executor in the pool the
+    /// current executor belongs to, such as the ones created by
+    /// [`LocalExecutorPoolBuilder::on_all_shards`]. For executors that are not
+    /// part of a pool, this only holds the snapshot of
+    /// [`ExecutorProxy::metrics`].
+    ///
+    /// The snapshots are ordered by executor id, and executors that exit
+    /// before taking theirs are left out. Rendering them together with
+    /// [`metrics::encode`](crate::metrics::encode) exposes the whole pool in a
+    /// single scrape.
+    pub async fn pool_metrics(&self) -> Vec<ExecutorMetrics> {
+        #[cfg(not(feature = "native-tls"))]
+        let (id, ids) = LOCAL_EX.with(|local_ex| (local_ex.id, local_ex.pool_executor_ids()));
+
+        #[cfg(feature = "native-tls")]
+        let (id, ids) = unsafe {
+            let local_ex = LOCAL_EX
+                .as_ref()
+                .expect("this thread doesn't have a LocalExecutor running");
+            (local_ex.id, local_ex.pool_executor_ids())
+        };
+
+        let remotes: Vec<_> = ids
+            .into_iter()
+            .filter(|&other| other != id)
+            .filter_map(|other| self.metrics_on(other).ok())
+            .collect();
+        let mut metrics = vec![self.metrics()];
+        for remote in remotes {
+            if let Ok(remote) = remote.await {
+                metrics.push(remote);
+            }
+        }
+        metrics.sort_by_key(|m| m.executor_id);
+        metrics
+    }
+
     /// Spawns a blocking task into a background thread where blocking is
     /// acceptable.
     ///
@@ -2795,6 +5601,52 @@ impl ExecutorProxy {
     /// });
     /// ```
     pub fn spawn_blocking<F, R>(&self, func: F) -> impl Future<Output = R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let waiter = Self::run_blocking(func, false);
+        async move {
+            waiter
+                .await
+                .expect("blocking operations that wait for room in the queue can't fail")
+        }
+    }
+
+    /// Like [`spawn_blocking`], but fails with an error of kind
+    /// [`std::io::ErrorKind::WouldBlock`] rather than waiting for room when
+    /// the queue of the blocking thread pool is full and the executor was
+    /// built with [`BlockingQueuePolicy::Reject`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use glommio::{BlockingQueuePolicy, LocalExecutorBuilder};
+    ///
+    /// let local_ex = LocalExecutorBuilder::default()
+    ///     .blocking_queue_capacity(16)
+    ///     .blocking_queue_policy(BlockingQueuePolicy::Reject)
+    ///     .make()
+    ///     .unwrap();
+    ///
+    /// local_ex.run(async {
+    ///     match glommio::executor().try_spawn_blocking(|| 1 + 1).await {
+    ///         Ok(two) => assert_eq!(two, 2),
+    ///         Err(err) => println!("the blocking thread pool is saturated: {}", err),
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// [`spawn_blocking`]: ExecutorProxy::spawn_blocking
+    pub fn try_spawn_blocking<F, R>(&self, func: F) -> impl Future<Output = Result<R>>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        Self::run_blocking(func, true)
+    }
+
+    fn run_blocking<F, R>(func: F, may_reject: bool) -> impl Future<Output = Result<R>>
     where
         F: FnOnce() -> R + Send + 'static,
         R: Send + 'static,
@@ -2803,8 +5655,8 @@ impl ExecutorProxy {
         let f_inner = enclose::enclose!((result) move || {result.lock().unwrap().write(func());});
 
         #[cfg(not(feature = "native-tls"))]
-        let waiter =
-            LOCAL_EX.with(move |local_ex| local_ex.reactor.run_blocking(Box::new(f_inner)));
+        let waiter = LOCAL_EX
+            .with(move |local_ex| local_ex.reactor.run_blocking(Box::new(f_inner), may_reject));
 
         #[cfg(feature = "native-tls")]
         let waiter = unsafe {
@@ -2812,12 +5664,12 @@ impl ExecutorProxy {
                 .as_ref()
                 .expect("this thread doesn't have a LocalExecutor running")
                 .reactor
-                .run_blocking(Box::new(f_inner))
+                .run_blocking(Box::new(f_inner), may_reject)
         };
 
         async move {
             let source = waiter.await;
-            assert!(source.collect_rw().await.is_ok());
+            source.collect_rw().await?;
             unsafe {
                 let res_arc = Arc::try_unwrap(result).expect("leak");
                 let ret = std::mem::replace(
@@ -2825,7 +5677,7 @@ impl ExecutorProxy {
                     MaybeUninit::<R>::uninit(),
                 )
                 .assume_init();
-                ret
+                Ok(ret)
             }
         }
     }
@@ -2838,7 +5690,7 @@ mod test {
         cell::Cell,
         collections::HashMap,
         sync::{
-            atomic::{AtomicUsize, Ordering},
+            atomic::{AtomicBool, AtomicUsize, Ordering},
             Arc, Mutex,
         },
         task::Waker,
@@ -2852,7 +5704,7 @@ mod test {
     use crate::{
         enclose,
         timer::{self, sleep, Timer},
-        SharesManager,
+        QueueErrorKind, SharesManager,
     };
 
     use super::*;
@@ -2993,6 +5845,7 @@ mod test {
                         }
                     },
                     not_latency,
+                    Location::caller(),
                 )
                 .unwrap();
 
@@ -3012,6 +5865,7 @@ mod test {
                         }
                     },
                     latency,
+                    Location::caller(),
                 )
                 .unwrap();
 
@@ -3099,6 +5953,7 @@ mod test {
                         }
                     },
                     tq1,
+                    Location::caller(),
                 )
                 .unwrap();
 
@@ -3120,6 +5975,7 @@ mod test {
                         }
                     },
                     tq2,
+                    Location::caller(),
                 )
                 .unwrap();
 
@@ -3343,31 +6199,219 @@ mod test {
     }
 
     #[test]
-    fn test_shares_high_disparity_fat_task() {
-        test_static_shares!(1000, 10, { work_quanta().await });
-    }
+    fn test_nested_shares() {
+        let local_ex = LocalExecutor::default();
 
-    #[test]
-    fn test_shares_low_disparity_fat_task() {
-        test_static_shares!(1000, 1000, { work_quanta().await });
+        local_ex.run(async {
+            let parent = crate::executor().create_task_queue(
+                Shares::Static(500),
+                Latency::Matters(Duration::from_millis(1)),
+                "parent",
+            );
+            let sibling = crate::executor().create_task_queue(
+                Shares::Static(500),
+                Latency::Matters(Duration::from_millis(1)),
+                "sibling",
+            );
+            let child1 = crate::executor()
+                .create_child_task_queue(
+                    parent,
+                    Shares::Static(750),
+                    Latency::Matters(Duration::from_millis(1)),
+                    "child1",
+                )
+                .unwrap();
+            let child2 = crate::executor()
+                .create_child_task_queue(
+                    parent,
+                    Shares::Static(250),
+                    Latency::Matters(Duration::from_millis(1)),
+                    "child2",
+                )
+                .unwrap();
+
+            let counts: Vec<_> = (0..3).map(|_| Rc::new(Cell::new(0))).collect();
+            let now = Instant::now();
+            let tasks: Vec<_> = [sibling, child1, child2]
+                .into_iter()
+                .zip(counts.iter().cloned())
+                .map(|(tq, count)| {
+                    crate::spawn_local_into(
+                        async move {
+                            while now.elapsed().as_secs() < 4 {
+                                work_quanta().await;
+                                count.replace(count.get() + 1);
+                            }
+                        },
+                        tq,
+                    )
+                    .unwrap()
+                })
+                .collect();
+            join_all(tasks).await;
+
+            let total: u64 = counts.iter().map(|c| c.get()).sum();
+            let ratio = |idx: usize| counts[idx].get() as f64 / total as f64;
+            // the sibling gets half of the CPU, and the children split the
+            // other half 3:1
+            assert!((ratio(0) - 0.5).abs() < 0.1, "{}", ratio(0));
+            assert!((ratio(1) - 0.375).abs() < 0.1, "{}", ratio(1));
+            assert!((ratio(2) - 0.125).abs() < 0.1, "{}", ratio(2));
+
+            let parent_stats = crate::executor().task_queue_stats(parent).unwrap();
+            let child_stats = crate::executor().task_queue_stats(child1).unwrap();
+            assert_eq!(child_stats.parent(), Some(parent));
+            assert!(parent_stats.subtree_runtime() >= child_stats.runtime());
+        });
     }
 
     #[test]
-    fn test_allocate_dma_buffer() {
-        LocalExecutor::default().run(async {
-            let mut buffer = crate::allocate_dma_buffer(42);
-            assert_eq!(buffer.len(), 42);
-            buffer.as_bytes_mut()[0] = 12;
-            buffer.as_bytes_mut()[12] = 13;
-            assert_eq!(buffer.as_bytes_mut().len(), 42);
-            assert_eq!(buffer.as_bytes()[0], 12);
-            assert_eq!(buffer.as_bytes()[12], 13);
+    fn test_edf_runs_earliest_deadline_first() {
+        let local_ex = LocalExecutor::default();
+        local_ex.run(async {
+            let late = crate::executor().create_edf_task_queue(
+                Duration::from_millis(100),
+                Latency::NotImportant,
+                "late",
+            );
+            let early = crate::executor().create_edf_task_queue(
+                Duration::from_millis(10),
+                Latency::NotImportant,
+                "early",
+            );
+            let background = crate::executor().create_task_queue(
+                Shares::Static(1000),
+                Latency::NotImportant,
+                "background",
+            );
+
+            let order = Rc::new(RefCell::new(Vec::new()));
+            let tasks: Vec<_> = [(background, "background"), (late, "late"), (early, "early")]
+                .into_iter()
+                .map(|(tq, name)| {
+                    crate::spawn_local_into(
+                        enclose! { (order) async move {
+                            order.borrow_mut().push(name);
+                        }},
+                        tq,
+                    )
+                    .unwrap()
+                })
+                .collect();
+            join_all(tasks).await;
+
+            assert_eq!(*order.borrow(), vec!["early", "late", "background"]);
         });
     }
 
     #[test]
-    fn test_allocate_dma_buffer_global() {
-        LocalExecutor::default().run(async {
+    fn test_edf_preempts_background_queue() {
+        let local_ex = LocalExecutor::default();
+        local_ex.run(async {
+            let edf = crate::executor().create_edf_task_queue(
+                Duration::from_millis(1),
+                Latency::NotImportant,
+                "edf",
+            );
+            let background = crate::executor().create_task_queue(
+                Shares::Static(1000),
+                Latency::NotImportant,
+                "background",
+            );
+
+            let delay = crate::spawn_local_into(
+                async move {
+                    let start = Instant::now();
+                    while start.elapsed() < Duration::from_millis(20) {
+                        crate::executor().yield_if_needed().await;
+                    }
+
+                    let woken = Instant::now();
+                    let ran = crate::spawn_local_into(async { Instant::now() }, edf)
+                        .unwrap()
+                        .detach();
+                    while start.elapsed() < Duration::from_millis(300) {
+                        crate::executor().yield_if_needed().await;
+                    }
+                    ran.await.unwrap() - woken
+                },
+                background,
+            )
+            .unwrap()
+            .await;
+
+            // the background queue has a 100ms preempt timer, so anything
+            // below that means it was preempted in favor of the EDF queue
+            assert!(delay < Duration::from_millis(50), "{delay:?}");
+        });
+    }
+
+    #[test]
+    fn test_nested_task_queue_removal() {
+        let local_ex = LocalExecutor::default();
+        let missing = TaskQueueHandle { index: 1000 };
+        let (parent, child) = local_ex.run(async move {
+            assert!(matches!(
+                crate::executor().create_child_task_queue(
+                    missing,
+                    Shares::default(),
+                    Latency::NotImportant,
+                    "orphan",
+                ),
+                Err(GlommioError::ExecutorError(ExecutorErrorKind::QueueError {
+                    kind: QueueErrorKind::NotFound,
+                    ..
+                }))
+            ));
+
+            let parent = crate::executor().create_task_queue(
+                Shares::default(),
+                Latency::NotImportant,
+                "parent",
+            );
+            let child = crate::executor()
+                .create_child_task_queue(parent, Shares::default(), Latency::NotImportant, "child")
+                .unwrap();
+            (parent, child)
+        });
+
+        assert!(matches!(
+            local_ex.remove_task_queue(parent),
+            Err(GlommioError::ExecutorError(ExecutorErrorKind::QueueError {
+                kind: QueueErrorKind::StillActive,
+                ..
+            }))
+        ));
+        local_ex.remove_task_queue(child).unwrap();
+        local_ex.remove_task_queue(parent).unwrap();
+    }
+
+    #[test]
+    fn test_shares_high_disparity_fat_task() {
+        test_static_shares!(1000, 10, { work_quanta().await });
+    }
+
+    #[test]
+    fn test_shares_low_disparity_fat_task() {
+        test_static_shares!(1000, 1000, { work_quanta().await });
+    }
+
+    #[test]
+    fn test_allocate_dma_buffer() {
+        LocalExecutor::default().run(async {
+            let mut buffer = crate::allocate_dma_buffer(42);
+            assert_eq!(buffer.len(), 42);
+            buffer.as_bytes_mut()[0] = 12;
+            buffer.as_bytes_mut()[12] = 13;
+            assert_eq!(buffer.as_bytes_mut().len(), 42);
+            assert_eq!(buffer.as_bytes()[0], 12);
+            assert_eq!(buffer.as_bytes()[12], 13);
+        });
+    }
+
+    #[test]
+    fn test_allocate_dma_buffer_global() {
+        LocalExecutor::default().run(async {
             let mut buffer = crate::allocate_dma_buffer_global(42);
             assert_eq!(buffer.len(), 42);
             assert_eq!(buffer.len(), 42);
@@ -3379,6 +6423,37 @@ mod test {
         });
     }
 
+    #[test]
+    fn numa_local_io_memory() {
+        let cpu = CpuSet::allowed().unwrap().into_iter().next().unwrap();
+        LocalExecutorBuilder::new(Placement::Fixed(cpu.cpu))
+            .io_memory(1 << 20)
+            .io_memory_huge_pages(true)
+            .spawn(move || async move {
+                let stats = executor().io_memory_stats();
+                assert!(stats.size() >= 1 << 20);
+                if stats.huge_pages() {
+                    assert_eq!(stats.size() % (2 << 20), 0);
+                }
+                assert!(stats.bytes_per_node().values().sum::<usize>() <= stats.size());
+                if let Some(node) = stats.numa_node() {
+                    assert_eq!(node, cpu.numa_node);
+                    // registered memory is allocated up front
+                    if stats.registered() {
+                        assert!(stats.bytes_per_node().contains_key(&node));
+                    }
+                }
+
+                let buffer = crate::allocate_dma_buffer_global(4096);
+                if let Some(node) = buffer.numa_node() {
+                    assert!(stats.numa_node().is_none() || stats.numa_node() == Some(node));
+                }
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
     struct DynamicSharesTest {
         shares: Cell<usize>,
     }
@@ -3763,124 +6838,974 @@ mod test {
                         .values()
                         .for_each(|v| assert_eq!(*v, nn));
                 }
-            }
-        }
+            }
+        }
+    }
+
+    #[test]
+    fn executor_pool_builder_shards_limit() {
+        let cpu_set = CpuSet::online().unwrap();
+        assert!(!cpu_set.is_empty());
+
+        // test: confirm that we can always get shards up to the # of cpus
+        {
+            let mut placements = vec![
+                (false, PoolPlacement::Unbound(cpu_set.len())),
+                (false, PoolPlacement::Fenced(cpu_set.len(), cpu_set.clone())),
+                (true, PoolPlacement::MaxSpread(cpu_set.len(), None)),
+                (
+                    true,
+                    PoolPlacement::MaxSpread(cpu_set.len(), Some(cpu_set.clone())),
+                ),
+                (true, PoolPlacement::MaxPack(cpu_set.len(), None)),
+                (
+                    true,
+                    PoolPlacement::MaxPack(cpu_set.len(), Some(cpu_set.clone())),
+                ),
+            ];
+
+            for (_shard_limited, p) in placements.drain(..) {
+                LocalExecutorPoolBuilder::new(p)
+                    .on_all_shards(|| async move {})
+                    .unwrap()
+                    .join_all();
+            }
+        }
+
+        // test: confirm that some placements fail when shards are # of cpus + 1
+        {
+            let mut placements = vec![
+                (false, PoolPlacement::Unbound(1 + cpu_set.len())),
+                (
+                    false,
+                    PoolPlacement::Fenced(1 + cpu_set.len(), cpu_set.clone()),
+                ),
+                (true, PoolPlacement::MaxSpread(1 + cpu_set.len(), None)),
+                (
+                    true,
+                    PoolPlacement::MaxSpread(1 + cpu_set.len(), Some(cpu_set.clone())),
+                ),
+                (true, PoolPlacement::MaxPack(1 + cpu_set.len(), None)),
+                (
+                    true,
+                    PoolPlacement::MaxPack(1 + cpu_set.len(), Some(cpu_set)),
+                ),
+            ];
+
+            for (shard_limited, p) in placements.drain(..) {
+                match LocalExecutorPoolBuilder::new(p).on_all_shards(|| async move {}) {
+                    Ok(handles) => {
+                        handles.join_all();
+                        assert!(!shard_limited);
+                    }
+                    Err(_) => assert!(shard_limited),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scoped_task() {
+        LocalExecutor::default().run(async {
+            let mut a = 1;
+            unsafe {
+                crate::spawn_scoped_local(async {
+                    a = 2;
+                })
+                .await;
+            }
+            crate::executor().yield_task_queue_now().await;
+            assert_eq!(a, 2);
+
+            let mut a = 1;
+            let do_later = unsafe {
+                crate::spawn_scoped_local(async {
+                    a = 2;
+                })
+            };
+
+            crate::executor().yield_task_queue_now().await;
+            do_later.await;
+            assert_eq!(a, 2);
+        });
+    }
+
+    #[test]
+    fn executor_pool_builder_thread_panic() {
+        let nr_execs = 8;
+        let res = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(nr_execs))
+            .on_all_shards(|| async move { panic!("join handle will be Err") })
+            .unwrap()
+            .join_all();
+
+        assert_eq!(nr_execs, res.len());
+        assert!(res.into_iter().all(|r| r.is_err()));
+    }
+
+    #[test]
+    fn executor_pool_builder_return_values() {
+        let nr_execs = 8;
+        let x = Arc::new(AtomicUsize::new(0));
+        let mut values = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(nr_execs))
+            .on_all_shards(|| async move { x.fetch_add(1, Ordering::Relaxed) })
+            .unwrap()
+            .join_all()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+
+        values.sort_unstable();
+        assert_eq!(values, (0..nr_execs).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn executor_pool_shutdown_drains_tasks() {
+        let drained = Arc::new(AtomicUsize::new(0));
+        let handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .on_all_shards(enclose! { (drained) move || async move {
+                let token = crate::executor().shutdown_token().unwrap();
+                // a task still in flight when the shard's future returns
+                crate::spawn_local(enclose! { (drained, token) async move {
+                    token.wait().await;
+                    sleep(Duration::from_millis(50)).await;
+                    drained.fetch_add(1, Ordering::Relaxed);
+                }})
+                .detach();
+                token.wait().await;
+                crate::executor().id()
+            }})
+            .unwrap();
+
+        let report = handles.shutdown(Duration::from_secs(5));
+        assert!(report.missed_deadline.is_empty());
+        assert_eq!(report.results.len(), 2);
+        assert!(report.results.iter().all(|res| res.is_ok()));
+        assert_eq!(drained.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn executor_pool_shutdown_reports_missed_deadline() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .on_all_shards(enclose! { (started) move || async move {
+                if started.fetch_add(1, Ordering::Relaxed) == 0 {
+                    // ignores the token
+                    sleep(Duration::from_secs(60)).await;
+                } else {
+                    crate::executor().shutdown_token().unwrap().wait().await;
+                }
+                crate::executor().id()
+            }})
+            .unwrap();
+
+        let report = handles.shutdown(Duration::from_millis(100));
+        assert_eq!(report.missed_deadline.len(), 1);
+        let (ok, timed_out): (Vec<_>, Vec<_>) =
+            report.results.into_iter().partition(|res| res.is_ok());
+        assert_eq!(ok.len(), 1);
+        assert_ne!(ok[0].as_ref().unwrap(), &report.missed_deadline[0]);
+        assert!(matches!(
+            &timed_out[..],
+            [Err(GlommioError::TimedOut(dur))] if *dur == Duration::from_millis(100)
+        ));
+    }
+
+    #[test]
+    fn executor_pool_add_and_retire_shards() {
+        let mut handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .on_all_shards(|| async move {
+                let id = crate::executor().id();
+                let membership = crate::executor().pool_membership().unwrap();
+                let is_member = membership.executors().contains(&id);
+                crate::executor().shutdown_token().unwrap().wait().await;
+                (id, is_member)
+            })
+            .unwrap();
+        let membership = handles.membership();
+        let initial = handles.executor_ids();
+        assert_eq!(membership.executors(), initial);
+        let generation = membership.generation();
+
+        let added = handles.add_shard().unwrap();
+        assert_eq!(handles.executor_ids().len(), 3);
+        assert!(membership.executors().contains(&added));
+        assert!(membership.generation() > generation);
+
+        let retired = handles.retire(initial[0]).unwrap();
+        assert_eq!(retired.join().unwrap().unwrap(), (initial[0], true));
+        assert!(!membership.executors().contains(&initial[0]));
+        assert!(handles.retire(initial[0]).is_none());
+
+        let ids = handles.executor_ids();
+        let report = handles.shutdown(Duration::from_secs(5));
+        assert!(report.missed_deadline.is_empty());
+        let results: Vec<_> = report.results.into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(
+            results,
+            ids.into_iter().map(|id| (id, true)).collect::<Vec<_>>()
+        );
+        assert!(results.iter().any(|(id, _)| *id == added));
+    }
+
+    #[test]
+    fn executor_pool_from_fixed_executor() {
+        let allowed = CpuSet::allowed().unwrap();
+        let cpu = allowed.iter().next().unwrap().cpu;
+        let nr_allowed = allowed.len();
+
+        LocalExecutorBuilder::new(Placement::Fixed(cpu))
+            .spawn(move || async move {
+                // the executor is bound to a single CPU, the process isn't
+                assert_eq!(CpuSet::allowed().unwrap().len(), nr_allowed);
+                let ids = LocalExecutorPoolBuilder::new(PoolPlacement::MaxSpread(nr_allowed, None))
+                    .on_all_shards(|| async move { crate::executor().id() })
+                    .unwrap()
+                    .join_all();
+                assert_eq!(ids.len(), nr_allowed);
+                assert!(ids.iter().all(|id| id.is_ok()));
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn executor_pool_add_shard_without_free_cpus() {
+        fn send_sync<T: Send + Sync>(_: &T) {}
+
+        let online = CpuSet::online().unwrap();
+        let cpu = online.iter().next().unwrap().cpu;
+        let cpus = online.filter(|l| l.cpu == cpu);
+        let release = Arc::new(AtomicBool::new(false));
+        let mut handles = LocalExecutorPoolBuilder::new(PoolPlacement::MaxSpread(1, Some(cpus)))
+            .on_all_shards(enclose! { (release) move || async move {
+                crate::executor().shutdown_token().unwrap().wait().await;
+                while !release.load(Ordering::Relaxed) {
+                    sleep(Duration::from_millis(1)).await;
+                }
+            }})
+            .unwrap();
+        send_sync(&handles);
+
+        // the only CPU of the pool is taken
+        assert!(matches!(
+            handles.add_shard(),
+            Err(GlommioError::BuilderError(
+                BuilderErrorKind::InsufficientCpus { .. }
+            ))
+        ));
+        // until its executor retires and exits
+        let first = handles.executor_ids()[0];
+        let retired = handles.retire(first).unwrap();
+        assert!(matches!(
+            handles.add_shard(),
+            Err(GlommioError::BuilderError(
+                BuilderErrorKind::InsufficientCpus { .. }
+            ))
+        ));
+        release.store(true, Ordering::Relaxed);
+        retired.join().unwrap().unwrap();
+        handles.add_shard().unwrap();
+
+        let report = handles.shutdown(Duration::from_secs(5));
+        assert!(report.results.iter().all(|res| res.is_ok()));
+    }
+
+    #[test]
+    fn executor_pool_membership_rebuilds_meshes() {
+        let (joined_all, joined) = std::sync::mpsc::channel();
+        let mut handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .on_all_shards(enclose! { (joined_all) move || async move {
+                let membership = crate::executor().pool_membership().unwrap();
+                let token = crate::executor().shutdown_token().unwrap();
+                while !token.is_shutdown() {
+                    let (generation, mesh) = membership.full_mesh::<usize>("test", 1);
+                    let over = || futures_lite::future::or(
+                        async {
+                            membership.changed_since(generation).await;
+                        },
+                        token.wait(),
+                    );
+                    let joined = futures_lite::future::or(
+                        async { Some(mesh.join().await.unwrap()) },
+                        async {
+                            over().await;
+                            None
+                        },
+                    );
+                    if let Some((senders, _)) = joined.await {
+                        if senders.nr_consumers() == 3 {
+                            joined_all.send(()).unwrap();
+                        }
+                        over().await;
+                    }
+                }
+            }})
+            .unwrap();
+
+        handles.add_shard().unwrap();
+        // all three executors end up in a mesh of three
+        for _ in 0..3 {
+            joined.recv().unwrap();
+        }
+        let report = handles.shutdown(Duration::from_secs(5));
+        assert!(report.missed_deadline.is_empty());
+        assert!(report.results.iter().all(|res| res.is_ok()));
+    }
+
+    #[test]
+    fn executor_pool_membership_drops_exited_shards() {
+        let early = Arc::new(AtomicUsize::new(0));
+        let handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .on_all_shards(move || async move {
+                let me = crate::executor().id();
+                if early
+                    .compare_exchange(0, me, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return 0;
+                }
+
+                // the mesh would never complete if the executor that returned
+                // was still a member
+                let membership = crate::executor().pool_membership().unwrap();
+                loop {
+                    let generation = membership.generation();
+                    if membership.executors() == [me] {
+                        break;
+                    }
+                    membership.changed_since(generation).await;
+                }
+                let (_, mesh) = membership.full_mesh::<usize>("test", 1);
+                let (senders, _) = mesh.join().await.unwrap();
+                senders.nr_consumers()
+            })
+            .unwrap();
+
+        let mut results: Vec<_> = handles.join_all().into_iter().map(|r| r.unwrap()).collect();
+        results.sort_unstable();
+        assert_eq!(results, [0, 1]);
+    }
+
+    #[test]
+    fn panic_policy_catch_surfaces_panic() {
+        let hook_calls = Arc::new(AtomicUsize::new(0));
+        let ex = LocalExecutorBuilder::default()
+            .panic_policy(PanicPolicy::Catch)
+            .on_task_panic(enclose! { (hook_calls) move |panic| {
+                assert_eq!(panic.message(), Some("boom"));
+                hook_calls.fetch_add(1, Ordering::Relaxed);
+            }})
+            .make()
+            .unwrap();
+
+        ex.run(async {
+            let tq = crate::executor().create_task_queue(
+                Shares::default(),
+                Latency::NotImportant,
+                "panicky",
+            );
+            let task = crate::spawn_local_into(
+                async {
+                    sleep(Duration::from_millis(1)).await;
+                    panic!("boom")
+                },
+                tq,
+            )
+            .unwrap();
+            task.set_label("bomb");
+            let panic = task.join().await.unwrap_err();
+            assert_eq!(panic.message(), Some("boom"));
+            assert_eq!(panic.label(), Some("bomb"));
+            assert_eq!(panic.task_queue_name(), "panicky");
+            assert!(panic
+                .to_string()
+                .starts_with("task \"bomb\" in queue panicky"));
+            assert_eq!(panic.executor_id(), crate::executor().id());
+
+            // the executor keeps running other tasks
+            assert_eq!(crate::spawn_local(async { 42 }).join().await.unwrap(), 42);
+        });
+        assert_eq!(hook_calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn panic_policy_catch_detached_task() {
+        let ex = LocalExecutorBuilder::default()
+            .panic_policy(PanicPolicy::Catch)
+            .make()
+            .unwrap();
+
+        ex.run(async {
+            let handle = crate::spawn_local(async { panic!("detached") }).detach();
+            let panic = handle.join().await.unwrap().unwrap_err();
+            assert_eq!(panic.message(), Some("detached"));
+
+            let handle = crate::spawn_local(sleep(Duration::from_secs(10))).detach();
+            handle.cancel();
+            assert!(handle.join().await.is_none());
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "main future")]
+    fn panic_policy_catch_resumes_main_future_panic() {
+        let ex = LocalExecutorBuilder::default()
+            .panic_policy(PanicPolicy::Catch)
+            .make()
+            .unwrap();
+        ex.run(async { panic!("main future") });
+    }
+
+    #[test]
+    fn task_stats_track_polls() {
+        let ex = LocalExecutorBuilder::default()
+            .record_task_stats(true)
+            .make()
+            .unwrap();
+        ex.run(async {
+            let task = crate::spawn_local(async {
+                for _ in 0..3 {
+                    let start = Instant::now();
+                    while start.elapsed() < Duration::from_millis(5) {}
+                    crate::executor().yield_task_queue_now().await;
+                }
+            });
+            assert_eq!(task.stats().polls(), 0);
+
+            let handle = task.detach();
+            while handle.stats().polls() < 4 {
+                crate::executor().yield_task_queue_now().await;
+            }
+            let stats = handle.stats();
+            assert_eq!(stats.polls(), 4);
+            assert!(stats.poll_time() >= Duration::from_millis(15));
+            assert!(stats.longest_poll() >= Duration::from_millis(5));
+            assert!(stats.longest_poll() <= stats.poll_time());
+            assert!(stats.runnable_time() > Duration::ZERO);
+            handle.await;
+        });
+    }
+
+    #[test]
+    fn task_stats_disabled_by_default() {
+        LocalExecutor::default().run(async {
+            let task = crate::spawn_local(async {}).detach();
+            crate::executor().yield_task_queue_now().await;
+            assert_eq!(task.stats().polls(), 0);
+            assert!(task.stats().last_poll().is_none());
+        });
+    }
+
+    #[test]
+    fn scheduling_delays_recorded_per_task_queue() {
+        let ex = LocalExecutorBuilder::default()
+            .record_scheduling_delays(true)
+            .make()
+            .unwrap();
+
+        ex.run(async {
+            let tq = crate::executor().create_task_queue(
+                Shares::default(),
+                Latency::NotImportant,
+                "delayed",
+            );
+            let tasks: Vec<_> = (0..10)
+                .map(|_| crate::spawn_local_into(async {}, tq).unwrap())
+                .collect();
+            // keep the tasks runnable for a while
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(5) {}
+            join_all(tasks).await;
+
+            let delays = crate::executor()
+                .task_queue_scheduling_delay_us(tq)
+                .unwrap();
+            assert_eq!(delays.count(), 10);
+            assert!(delays.min().unwrap() >= 4000.0);
+
+            // reading the delays resets them
+            let delays = crate::executor()
+                .task_queue_scheduling_delay_us(tq)
+                .unwrap();
+            assert_eq!(delays.count(), 0);
+
+            // the stats of the task queue are still plain data
+            let stats = crate::executor().task_queue_stats(tq).unwrap();
+            let copy = stats;
+            assert_eq!(copy.queue_selected(), stats.queue_selected());
+        });
+
+        LocalExecutor::default().run(async {
+            crate::spawn_local(async {}).await;
+            let delays = crate::executor()
+                .task_queue_scheduling_delay_us(TaskQueueHandle::default())
+                .unwrap();
+            assert_eq!(delays.count(), 0);
+        });
+    }
+
+    #[test]
+    fn simulation_reproduces_interleavings() {
+        fn interleaving(seed: u64) -> Vec<usize> {
+            let ex = LocalExecutorBuilder::default()
+                .simulation(seed)
+                .make()
+                .unwrap();
+            ex.run(async {
+                let order = Rc::new(RefCell::new(Vec::new()));
+                let tq = crate::executor().create_task_queue(
+                    Shares::default(),
+                    Latency::NotImportant,
+                    "other",
+                );
+                let tasks: Vec<_> = (0..8)
+                    .map(|i| {
+                        let order = order.clone();
+                        let tq = if i % 2 == 0 {
+                            TaskQueueHandle::default()
+                        } else {
+                            tq
+                        };
+                        crate::spawn_local_into(
+                            async move {
+                                for _ in 0..4 {
+                                    order.borrow_mut().push(i);
+                                    futures_lite::future::yield_now().await;
+                                }
+                            },
+                            tq,
+                        )
+                        .unwrap()
+                    })
+                    .collect();
+                join_all(tasks).await;
+                order.take()
+            })
+        }
+
+        assert_eq!(interleaving(42), interleaving(42));
+        let reference = interleaving(42);
+        assert!((0..16).any(|seed| interleaving(seed) != reference));
+    }
+
+    #[test]
+    fn simulation_reorders_io_completions() {
+        fn completions(seed: u64, slow_first: bool) -> Vec<u64> {
+            let ex = LocalExecutorBuilder::default()
+                .simulation(seed)
+                .blocking_thread_pool_placement(PoolPlacement::Unbound(4))
+                .make()
+                .unwrap();
+            ex.run(async move {
+                let order = Rc::new(RefCell::new(Vec::new()));
+                let tasks: Vec<_> = (0..8)
+                    .map(|i| {
+                        let order = order.clone();
+                        let millis = if slow_first { 8 - i } else { i };
+                        crate::spawn_local(async move {
+                            crate::executor()
+                                .spawn_blocking(move || {
+                                    std::thread::sleep(Duration::from_millis(millis))
+                                })
+                                .await;
+                            order.borrow_mut().push(i);
+                        })
+                    })
+                    .collect();
+                join_all(tasks).await;
+                order.take()
+            })
+        }
+
+        // how long the I/O takes doesn't matter, only the seed does
+        let reference = completions(42, false);
+        assert_eq!(completions(42, true), reference);
+        assert!((0..16).any(|seed| completions(seed, false) != reference));
+    }
+
+    #[test]
+    fn simulation_runs_timers_on_virtual_time() {
+        let ex = LocalExecutorBuilder::default()
+            .simulation(7)
+            .make()
+            .unwrap();
+
+        let start = Instant::now();
+        ex.run(async {
+            let fired = Rc::new(RefCell::new(Vec::new()));
+            let tasks: Vec<_> = [3, 1, 2]
+                .into_iter()
+                .map(|hours| {
+                    let fired = fired.clone();
+                    crate::spawn_local(async move {
+                        crate::timer::sleep(Duration::from_secs(hours * 3600)).await;
+                        fired.borrow_mut().push(hours);
+                    })
+                })
+                .collect();
+            join_all(tasks).await;
+            assert_eq!(*fired.borrow(), vec![1, 2, 3]);
+        });
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn tasks_reenter_the_span_they_were_spawned_in() {
+        tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            LocalExecutor::default().run(async {
+                let span = tracing::info_span!("request");
+                let task = span.in_scope(|| {
+                    crate::spawn_local(async {
+                        let first = tracing::Span::current().id();
+                        crate::executor().yield_now().await;
+                        (first, tracing::Span::current().id())
+                    })
+                });
+                assert_eq!(task.await, (span.id(), span.id()));
+
+                // the spans of the scheduler are not captured
+                let task = crate::spawn_local(async { tracing::Span::current().id() });
+                assert_eq!(task.await, None);
+            });
+        });
+    }
+
+    #[test]
+    fn loop_hooks() {
+        let parks = Arc::new(AtomicUsize::new(0));
+        let unparks = Arc::new(AtomicUsize::new(0));
+        let parked = Arc::new(Mutex::new(Duration::ZERO));
+        let passes = Arc::new(AtomicUsize::new(0));
+        let start = Instant::now();
+        LocalExecutorBuilder::default()
+            .on_park(enclose! { (parks) move || {
+                parks.fetch_add(1, Ordering::Relaxed);
+            }})
+            .on_unpark(enclose! { (unparks, parked) move |duration| {
+                unparks.fetch_add(1, Ordering::Relaxed);
+                *parked.lock().unwrap() += duration;
+            }})
+            .on_scheduler_pass(enclose! { (passes) move || {
+                passes.fetch_add(1, Ordering::Relaxed);
+            }})
+            .make()
+            .unwrap()
+            .run(sleep(Duration::from_millis(50)));
+
+        assert!(parks.load(Ordering::Relaxed) > 0);
+        assert_eq!(
+            parks.load(Ordering::Relaxed),
+            unparks.load(Ordering::Relaxed)
+        );
+        assert!(passes.load(Ordering::Relaxed) > 0);
+        let parked = *parked.lock().unwrap();
+        assert!(parked > Duration::ZERO);
+        assert!(parked <= start.elapsed());
+    }
+
+    #[test]
+    fn park_hook_wakes_tasks_up() {
+        // nothing but the hook can wake the future up, so the executor would
+        // sleep forever if it parked after running the hook
+        let flushed = Arc::new(AtomicBool::new(false));
+        let waiter: Arc<Mutex<Option<Waker>>> = Default::default();
+        LocalExecutorBuilder::default()
+            .on_park(enclose! { (flushed, waiter) move || {
+                flushed.store(true, Ordering::Relaxed);
+                if let Some(waker) = waiter.lock().unwrap().take() {
+                    waker.wake();
+                }
+            }})
+            .make()
+            .unwrap()
+            .run(poll_fn(|cx| {
+                if flushed.load(Ordering::Relaxed) {
+                    return Poll::Ready(());
+                }
+                *waiter.lock().unwrap() = Some(cx.waker().clone());
+                Poll::Pending
+            }));
     }
 
     #[test]
-    fn executor_pool_builder_shards_limit() {
-        let cpu_set = CpuSet::online().unwrap();
-        assert!(!cpu_set.is_empty());
+    fn configure_shards_individually() {
+        let indexes: Arc<Mutex<Vec<usize>>> = Default::default();
+        let mut handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .name("pool")
+            .configure_shards(enclose! { (indexes) move |index, cpus, builder| {
+                assert!(cpus.is_empty());
+                indexes.lock().unwrap().push(index);
+                builder.name(&format!("shard{index}"))
+            }})
+            .on_all_shards(|| async move {
+                let token = crate::executor().shutdown_token().unwrap();
+                token.wait().await;
+                std::thread::current().name().unwrap().to_string()
+            })
+            .unwrap();
+        handles.add_shard().unwrap();
 
-        // test: confirm that we can always get shards up to the # of cpus
-        {
-            let mut placements = vec![
-                (false, PoolPlacement::Unbound(cpu_set.len())),
-                (false, PoolPlacement::Fenced(cpu_set.len(), cpu_set.clone())),
-                (true, PoolPlacement::MaxSpread(cpu_set.len(), None)),
-                (
-                    true,
-                    PoolPlacement::MaxSpread(cpu_set.len(), Some(cpu_set.clone())),
-                ),
-                (true, PoolPlacement::MaxPack(cpu_set.len(), None)),
-                (
-                    true,
-                    PoolPlacement::MaxPack(cpu_set.len(), Some(cpu_set.clone())),
-                ),
-            ];
+        let mut names: Vec<_> = handles
+            .shutdown(Duration::from_secs(1))
+            .results
+            .into_iter()
+            .map(|res| res.unwrap().split('-').next().unwrap().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["shard0", "shard1", "shard2"]);
+        let mut indexes = indexes.lock().unwrap().clone();
+        indexes.sort();
+        assert_eq!(indexes, [0, 1, 2]);
+    }
 
-            for (_shard_limited, p) in placements.drain(..) {
-                LocalExecutorPoolBuilder::new(p)
-                    .on_all_shards(|| async move {})
-                    .unwrap()
-                    .join_all();
-            }
+    #[test]
+    fn configure_shards_keeps_pool_placement() {
+        let res = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .configure_shards(|index, _, builder| match index {
+                1 => LocalExecutorBuilder {
+                    placement: Placement::Fixed(0),
+                    ..builder
+                },
+                _ => builder,
+            })
+            .on_all_shards(|| async {});
+        match res {
+            Err(GlommioError::BuilderError(BuilderErrorKind::ShardPlacement { index: 1 })) => {}
+            Err(x) => panic!("unexpected error {x:?}"),
+            Ok(_) => panic!("the placement of a shard changed"),
         }
+    }
 
-        // test: confirm that some placements fail when shards are # of cpus + 1
-        {
-            let mut placements = vec![
-                (false, PoolPlacement::Unbound(1 + cpu_set.len())),
-                (
-                    false,
-                    PoolPlacement::Fenced(1 + cpu_set.len(), cpu_set.clone()),
-                ),
-                (true, PoolPlacement::MaxSpread(1 + cpu_set.len(), None)),
-                (
-                    true,
-                    PoolPlacement::MaxSpread(1 + cpu_set.len(), Some(cpu_set.clone())),
-                ),
-                (true, PoolPlacement::MaxPack(1 + cpu_set.len(), None)),
-                (
-                    true,
-                    PoolPlacement::MaxPack(1 + cpu_set.len(), Some(cpu_set)),
-                ),
-            ];
+    #[test]
+    fn configure_shards_overrides_stall_detection() {
+        let generated = Arc::new(AtomicUsize::new(0));
+        LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .detect_stalls(Some(Box::new(enclose! { (generated) move || {
+                generated.fetch_add(1, Ordering::Relaxed);
+                Box::new(crate::DefaultStallDetectionHandler {})
+            }})))
+            .configure_shards(|index, _, builder| match index {
+                0 => builder.detect_stalls(None),
+                _ => builder,
+            })
+            .on_all_shards(|| async {})
+            .unwrap()
+            .join_all();
+        assert_eq!(generated.load(Ordering::Relaxed), 1);
+    }
 
-            for (shard_limited, p) in placements.drain(..) {
-                match LocalExecutorPoolBuilder::new(p).on_all_shards(|| async move {}) {
-                    Ok(handles) => {
-                        handles.join_all();
-                        assert!(!shard_limited);
-                    }
-                    Err(_) => assert!(shard_limited),
+    #[test]
+    fn watchdog_reports_unresponsive_executors() {
+        let reports: Arc<Mutex<Vec<ExecutorLiveness>>> = Default::default();
+        let tickets = Arc::new(AtomicUsize::new(0));
+        let handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .watchdog(
+                Duration::from_millis(50),
+                enclose! { (reports) move |liveness| {
+                    reports.lock().unwrap().push(liveness.clone());
+                }},
+            )
+            .on_all_shards(enclose! { (tickets) move || async move {
+                // one shard blocks its thread, the other sleeps
+                let blocks = tickets.fetch_add(1, Ordering::Relaxed) == 0;
+                if blocks {
+                    std::thread::sleep(Duration::from_millis(300));
+                } else {
+                    sleep(Duration::from_millis(300)).await;
                 }
-            }
-        }
+                (crate::executor().id(), blocks)
+            }})
+            .unwrap();
+
+        std::thread::sleep(Duration::from_millis(150));
+        let liveness = handles.liveness();
+        assert_eq!(liveness.len(), 2);
+        let results: Vec<_> = handles.join_all().into_iter().map(Result::unwrap).collect();
+        let blocker = results.iter().find(|(_, blocks)| *blocks).unwrap().0;
+        let sleeper = results.iter().find(|(_, blocks)| !*blocks).unwrap().0;
+
+        let blocked = liveness
+            .iter()
+            .find(|l| l.executor_id() == blocker)
+            .unwrap();
+        assert!(!blocked.is_parked());
+        assert!(!blocked.is_responsive());
+        assert_eq!(blocked.task_queue(), Some(TaskQueueHandle::default()));
+        let slept = liveness
+            .iter()
+            .find(|l| l.executor_id() == sleeper)
+            .unwrap();
+        assert!(slept.is_parked());
+        assert!(slept.is_responsive());
+
+        // reported once, no matter how long it stayed blocked
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].executor_id(), blocker);
+        assert!(reports[0].last_heartbeat().elapsed() >= Duration::from_millis(300));
     }
 
     #[test]
-    fn scoped_task() {
-        LocalExecutor::default().run(async {
-            let mut a = 1;
-            unsafe {
-                crate::spawn_scoped_local(async {
-                    a = 2;
-                })
-                .await;
-            }
-            crate::executor().yield_task_queue_now().await;
-            assert_eq!(a, 2);
+    fn poll_budget_is_fair_within_task_queue() {
+        // how many messages the first task received when the second one ran
+        let received_by_second_poll = |budget| {
+            LocalExecutor::default().run(async move {
+                let tq = crate::executor().create_task_queue(
+                    Shares::default(),
+                    Latency::NotImportant,
+                    "budget",
+                );
+                crate::executor()
+                    .set_task_queue_poll_budget(tq, budget)
+                    .unwrap();
+                let (sender, receiver) = crate::channels::local_channel::new_bounded(100);
+                for i in 0..100 {
+                    sender.try_send(i).unwrap();
+                }
+                drop(sender);
 
-            let mut a = 1;
-            let do_later = unsafe {
-                crate::spawn_scoped_local(async {
-                    a = 2;
-                })
-            };
+                let received = Rc::new(Cell::new(0));
+                let receiving = crate::spawn_local_into(
+                    enclose! { (received) async move {
+                        while receiver.recv().await.is_some() {
+                            received.set(received.get() + 1);
+                        }
+                    }},
+                    tq,
+                )
+                .unwrap();
+                let other = crate::spawn_local_into(
+                    enclose! { (received) async move { received.get() }},
+                    tq,
+                )
+                .unwrap();
+                let (_, received_then) = join(receiving, other).await;
+                assert_eq!(received.get(), 100);
+                received_then
+            })
+        };
 
-            crate::executor().yield_task_queue_now().await;
-            do_later.await;
-            assert_eq!(a, 2);
+        assert_eq!(received_by_second_poll(Some(4)), 4);
+        assert_eq!(received_by_second_poll(None), 100);
+
+        LocalExecutor::default().run(async {
+            let tq = TaskQueueHandle { index: 100 };
+            assert!(crate::executor()
+                .set_task_queue_poll_budget(tq, Some(1))
+                .is_err());
         });
     }
 
     #[test]
-    fn executor_pool_builder_thread_panic() {
-        let nr_execs = 8;
-        let res = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(nr_execs))
-            .on_all_shards(|| async move { panic!("join handle will be Err") })
-            .unwrap()
-            .join_all();
+    fn dump_tasks_reports_last_poll_with_scheduling_delays() {
+        let ex = LocalExecutorBuilder::default()
+            .record_scheduling_delays(true)
+            .make()
+            .unwrap();
+        ex.run(async {
+            let idle = crate::spawn_local(sleep(Duration::from_secs(10)));
+            idle.set_label("idle");
+            crate::executor().yield_task_queue_now().await;
 
-        assert_eq!(nr_execs, res.len());
-        assert!(res.into_iter().all(|r| r.is_err()));
+            let dump = crate::executor().dump_tasks();
+            let idle_dump = dump.iter().find(|t| t.label() == Some("idle")).unwrap();
+            assert!(idle_dump.last_poll().is_some());
+            assert_eq!(idle_dump.stats().polls(), 0);
+        });
     }
 
     #[test]
-    fn executor_pool_builder_return_values() {
-        let nr_execs = 8;
-        let x = Arc::new(AtomicUsize::new(0));
-        let mut values = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(nr_execs))
-            .on_all_shards(|| async move { x.fetch_add(1, Ordering::Relaxed) })
-            .unwrap()
-            .join_all()
-            .into_iter()
-            .map(Result::unwrap)
-            .collect::<Vec<_>>();
+    fn dump_tasks_reports_live_tasks() {
+        let ex = LocalExecutorBuilder::default()
+            .record_task_stats(true)
+            .make()
+            .unwrap();
+        ex.run(async {
+            let tq = crate::executor().create_task_queue(
+                Shares::default(),
+                Latency::NotImportant,
+                "dumped",
+            );
+            let line = line!() + 1;
+            let idle = crate::spawn_local_into(sleep(Duration::from_secs(10)), tq).unwrap();
+            idle.set_label("idle");
+            crate::executor().yield_task_queue_now().await;
+            let scheduled = crate::spawn_local_into(async {}, tq).unwrap();
+            scheduled.set_label("scheduled");
+
+            let dump = crate::executor().dump_tasks();
+            let find = |label| dump.iter().find(|t| t.label() == Some(label)).unwrap();
+
+            let idle_dump = find("idle");
+            assert_eq!(idle_dump.executor_id(), crate::executor().id());
+            assert_eq!(idle_dump.task_queue(), tq);
+            assert_eq!(idle_dump.task_queue_name(), "dumped");
+            assert_eq!(idle_dump.state(), task::TaskState::Idle);
+            assert!(!idle_dump.is_detached());
+            assert_eq!(idle_dump.spawn_location().file(), file!());
+            assert_eq!(idle_dump.spawn_location().line(), line);
+            assert_eq!(idle_dump.stats().polls(), 1);
+            assert!(idle_dump.stats().last_poll().is_some());
+            assert_eq!(idle_dump.last_poll(), idle_dump.stats().last_poll());
+
+            let scheduled_dump = find("scheduled");
+            assert_eq!(scheduled_dump.state(), task::TaskState::Scheduled);
+            assert!(scheduled_dump.stats().last_poll().is_none());
+            assert!(scheduled_dump.last_poll().is_none());
+            assert!(idle_dump.age() >= scheduled_dump.age());
+
+            scheduled.await;
+            drop(idle);
+            crate::executor().yield_task_queue_now().await;
+            assert!(crate::executor()
+                .dump_tasks()
+                .iter()
+                .all(|t| t.task_queue() != tq));
+        });
+    }
 
-        values.sort_unstable();
-        assert_eq!(values, (0..nr_execs).collect::<Vec<_>>());
+    #[test]
+    fn dump_pool_tasks_covers_all_shards() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let dumped = Arc::new(AtomicUsize::new(0));
+        let handles = LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(3))
+            .on_all_shards(enclose! { (started, dumped) move || async move {
+                let token = crate::executor().shutdown_token().unwrap();
+                let task = crate::spawn_local(enclose! { (token) async move { token.wait().await }});
+                task.set_label("waiter");
+                let mut shards = None;
+                if started.fetch_add(1, Ordering::AcqRel) == 0 {
+                    while started.load(Ordering::Acquire) < 3 {
+                        sleep(Duration::from_millis(1)).await;
+                    }
+                    let dump = crate::executor().dump_pool_tasks().await;
+                    let mut ids: Vec<_> = dump
+                        .iter()
+                        .filter(|t| t.label() == Some("waiter"))
+                        .map(|t| t.executor_id())
+                        .collect();
+                    ids.sort_unstable();
+                    ids.dedup();
+                    shards = Some(ids.len());
+                    dumped.store(1, Ordering::Release);
+                }
+                task.await;
+                shards
+            }})
+            .unwrap();
+
+        while dumped.load(Ordering::Acquire) == 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let report = handles.shutdown(Duration::from_secs(5));
+        assert!(report.missed_deadline.is_empty());
+        let shards: Vec<_> = report
+            .results
+            .into_iter()
+            .filter_map(|res| res.unwrap())
+            .collect();
+        assert_eq!(shards, vec![3]);
     }
 
     #[test]
@@ -3897,8 +7822,16 @@ mod test {
             }
         };
 
-        let mut handles = PoolThreadHandles::new();
-        let mut cpu_set_gen = placement::CpuSetGenerator::pool(builder.placement.clone()).unwrap();
+        let pool = PoolState {
+            stealable_tasks: StealableQueue::new(),
+            shutdown: ShutdownToken::new(),
+            membership: PoolMembership::new(),
+            watchdog: None,
+        };
+        let mut handles =
+            PoolThreadHandles::new(pool.shutdown.clone(), pool.membership.clone(), true);
+        let mut cpu_set_gen =
+            placement::CpuSetGenerator::pool(builder.placement.clone(), true).unwrap();
         let latch = Latch::new(builder.placement.executor_count());
 
         let ii_cxl = 2;
@@ -3907,8 +7840,9 @@ mod test {
                 std::thread::sleep(std::time::Duration::from_millis(100));
                 assert!(ii_cxl <= latch.cancel().unwrap());
             }
-            match builder.spawn_thread(&mut cpu_set_gen, &latch, fut_gen.clone()) {
-                Ok(handle) => handles.push(handle),
+            let cpus = cpu_set_gen.next();
+            match builder.spawn_thread(ii, cpus.clone(), &latch, &pool, fut_gen.clone()) {
+                Ok((id, handle)) => handles.push(id, cpus, handle),
                 Err(_) => break,
             }
         }
@@ -4206,6 +8140,74 @@ mod test {
         assert!(ret.is_err());
     }
 
+    #[test]
+    fn blocking_pool_rejects_when_full() {
+        LocalExecutorBuilder::default()
+            .blocking_thread_pool_placement(PoolPlacement::Unbound(1))
+            .blocking_queue_capacity(0)
+            .blocking_queue_policy(BlockingQueuePolicy::Reject)
+            .make()
+            .unwrap()
+            .run(async {
+                let started = Arc::new(std::sync::atomic::AtomicBool::new(false));
+                let (tx, rx) = std::sync::mpsc::channel::<()>();
+                let busy =
+                    crate::spawn_local(executor().spawn_blocking(enclose!((started) move || {
+                        started.store(true, Ordering::Release);
+                        rx.recv().unwrap();
+                    })));
+                while !started.load(Ordering::Acquire) {
+                    sleep(Duration::from_millis(1)).await;
+                }
+
+                // the only thread is busy, and the queue has no room
+                let err = executor().try_spawn_blocking(|| {}).await.unwrap_err();
+                assert_eq!(
+                    std::io::Error::from(err).kind(),
+                    std::io::ErrorKind::WouldBlock
+                );
+                tx.send(()).unwrap();
+                busy.await;
+
+                let stats = executor().blocking_pool_stats();
+                assert_eq!(stats.user.threads(), 1);
+                assert_eq!(stats.user.queue_capacity(), 0);
+                assert_eq!(stats.user.completed(), 1);
+                assert_eq!(stats.user.rejected(), 1);
+                assert_eq!(stats.internal.completed(), 0);
+            });
+    }
+
+    #[test]
+    fn blocking_pool_internal_lane() {
+        LocalExecutorBuilder::default()
+            .blocking_thread_pool_placement(PoolPlacement::Unbound(1))
+            .internal_blocking_thread_pool_placement(PoolPlacement::Unbound(1))
+            .make()
+            .unwrap()
+            .run(async {
+                let (tx, rx) = std::sync::mpsc::channel::<()>();
+                let busy = crate::spawn_local(executor().spawn_blocking(move || {
+                    rx.recv().unwrap();
+                }));
+
+                // the thread running closures is stuck, but file operations
+                // have a thread of their own
+                let path = std::env::temp_dir()
+                    .join(format!("glommio-internal-lane-{}", std::process::id()));
+                assert!(crate::io::remove(&path).await.is_err());
+                tx.send(()).unwrap();
+                busy.await;
+
+                let stats = executor().blocking_pool_stats();
+                assert_eq!(stats.internal.threads(), 1);
+                assert_eq!(stats.internal.completed(), 1);
+                assert_eq!(stats.user.completed(), 1);
+                assert_eq!(stats.internal.wait_time_us().count(), 1);
+                assert_eq!(stats.user.run_time_us().count(), 1);
+            });
+    }
+
     #[test]
     fn local_executor_unset() {
         LocalExecutor::default().run(async {});
@@ -4232,4 +8234,239 @@ mod test {
         #[cfg(feature = "native-tls")]
         assert!(unsafe { LOCAL_EX.is_null() });
     }
+
+    #[test]
+    fn spawn_on_remote_executor() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let done = Arc::new(AtomicUsize::new(0));
+
+        let ex1 = LocalExecutorBuilder::default()
+            .spawn({
+                let done = done.clone();
+                move || async move {
+                    let tq = crate::executor().create_task_queue(
+                        Shares::default(),
+                        Latency::NotImportant,
+                        "remote",
+                    );
+                    tx.send((crate::executor().id(), tq)).unwrap();
+                    while done.load(Ordering::Relaxed) == 0 {
+                        sleep(Duration::from_millis(10)).await;
+                    }
+                }
+            })
+            .unwrap();
+
+        let ex2 = LocalExecutorBuilder::default()
+            .spawn(move || async move {
+                let (id, tq) = rx.recv().unwrap();
+                assert_ne!(id, crate::executor().id());
+
+                let (remote_id, remote_tq) = crate::executor()
+                    .spawn_on_into(
+                        id,
+                        async move {
+                            (
+                                crate::executor().id(),
+                                crate::executor().current_task_queue(),
+                            )
+                        },
+                        tq,
+                    )
+                    .unwrap()
+                    .await
+                    .unwrap();
+                assert_eq!(remote_id, id);
+                assert_eq!(remote_tq, tq);
+
+                let res = crate::executor()
+                    .spawn_on_into(id, async {}, TaskQueueHandle { index: 1000 })
+                    .unwrap()
+                    .await;
+                assert!(matches!(
+                    res,
+                    Err(GlommioError::ExecutorError(ExecutorErrorKind::QueueError {
+                        kind: crate::QueueErrorKind::NotFound,
+                        ..
+                    }))
+                ));
+
+                done.store(1, Ordering::Relaxed);
+            })
+            .unwrap();
+
+        ex2.join().unwrap();
+        ex1.join().unwrap();
+    }
+
+    #[test]
+    fn spawn_on_invalid_executor() {
+        LocalExecutor::default().run(async {
+            assert!(crate::executor().spawn_on(usize::MAX, async {}).is_err());
+            assert!(crate::executor().spawn_on(0, async {}).is_err());
+        });
+    }
+
+    #[test]
+    fn spawn_on_exited_executor() {
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        // the waker keeps the notifier of the exited executor alive
+        LocalExecutorBuilder::default()
+            .spawn(move || async move {
+                let waker = futures::future::poll_fn(|cx| Poll::Ready(cx.waker().clone())).await;
+                tx.send((crate::executor().id(), waker)).unwrap();
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        let (id, _waker) = rx.recv().unwrap();
+
+        LocalExecutor::default().run(async move {
+            assert!(matches!(
+                crate::executor().spawn_on(id, async {}),
+                Err(GlommioError::ExecutorError(ExecutorErrorKind::InvalidId(x))) if x == id
+            ));
+        });
+    }
+
+    #[test]
+    fn spawn_stealable_runs_on_idle_executor() {
+        let spawner = Arc::new(AtomicUsize::new(0));
+        let stealer_queue = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+        // counted down by the stealable tasks
+        let ran = Latch::new(20);
+
+        LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .on_all_shards(move || async move {
+                let me = crate::executor().id();
+                let is_spawner = spawner
+                    .compare_exchange(0, me, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok();
+                if !is_spawner {
+                    // so the stealable queue gets a different handle here
+                    crate::executor().create_task_queue(
+                        Shares::default(),
+                        Latency::NotImportant,
+                        "other",
+                    );
+                }
+                let tq = crate::executor().create_task_queue(
+                    Shares::default(),
+                    Latency::NotImportant,
+                    "stealable",
+                );
+                if !is_spawner {
+                    stealer_queue.store(tq.index(), Ordering::Relaxed);
+                    // stay idle so we can steal from the busy executor
+                    while done.load(Ordering::Relaxed) == 0 {
+                        sleep(Duration::from_millis(1)).await;
+                    }
+                    return;
+                }
+
+                let handles = (0..20)
+                    .map(|_| {
+                        let ran = ran.clone();
+                        crate::executor()
+                            .spawn_stealable_into(
+                                async move {
+                                    ran.count_down(1).unwrap();
+                                    (
+                                        crate::executor().id(),
+                                        crate::executor().current_task_queue(),
+                                    )
+                                },
+                                tq,
+                            )
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>();
+
+                // block this executor until the other one ran all the tasks
+                assert_eq!(ran.wait(), LatchState::Ready);
+
+                for handle in handles {
+                    let stealer = handle.executor_id();
+                    let (id, queue) = handle.await.unwrap();
+                    assert_ne!(queue, tq);
+                    assert_eq!(queue.index(), stealer_queue.load(Ordering::Relaxed));
+                    assert_ne!(id, me);
+                    assert_eq!(id, stealer);
+                }
+                done.store(1, Ordering::Relaxed);
+            })
+            .unwrap()
+            .join_all()
+            .into_iter()
+            .for_each(|r| r.unwrap());
+    }
+
+    #[test]
+    fn spawn_stealable_skips_tasks_for_missing_queues() {
+        let spawner = Arc::new(AtomicUsize::new(0));
+        let (done_sender, done_receiver) = flume::bounded::<()>(1);
+        let ran_elsewhere = Arc::new(AtomicUsize::new(0));
+
+        LocalExecutorPoolBuilder::new(PoolPlacement::Unbound(2))
+            .on_all_shards(move || async move {
+                let me = crate::executor().id();
+                if spawner
+                    .compare_exchange(0, me, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_err()
+                {
+                    // park until the spawner is done; this executor doesn't
+                    // have the spawner's task queue
+                    done_receiver.recv_async().await.unwrap();
+                    return;
+                }
+
+                let tq = crate::executor().create_task_queue(
+                    Shares::default(),
+                    Latency::NotImportant,
+                    "only-here",
+                );
+                // give the other executor time to go to sleep
+                std::thread::sleep(Duration::from_millis(50));
+
+                // at the head of the queue, but only this executor can run it
+                let pinned = crate::executor()
+                    .spawn_stealable_into(async { crate::executor().id() }, tq)
+                    .unwrap();
+                let ran = ran_elsewhere.clone();
+                let free = crate::executor().spawn_stealable(async move {
+                    ran.store(1, Ordering::Relaxed);
+                    crate::executor().id()
+                });
+
+                // block this executor: the other one must run the second task
+                // without waiting for us
+                let start = Instant::now();
+                while ran_elsewhere.load(Ordering::Relaxed) == 0 {
+                    assert!(start.elapsed() < Duration::from_secs(5));
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+
+                assert_eq!(pinned.await.unwrap(), me);
+                assert_ne!(free.await.unwrap(), me);
+                done_sender.send(()).unwrap();
+            })
+            .unwrap()
+            .join_all()
+            .into_iter()
+            .for_each(|r| r.unwrap());
+    }
+
+    #[test]
+    fn spawn_stealable_without_pool() {
+        LocalExecutor::default().run(async {
+            let res = crate::executor().spawn_stealable(async { 1 + 2 }).await;
+            assert_eq!(res.unwrap(), 3);
+
+            assert!(crate::executor()
+                .spawn_stealable_into(async {}, TaskQueueHandle { index: 1000 })
+                .is_err());
+        });
+    }
 }
@@ -9,19 +9,21 @@
 #![warn(missing_docs, missing_debug_implementations)]
 
 use crate::{
-    executor::{maybe_activate, TaskQueue},
-    task::{task_impl, JoinHandle},
-    Latency,
+    executor::{in_current_span, maybe_activate, simulation::Simulation, TaskQueue},
+    task::{dump::TaskRef, task_impl, JoinHandle, TaskPanic, TaskStats, Timing},
+    Latency, TaskQueueHandle,
 };
+use ahash::AHashMap;
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::VecDeque,
     future::Future,
     marker::PhantomData,
-    panic::{RefUnwindSafe, UnwindSafe},
+    panic::{Location, RefUnwindSafe, UnwindSafe},
     pin::Pin,
     rc::Rc,
-    task::{Context, Poll},
+    task::{Context, Poll, Waker},
+    time::Instant,
 };
 
 /// A runnable future, ready for execution.
@@ -70,6 +72,25 @@ impl<T> Task<T> {
         handle.cancel();
         handle.await
     }
+
+    /// Sets a label identifying the task in task dumps.
+    pub(crate) fn set_label(&self, label: &'static str) {
+        self.0.as_ref().unwrap().set_label(label)
+    }
+
+    /// Returns statistics about the polls of the task so far.
+    pub(crate) fn stats(&self) -> TaskStats {
+        self.0.as_ref().unwrap().stats()
+    }
+
+    /// Waits for the task to complete, or returns the panic it raised.
+    pub(crate) async fn join(mut self) -> Result<T, TaskPanic> {
+        let handle = self.0.as_mut().unwrap();
+        match (&mut *handle).await {
+            Some(output) => Ok(output),
+            None => Err(handle.take_panic().expect("task has failed")),
+        }
+    }
 }
 
 impl<T> Drop for Task<T> {
@@ -111,10 +132,89 @@ impl LocalQueue {
     }
 }
 
+/// The tasks of an executor whose future has not been dropped yet, across all
+/// its task queues.
+#[derive(Debug, Default)]
+pub(crate) struct LiveTasks {
+    tasks: RefCell<AHashMap<u64, TrackedTask>>,
+    next_id: Cell<u64>,
+    waiter: RefCell<Option<Waker>>,
+}
+
+/// What is known about a live task, as reported by task dumps.
+#[derive(Debug)]
+pub(crate) struct TrackedTask {
+    // `None` until the task is allocated
+    pub(crate) task: Option<TaskRef>,
+    pub(crate) task_queue: TaskQueueHandle,
+    pub(crate) spawn_location: &'static Location<'static>,
+    pub(crate) spawned_at: Instant,
+}
+
+impl LiveTasks {
+    pub(crate) fn count(&self) -> usize {
+        self.tasks.borrow().len()
+    }
+
+    /// Registers a waker to be woken up the next time a task goes away.
+    pub(crate) fn register(&self, waker: &Waker) {
+        *self.waiter.borrow_mut() = Some(waker.clone());
+    }
+
+    /// Returns where the live task `id` was spawned from.
+    pub(crate) fn spawn_location(&self, id: u64) -> Option<&'static Location<'static>> {
+        self.tasks.borrow().get(&id).map(|t| t.spawn_location)
+    }
+
+    /// Calls `f` on every live task.
+    pub(crate) fn for_each(&self, f: impl FnMut(&TrackedTask)) {
+        self.tasks.borrow().values().for_each(f);
+    }
+}
+
+/// Accounts for a live task until dropped along with its future.
+#[derive(Debug)]
+struct LiveTask {
+    live_tasks: Rc<LiveTasks>,
+    id: u64,
+}
+
+impl LiveTask {
+    fn new(
+        live_tasks: Rc<LiveTasks>,
+        task_queue: TaskQueueHandle,
+        spawn_location: &'static Location<'static>,
+    ) -> Self {
+        let id = live_tasks.next_id.get();
+        live_tasks.next_id.set(id + 1);
+        live_tasks.tasks.borrow_mut().insert(
+            id,
+            TrackedTask {
+                task: None,
+                task_queue,
+                spawn_location,
+                spawned_at: Instant::now(),
+            },
+        );
+        Self { live_tasks, id }
+    }
+}
+
+impl Drop for LiveTask {
+    fn drop(&mut self) {
+        self.live_tasks.tasks.borrow_mut().remove(&self.id);
+        let waiter = self.live_tasks.waiter.borrow_mut().take();
+        if let Some(waker) = waiter {
+            waker.wake();
+        }
+    }
+}
+
 /// A single-threaded executor.
 #[derive(Debug)]
 pub(crate) struct LocalExecutor {
     local_queue: LocalQueue,
+    live_tasks: Rc<LiveTasks>,
 
     /// Make sure the type is `!Send` and `!Sync`.
     _marker: PhantomData<Rc<()>>,
@@ -126,9 +226,10 @@ impl RefUnwindSafe for LocalExecutor {}
 
 impl LocalExecutor {
     /// Creates a new single-threaded executor.
-    pub(crate) fn new() -> LocalExecutor {
+    pub(crate) fn new(live_tasks: Rc<LiveTasks>) -> LocalExecutor {
         LocalExecutor {
             local_queue: LocalQueue::new(),
+            live_tasks,
             _marker: PhantomData,
         }
     }
@@ -137,14 +238,26 @@ impl LocalExecutor {
     fn spawn<T>(
         &self,
         executor_id: usize,
+        timing: Timing,
         tq: Rc<RefCell<TaskQueue>>,
         future: impl Future<Output = T>,
+        location: &'static Location<'static>,
     ) -> (Runnable, JoinHandle<T>) {
-        let latency_matters = match tq.borrow().io_requirements.latency_req {
-            Latency::Matters(_) => true,
-            Latency::NotImportant => false,
+        let (latency_matters, task_queue) = {
+            let tq = tq.borrow();
+            let latency_matters = match tq.io_requirements.latency_req {
+                Latency::Matters(_) => true,
+                Latency::NotImportant => false,
+            };
+            (latency_matters, tq.stats.index)
         };
         let tq = Rc::downgrade(&tq);
+        let live_task = LiveTask::new(self.live_tasks.clone(), task_queue, location);
+        let id = live_task.id;
+        let future = in_current_span(async move {
+            let _live_task = live_task;
+            future.await
+        });
 
         // The function that schedules a runnable task when it gets woken up.
         let schedule = move |runnable: Runnable| {
@@ -161,16 +274,23 @@ impl LocalExecutor {
 
         // Create a task, push it into the queue by scheduling it, and return its `Task`
         // handle.
-        task_impl::spawn_local(executor_id, future, schedule, latency_matters)
+        let (runnable, handle) =
+            task_impl::spawn_local(executor_id, id, future, schedule, latency_matters, timing);
+        if let Some(tracked) = self.live_tasks.tasks.borrow_mut().get_mut(&id) {
+            tracked.task = Some(handle.task_ref());
+        }
+        (runnable, handle)
     }
 
     pub(crate) fn spawn_and_run<T>(
         &self,
         executor_id: usize,
+        timing: Timing,
         tq: Rc<RefCell<TaskQueue>>,
         future: impl Future<Output = T>,
+        location: &'static Location<'static>,
     ) -> Task<T> {
-        let (runnable, handle) = self.spawn(executor_id, tq, future);
+        let (runnable, handle) = self.spawn(executor_id, timing, tq, future, location);
         runnable.run_right_away();
         Task(Some(handle))
     }
@@ -178,10 +298,12 @@ impl LocalExecutor {
     pub(crate) fn spawn_and_schedule<T>(
         &self,
         executor_id: usize,
+        timing: Timing,
         tq: Rc<RefCell<TaskQueue>>,
         future: impl Future<Output = T>,
+        location: &'static Location<'static>,
     ) -> Task<T> {
-        let (runnable, handle) = self.spawn(executor_id, tq, future);
+        let (runnable, handle) = self.spawn(executor_id, timing, tq, future, location);
         runnable.schedule();
         Task(Some(handle))
     }
@@ -193,6 +315,21 @@ impl LocalExecutor {
         self.local_queue.pop()
     }
 
+    /// Gets the task the simulation picks from the queue, if one exists.
+    pub(crate) fn pick_task(&self, simulation: &Simulation) -> Option<Runnable> {
+        let mut queue = self.local_queue.queue.borrow_mut();
+        if queue.is_empty() {
+            return None;
+        }
+        // tasks are queued in the order they were woken up in, which may
+        // depend on when I/O completed
+        queue
+            .make_contiguous()
+            .sort_by_key(|runnable| runnable.id());
+        let index = simulation.below(queue.len());
+        queue.remove(index)
+    }
+
     pub(crate) fn is_active(&self) -> bool {
         !self.local_queue.queue.borrow().is_empty()
     }
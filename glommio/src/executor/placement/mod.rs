@@ -47,7 +47,11 @@
 
 mod pq_tree;
 
-use crate::{error::BuilderErrorKind, sys::hardware_topology, CpuLocation, GlommioError};
+use crate::{
+    error::BuilderErrorKind,
+    sys::{hardware_topology, sysfs},
+    CpuLocation, GlommioError,
+};
 
 use pq_tree::{
     marker::{Pack, Priority, Spread},
@@ -57,7 +61,7 @@ use std::{
     collections::{
         hash_map::RandomState,
         hash_set::{Difference, Intersection, IntoIter, Iter, SymmetricDifference, Union},
-        HashSet,
+        HashMap, HashSet,
     },
     convert::TryInto,
     hash::{Hash, Hasher},
@@ -118,50 +122,58 @@ pub enum PoolPlacement {
     /// allow the executors to run on the newly available CPUs.  The
     /// `Fenced` variant allows the number of shards specified in
     /// [`LocalExecutorPoolBuilder::new`] to be greater than the number of CPUs
-    /// as long as at least one CPU is included in `CpuSet`.
+    /// as long as at least one CPU is included in `CpuSet`.  CPUs the process
+    /// is not allowed to run on (see [`CpuSet::allowed`]) are left out of the
+    /// set.
     ///
     /// #### Errors
     ///
-    /// If the provided [`CpuSet`] contains no CPUs, a call to
+    /// If the provided [`CpuSet`] contains no CPUs, or none the process is
+    /// allowed to run on, a call to
     /// [`LocalExecutorPoolBuilder::on_all_shards`] will return `Result::
     /// Err`.
     Fenced(usize, CpuSet),
     /// Each [`LocalExecutor`] to create is pinned to a particular
     /// [`CpuLocation`] such that the set of all CPUs selected has a high
     /// degree of separation. The selection proceeds from all CPUs that are
-    /// online in a non-deterministic manner.  The `Option<CpuSet>`
-    /// parameter may be used to restrict the [`CpuSet`] from which CPUs are
-    /// selected; specifying `None` is equivalent to using
-    /// `Some(CpuSet::online()?)`.
+    /// online and allowed in a non-deterministic manner.  The
+    /// `Option<CpuSet>` parameter may be used to restrict the [`CpuSet`] from
+    /// which CPUs are selected; specifying `None` is equivalent to using
+    /// `Some(CpuSet::allowed()?)`.
     ///
     /// #### Errors
     ///
     /// If the number of shards is greater than the number of CPUs available,
-    /// then a call to [`LocalExecutorPoolBuilder::on_all_shards`] will
-    /// return `Result:: Err`.
+    /// or than the number of those the process is allowed to run on, then a
+    /// call to [`LocalExecutorPoolBuilder::on_all_shards`] will return
+    /// `Result:: Err`.
     MaxSpread(usize, Option<CpuSet>),
     /// Each [`LocalExecutor`] to create is pinned to a particular
     /// [`CpuLocation`] such that the set of all CPUs selected has a low
     /// degree of separation. The selection proceeds from all CPUs that are
-    /// online in a non-deterministic manner.  The `Option<CpuSet>`
-    /// parameter may be used to restrict the [`CpuSet`] from which CPUs are
-    /// selected; specifying `None` is equivalent to using
-    /// `Some(CpuSet::online()?)`.
+    /// online and allowed in a non-deterministic manner.  The
+    /// `Option<CpuSet>` parameter may be used to restrict the [`CpuSet`] from
+    /// which CPUs are selected; specifying `None` is equivalent to using
+    /// `Some(CpuSet::allowed()?)`.
     ///
     /// #### Errors
     ///
     /// If the number of shards is greater than the number of CPUs available,
-    /// then a call to [`LocalExecutorPoolBuilder::on_all_shards`] will
-    /// return `Result:: Err`.
+    /// or than the number of those the process is allowed to run on, then a
+    /// call to [`LocalExecutorPoolBuilder::on_all_shards`] will return
+    /// `Result:: Err`.
     MaxPack(usize, Option<CpuSet>),
     /// One [`LocalExecutor`] is bound to each of the [`CpuSet`]s specified by
     /// `Custom`. The number of `CpuSet`s in the `Vec` should match the
-    /// number of shards requested from the pool builder.
+    /// number of shards requested from the pool builder.  CPUs the process is
+    /// not allowed to run on (see [`CpuSet::allowed`]) are left out of each
+    /// set.
     ///
     /// #### Errors
     ///
     /// [`LocalExecutorPoolBuilder::on_all_shards`] will return `Result::Err` if
-    /// any of the provided [`CpuSet`] is empty.
+    /// any of the provided [`CpuSet`] is empty, or has no CPU the process is
+    /// allowed to run on.
     Custom(Vec<CpuSet>),
 }
 
@@ -181,7 +193,7 @@ impl PoolPlacement {
     /// Probe the topology of the system and materialize this [`PoolPlacement`]
     /// into a set of [`CpuSet`]
     pub fn generate_cpu_set(self) -> Result<CpuSetGenerator> {
-        CpuSetGenerator::pool(self)
+        CpuSetGenerator::pool(self, true)
     }
 
     /// Shrinks the pool placement policy to the first `len` placements.
@@ -203,6 +215,82 @@ impl PoolPlacement {
             }
         }
     }
+
+    /// Pins `nr_shards` executors to CPUs isolated from general scheduling
+    /// (see [`CpuSet::isolated`]), such that no two executors run on the same
+    /// core: only one of the hyper-threads of each core is selected.  This is
+    /// a `MaxSpread` placement over those CPUs.
+    ///
+    /// Isolated CPUs are usually left out of the affinity mask processes
+    /// inherit, so placements consider them allowed as long as they are in
+    /// the effective cpuset of the cgroup of the process.  Executors placed
+    /// this way default to a blocking thread pool placed with
+    /// [`PoolPlacement::housekeeping`], so that blocking operations stay off
+    /// isolated CPUs.
+    ///
+    /// #### Errors
+    ///
+    /// Fails if the isolated CPUs can't be read.  If the number of shards is
+    /// greater than the number of isolated cores available, then a call to
+    /// [`LocalExecutorPoolBuilder::on_all_shards`] will return `Result::Err`.
+    ///
+    /// [`LocalExecutorPoolBuilder::on_all_shards`]: super::LocalExecutorPoolBuilder::on_all_shards
+    pub fn isolated(nr_shards: usize) -> Result<Self> {
+        Ok(PoolPlacement::MaxSpread(
+            nr_shards,
+            Some(CpuSet::isolated()?.one_per_core()),
+        ))
+    }
+
+    /// Binds `nr_shards` executors, or blocking threads, to the housekeeping
+    /// CPUs (see [`CpuSet::housekeeping`]), which are not isolated from
+    /// general scheduling and share no core with an isolated CPU.  This is a
+    /// `Fenced` placement over those CPUs, meant for
+    /// [`LocalExecutorPoolBuilder::blocking_thread_pool_placement`] when
+    /// executors are placed with [`PoolPlacement::isolated`].
+    ///
+    /// #### Errors
+    ///
+    /// Fails if the housekeeping CPUs can't be read.  If there are none, a
+    /// call to [`LocalExecutorPoolBuilder::on_all_shards`] will return
+    /// `Result::Err`.
+    ///
+    /// [`LocalExecutorPoolBuilder::on_all_shards`]: super::LocalExecutorPoolBuilder::on_all_shards
+    /// [`LocalExecutorPoolBuilder::blocking_thread_pool_placement`]: super::LocalExecutorPoolBuilder::blocking_thread_pool_placement
+    pub fn housekeeping(nr_shards: usize) -> Result<Self> {
+        Ok(PoolPlacement::Fenced(nr_shards, CpuSet::housekeeping()?))
+    }
+
+    /// The default placement of the blocking thread pool of an executor placed
+    /// according to this policy: a single thread, placed the same way unless
+    /// that would put it on an isolated CPU.
+    pub(super) fn for_blocking_pool(self) -> Self {
+        if self.is_isolated() {
+            if let Ok(placement) = PoolPlacement::housekeeping(1) {
+                return placement;
+            }
+        }
+        self.shrink_to(1)
+    }
+
+    /// Whether every CPU set of this placement only has isolated CPUs
+    fn is_isolated(&self) -> bool {
+        let sets: Vec<&CpuSet> = match self {
+            PoolPlacement::Fenced(_, set)
+            | PoolPlacement::MaxSpread(_, Some(set))
+            | PoolPlacement::MaxPack(_, Some(set)) => vec![set],
+            PoolPlacement::Custom(sets) => sets.iter().collect(),
+            _ => return false,
+        };
+        let isolated = match CpuSet::isolated() {
+            Ok(isolated) if !isolated.is_empty() => isolated,
+            _ => return false,
+        };
+        !sets.is_empty()
+            && sets
+                .iter()
+                .all(|set| !set.is_empty() && set.is_subset(&isolated))
+    }
 }
 
 impl From<Placement> for PoolPlacement {
@@ -258,11 +346,14 @@ pub enum Placement {
     /// of CPUs specified by [`CpuSet`].  With an unfiltered CPU
     /// set returned by [`CpuSet::online`], this is similar to using `Unbound`
     /// with the distinction that bringing additional CPUs online will not
-    /// allow the executor to run on the newly available CPUs.
+    /// allow the executor to run on the newly available CPUs.  CPUs the process
+    /// is not allowed to run on (see [`CpuSet::allowed`]) are left out of the
+    /// set.
     ///
     /// #### Errors
     ///
-    /// If the provided [`CpuSet`] contains no CPUs, the builder will fail.
+    /// If the provided [`CpuSet`] contains no CPUs, or none the process is
+    /// allowed to run on, the builder will fail.
     Fenced(CpuSet),
     /// The [`LocalExecutor`] is bound to the CPU specified by
     /// `Fixed`.
@@ -270,7 +361,7 @@ pub enum Placement {
     /// #### Errors
     ///
     /// [`LocalExecutorBuilder`] will return `Result::Err` if the CPU doesn't
-    /// exist.
+    /// exist, or if the process is not allowed to run on it.
     Fixed(usize),
 }
 
@@ -278,7 +369,41 @@ impl Placement {
     /// Probe the topology of the system and materialize this [`Placement`]
     /// into a [`CpuSet`]
     pub fn generate_cpu_set(self) -> Result<CpuSetGenerator> {
-        CpuSetGenerator::one(self)
+        CpuSetGenerator::one(self, true)
+    }
+
+    /// Pins the [`LocalExecutor`] to the first CPU isolated from general
+    /// scheduling, as a `Fixed` placement.  Please see
+    /// [`PoolPlacement::isolated`] for details, and to place several
+    /// executors on distinct isolated cores.
+    ///
+    /// #### Errors
+    ///
+    /// Fails if there is no isolated CPU.
+    ///
+    /// [`LocalExecutor`]: super::LocalExecutor
+    pub fn isolated() -> Result<Self> {
+        match CpuSet::isolated()?.iter().map(|l| l.cpu).min() {
+            Some(cpu) => Ok(Placement::Fixed(cpu)),
+            None => Err(GlommioError::BuilderError(
+                BuilderErrorKind::InsufficientCpus {
+                    required: 1,
+                    available: 0,
+                },
+            )),
+        }
+    }
+
+    /// Binds the [`LocalExecutor`] to the housekeeping CPUs, as a `Fenced`
+    /// placement.  Please see [`PoolPlacement::housekeeping`] for details.
+    ///
+    /// #### Errors
+    ///
+    /// Fails if the housekeeping CPUs can't be read.
+    ///
+    /// [`LocalExecutor`]: super::LocalExecutor
+    pub fn housekeeping() -> Result<Self> {
+        Ok(Placement::Fenced(CpuSet::housekeeping()?))
     }
 }
 
@@ -341,6 +466,73 @@ impl CpuSet {
         Ok(Self::from_iter(topo))
     }
 
+    /// Creates a `CpuSet` representing all CPUs that are online and that this
+    /// process is allowed to run on, according to its affinity mask and the
+    /// effective cpuset of its cgroup.  This is the set placements select
+    /// CPUs from by default.
+    /// The function will return an `Err` if the hardware topology or the
+    /// allowed CPUs could not be obtained from this machine.
+    pub fn allowed() -> Result<Self> {
+        let allowed = hardware_topology::get_allowed_cpus()?;
+        Ok(Self::online()?.filter(|l| allowed.contains(&l.cpu)))
+    }
+
+    /// Creates a `CpuSet` representing all CPUs that are online and isolated
+    /// from general scheduling, either with the `isolcpus` kernel parameter
+    /// or with `nohz_full`.  Unlike [`CpuSet::allowed`], the set is not
+    /// restricted to the affinity mask of the process, which usually leaves
+    /// isolated CPUs out.
+    /// The function will return an `Err` if the hardware topology or the
+    /// isolated CPUs could not be obtained from this machine.
+    pub fn isolated() -> Result<Self> {
+        let isolated = sysfs::isolated_cpus()?;
+        Ok(Self::online()?.filter(|l| isolated.contains(&l.cpu)))
+    }
+
+    /// Creates a `CpuSet` representing the [allowed](CpuSet::allowed) CPUs
+    /// that are left for the housekeeping work of the kernel and of other
+    /// threads: those that are not [isolated](CpuSet::isolated) and don't
+    /// share a core with an isolated CPU.
+    /// The function will return an `Err` if the hardware topology or the
+    /// allowed or isolated CPUs could not be obtained from this machine.
+    pub fn housekeeping() -> Result<Self> {
+        Self::allowed()?.without_isolated()
+    }
+
+    fn without_isolated(self) -> Result<Self> {
+        let isolated = Self::isolated()?
+            .iter()
+            .map(|l| (l.numa_node, l.package, l.core))
+            .collect::<HashSet<_>>();
+        Ok(self.filter(|l| !isolated.contains(&(l.numa_node, l.package, l.core))))
+    }
+
+    /// Keeps a single CPU of each core, the one with the lowest id, such that
+    /// no two [`CpuLocation`]s of the resulting `CpuSet` are hyper-threads of
+    /// the same core.
+    ///
+    /// ```no_run
+    /// use glommio::CpuSet;
+    ///
+    /// let cpus = CpuSet::online()
+    ///     .expect("Err: please file an issue with glommio")
+    ///     .one_per_core();
+    /// ```
+    pub fn one_per_core(self) -> Self {
+        let mut cores = HashMap::new();
+        for l in self {
+            cores
+                .entry((l.numa_node, l.package, l.core))
+                .and_modify(|cpu: &mut CpuLocation| {
+                    if l.cpu < cpu.cpu {
+                        *cpu = l.clone();
+                    }
+                })
+                .or_insert(l);
+        }
+        Self::from_iter(cores.into_values())
+    }
+
     /// This method can be used to restrict the CPUs held by `CpuSet`.  The
     /// resulting `CpuSet` will only include [`CpuLocation`]s for which the
     /// provided closure returns `true`. Note that each call to `filter`
@@ -458,6 +650,17 @@ impl CpuIter {
         }
     }
 
+    /// The NUMA node of the CPUs, if they are all on the same node
+    pub(crate) fn numa_node(&self) -> Option<usize> {
+        let mut nodes = match self {
+            Self::Unbound => return None,
+            Self::Single(l) => return Some(l.numa_node),
+            Self::Multi(v) => v.iter().map(|l| l.numa_node),
+        };
+        let node = nodes.next()?;
+        nodes.all(|x| x == node).then_some(node)
+    }
+
     pub fn cpu_binding(self) -> Option<impl IntoIterator<Item = usize>> {
         match self {
             Self::Unbound => None,
@@ -486,13 +689,16 @@ impl Iterator for CpuIter {
 pub enum CpuSetGenerator {
     Unbound,
     Fenced(CpuSet),
-    MaxSpread(MaxSpreader),
-    MaxPack(MaxPacker),
+    // the iterators cycle through the CPUs, so they come with the number of
+    // CPUs not handed out yet
+    MaxSpread(usize, MaxSpreader),
+    MaxPack(usize, MaxPacker),
     Custom(Vec<CpuSet>),
 }
 
 impl CpuSetGenerator {
-    pub(crate) fn pool(placement: PoolPlacement) -> Result<Self> {
+    pub(crate) fn pool(placement: PoolPlacement, allowed_only: bool) -> Result<Self> {
+        let allowed = Self::allowed_cpus(allowed_only)?;
         let this = match placement {
             PoolPlacement::Unbound(nr_shards) => {
                 Self::check_nr_executors(1, nr_shards)?;
@@ -504,7 +710,7 @@ impl CpuSetGenerator {
                 for cpu in cpus.iter() {
                     Self::check_cpu(cpu.cpu)?;
                 }
-                Self::Fenced(cpus)
+                Self::Fenced(Self::restrict(1, cpus, &allowed)?)
             }
             PoolPlacement::MaxSpread(nr_shards, cpus) => {
                 Self::check_nr_executors(1, nr_shards)?;
@@ -516,7 +722,8 @@ impl CpuSetGenerator {
                 for cpu in cpus.iter() {
                     Self::check_cpu(cpu.cpu)?;
                 }
-                Self::MaxSpread(MaxSpreader::from_cpu_set(cpus))
+                let cpus = Self::restrict(nr_shards, cpus, &allowed)?;
+                Self::MaxSpread(cpus.len(), MaxSpreader::from_cpu_set(cpus))
             }
             PoolPlacement::MaxPack(nr_shards, cpus) => {
                 Self::check_nr_executors(1, nr_shards)?;
@@ -528,7 +735,8 @@ impl CpuSetGenerator {
                 for cpu in cpus.iter() {
                     Self::check_cpu(cpu.cpu)?;
                 }
-                Self::MaxPack(MaxPacker::from_cpu_set(cpus))
+                let cpus = Self::restrict(nr_shards, cpus, &allowed)?;
+                Self::MaxPack(cpus.len(), MaxPacker::from_cpu_set(cpus))
             }
             PoolPlacement::Custom(cpu_sets) => {
                 for cpu_set in &cpu_sets {
@@ -537,27 +745,69 @@ impl CpuSetGenerator {
                         Self::check_cpu(cpu.cpu)?;
                     }
                 }
+                let cpu_sets = cpu_sets
+                    .into_iter()
+                    .map(|cpus| Self::restrict(1, cpus, &allowed))
+                    .collect::<Result<_>>()?;
                 Self::Custom(cpu_sets)
             }
         };
         Ok(this)
     }
 
-    pub(crate) fn one(placement: Placement) -> Result<Self> {
+    pub(crate) fn one(placement: Placement, allowed_only: bool) -> Result<Self> {
+        let allowed = Self::allowed_cpus(allowed_only)?;
         let this = match placement {
             Placement::Unbound => Self::Unbound,
             Placement::Fenced(cpus) => {
                 Self::check_nr_cpus(1, &cpus)?;
-                Self::Fenced(cpus)
+                Self::Fenced(Self::restrict(1, cpus, &allowed)?)
             }
             Placement::Fixed(cpu) => {
                 Self::check_cpu(cpu)?;
-                Self::Custom(vec![CpuSet::online()?.filter(|x| x.cpu == cpu)])
+                let cpus = CpuSet::online()?.filter(|x| x.cpu == cpu);
+                Self::Custom(vec![Self::restrict(1, cpus, &allowed)?])
             }
         };
         Ok(this)
     }
 
+    fn allowed_cpus(allowed_only: bool) -> Result<Option<HashSet<usize>>> {
+        if !allowed_only {
+            return Ok(None);
+        }
+        let mut allowed = hardware_topology::get_allowed_cpus()?;
+        // isolated CPUs are usually not in the affinity mask of the process,
+        // but threads can still bind to them, so only the cgroup has a say
+        let cgroup = hardware_topology::get_cgroup_effective_cpus()?;
+        allowed.extend(
+            sysfs::isolated_cpus()?
+                .into_iter()
+                .filter(|cpu| cgroup.as_ref().map_or(true, |cgroup| cgroup.contains(cpu))),
+        );
+        Ok(Some(allowed))
+    }
+
+    /// Restricts `cpus` to the CPUs the process is allowed to run on, if
+    /// `allowed` is set, making sure `required` of them are left.
+    fn restrict(required: usize, cpus: CpuSet, allowed: &Option<HashSet<usize>>) -> Result<CpuSet> {
+        let allowed = match allowed {
+            Some(allowed) => allowed,
+            None => return Ok(cpus),
+        };
+        let cpus = cpus.filter(|l| allowed.contains(&l.cpu));
+        if required <= cpus.len() {
+            Ok(cpus)
+        } else {
+            Err(GlommioError::BuilderError(
+                BuilderErrorKind::InsufficientCpus {
+                    required,
+                    available: cpus.len(),
+                },
+            ))
+        }
+    }
+
     fn check_nr_executors(minimum: usize, shards: usize) -> Result<()> {
         if minimum <= shards {
             Ok(())
@@ -600,6 +850,27 @@ impl CpuSetGenerator {
         Ok(())
     }
 
+    /// Like [`next`](CpuSetGenerator::next), but fails when every CPU of the
+    /// placement was handed out already, rather than panicking, or, for
+    /// [`PoolPlacement::MaxSpread`] and [`PoolPlacement::MaxPack`], cycling
+    /// back to CPUs in use.
+    pub(crate) fn try_next(&mut self) -> Result<CpuIter> {
+        let exhausted = match self {
+            Self::Custom(cpu_sets) => cpu_sets.is_empty(),
+            Self::MaxSpread(left, _) | Self::MaxPack(left, _) => *left == 0,
+            Self::Unbound | Self::Fenced(_) => false,
+        };
+        if exhausted {
+            return Err(GlommioError::BuilderError(
+                BuilderErrorKind::InsufficientCpus {
+                    required: 1,
+                    available: 0,
+                },
+            ));
+        }
+        Ok(self.next())
+    }
+
     /// A method that generates a [`CpuIter`] according to the provided
     /// [`Placement`] policy. Sequential calls may generate different sets
     /// depending on the [`Placement`].
@@ -607,8 +878,14 @@ impl CpuSetGenerator {
         match self {
             Self::Unbound => CpuIter::Unbound,
             Self::Fenced(cpus) => CpuIter::from_vec(cpus.clone().into_iter().collect()),
-            Self::MaxSpread(it) => CpuIter::from_option(it.next()),
-            Self::MaxPack(it) => CpuIter::from_option(it.next()),
+            Self::MaxSpread(left, it) => {
+                *left = left.saturating_sub(1);
+                CpuIter::from_option(it.next())
+            }
+            Self::MaxPack(left, it) => {
+                *left = left.saturating_sub(1);
+                CpuIter::from_option(it.next())
+            }
             Self::Custom(cpu_sets) => CpuIter::Multi(
                 cpu_sets
                     .pop()
@@ -769,6 +1046,112 @@ mod test {
         assert_eq!(0, set.into_iter().count());
     }
 
+    #[test]
+    fn cpu_set_allowed() {
+        let online = CpuSet::online().unwrap();
+        let allowed = CpuSet::allowed().unwrap();
+        assert!(!allowed.is_empty());
+        assert!(allowed.is_subset(&online));
+    }
+
+    #[test]
+    fn placement_restricted_to_allowed_cpus() {
+        let allowed = CpuSet::allowed().unwrap();
+        let nr_allowed = allowed.len();
+
+        let mut gen =
+            CpuSetGenerator::pool(PoolPlacement::MaxSpread(nr_allowed, None), true).unwrap();
+        let cpus = (0..nr_allowed)
+            .map(|_| {
+                gen.next()
+                    .cpu_binding()
+                    .unwrap()
+                    .into_iter()
+                    .next()
+                    .unwrap()
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(cpus, allowed.iter().map(|l| l.cpu).collect::<HashSet<_>>());
+
+        // a CPU that can't be in the affinity mask of the process
+        let not_allowed = CpuSet::from_iter(vec![cpu_loc(0, 0, 0, nix::sched::CpuSet::count())]);
+        match CpuSetGenerator::pool(PoolPlacement::MaxPack(1, Some(not_allowed.clone())), true) {
+            Err(GlommioError::BuilderError(BuilderErrorKind::InsufficientCpus {
+                required: 1,
+                available: 0,
+            })) => {}
+            x => panic!("unexpected result {x:?}"),
+        }
+        CpuSetGenerator::pool(PoolPlacement::MaxPack(1, Some(not_allowed)), false).unwrap();
+    }
+
+    #[test]
+    fn cpu_set_one_per_core() {
+        let set = CpuSet::from_iter(vec![
+            cpu_loc(0, 0, 0, 4),
+            cpu_loc(0, 0, 0, 0),
+            cpu_loc(0, 0, 1, 1),
+            cpu_loc(0, 0, 1, 5),
+            cpu_loc(1, 1, 2, 6),
+        ]);
+        let cpus = set
+            .one_per_core()
+            .into_iter()
+            .map(|l| l.cpu)
+            .collect::<HashSet<_>>();
+        assert_eq!(cpus, HashSet::from_iter(vec![0, 1, 6]));
+    }
+
+    #[test]
+    fn isolated_and_housekeeping_cpus() {
+        let isolated = CpuSet::isolated().unwrap();
+        let housekeeping = CpuSet::housekeeping().unwrap();
+        assert!(isolated.is_disjoint(&housekeeping));
+        assert!(housekeeping.is_subset(&CpuSet::allowed().unwrap()));
+
+        let cores = isolated.clone().one_per_core().len();
+        match CpuSetGenerator::pool(PoolPlacement::isolated(cores + 1).unwrap(), true) {
+            Err(GlommioError::BuilderError(BuilderErrorKind::InsufficientCpus { .. })) => {}
+            x => panic!("unexpected result {x:?}"),
+        }
+        if isolated.is_empty() {
+            return;
+        }
+        let mut gen = CpuSetGenerator::pool(PoolPlacement::isolated(1).unwrap(), false).unwrap();
+        let cpu = gen
+            .next()
+            .cpu_binding()
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap();
+        assert!(isolated.iter().any(|l| l.cpu == cpu));
+    }
+
+    #[test]
+    fn isolated_placement_blocking_pool() {
+        assert_eq!(
+            PoolPlacement::MaxSpread(4, None).for_blocking_pool(),
+            PoolPlacement::MaxSpread(1, None)
+        );
+        if CpuSet::isolated().unwrap().is_empty() {
+            assert_eq!(
+                PoolPlacement::isolated(4).unwrap().for_blocking_pool(),
+                PoolPlacement::MaxSpread(1, Some(CpuSet::isolated().unwrap()))
+            );
+            return;
+        }
+        let housekeeping = PoolPlacement::housekeeping(1).unwrap();
+        assert_eq!(
+            PoolPlacement::isolated(4).unwrap().for_blocking_pool(),
+            housekeeping
+        );
+        assert_eq!(
+            PoolPlacement::from(Placement::isolated().unwrap()).for_blocking_pool(),
+            housekeeping
+        );
+    }
+
     #[test]
     fn placement_unbound_clone() {
         assert_eq!(PoolPlacement::Unbound(5).clone(), PoolPlacement::Unbound(5));
@@ -1104,7 +1487,7 @@ mod test {
         ]);
 
         let p = PoolPlacement::Custom(vec![set1, set2]);
-        let mut gen = CpuSetGenerator::pool(p).unwrap();
+        let mut gen = CpuSetGenerator::pool(p, false).unwrap();
         let mut bindings = vec![];
         for _ in 0..2 {
             let v = gen
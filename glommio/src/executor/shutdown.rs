@@ -0,0 +1,269 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2020 Datadog, Inc.
+//
+//! Coordinated shutdown of the executors of a pool.
+
+use crate::{executor::multitask::LiveTasks, GlommioError};
+use std::{
+    fmt,
+    future::Future,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    task::{Poll, Waker},
+    time::{Duration, Instant},
+};
+
+type Result<T> = crate::Result<T, ()>;
+
+/// A pool-wide signal asking the executors of a pool to shut down.
+///
+/// Every executor created by [`LocalExecutorPoolBuilder::on_all_shards`] can
+/// obtain the token of its pool with [`ExecutorProxy::shutdown_token`], and
+/// the token is signaled by [`PoolThreadHandles::shutdown`]. The future
+/// running on each shard is expected to watch the token and return once it is
+/// signaled.
+///
+/// The token of a shard is also signaled when that shard alone is retired with
+/// [`PoolThreadHandles::retire`].
+///
+/// [`LocalExecutorPoolBuilder::on_all_shards`]: crate::LocalExecutorPoolBuilder::on_all_shards
+/// [`ExecutorProxy::shutdown_token`]: crate::ExecutorProxy::shutdown_token
+/// [`PoolThreadHandles::shutdown`]: crate::PoolThreadHandles::shutdown
+/// [`PoolThreadHandles::retire`]: crate::PoolThreadHandles::retire
+#[derive(Clone)]
+pub struct ShutdownToken {
+    inner: Arc<ShutdownState>,
+    // the shard the token was handed to, if any
+    shard: Option<usize>,
+}
+
+#[derive(Default)]
+struct ShutdownState {
+    requested: AtomicBool,
+    // set once the deadline of the shutdown expires
+    forced: AtomicBool,
+    waiters: Mutex<Vec<Waker>>,
+    timeout: Mutex<Duration>,
+    // the ids of the shards that exited, and of the ones that had to be
+    // canceled
+    exited: Mutex<Vec<usize>>,
+    exited_cond: Condvar,
+    missed_deadline: Mutex<Vec<usize>>,
+    // the ids of the shards asked to shut down on their own
+    retired: Mutex<Vec<usize>>,
+}
+
+impl ShutdownToken {
+    pub(crate) fn new() -> Self {
+        Self {
+            inner: Arc::new(ShutdownState::default()),
+            shard: None,
+        }
+    }
+
+    /// Returns the token handed to the shard `id`, which is also signaled
+    /// when that shard is retired.
+    pub(crate) fn for_shard(&self, id: usize) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            shard: Some(id),
+        }
+    }
+
+    /// Returns whether a shutdown was requested.
+    pub fn is_shutdown(&self) -> bool {
+        self.inner.requested.load(Ordering::Acquire) || self.is_retired()
+    }
+
+    fn is_retired(&self) -> bool {
+        self.shard
+            .map_or(false, |id| self.inner.retired.lock().unwrap().contains(&id))
+    }
+
+    /// Waits until a shutdown is requested.
+    pub async fn wait(&self) {
+        self.wait_for(|| self.is_shutdown()).await
+    }
+
+    /// Waits until the deadline of the shutdown expires.
+    pub(crate) async fn forced(&self) {
+        self.wait_for(|| self.inner.forced.load(Ordering::Acquire))
+            .await
+    }
+
+    fn wait_for<'a>(&'a self, done: impl Fn() -> bool + 'a) -> impl Future<Output = ()> + 'a {
+        futures_lite::future::poll_fn(move |cx| {
+            if done() {
+                return Poll::Ready(());
+            }
+            let mut waiters = self.inner.waiters.lock().unwrap();
+            // the flags are set before the waiters are woken up, so check
+            // again now that nobody else can touch them
+            if done() {
+                return Poll::Ready(());
+            }
+            if !waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                waiters.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+    }
+
+    fn signal(&self, flag: &AtomicBool) {
+        flag.store(true, Ordering::Release);
+        self.wake_waiters();
+    }
+
+    fn wake_waiters(&self) {
+        let waiters = std::mem::take(&mut *self.inner.waiters.lock().unwrap());
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    /// Signals the shutdown and waits up to `timeout` for `nr_shards` shards
+    /// to exit. Returns whether they all did.
+    pub(crate) fn shutdown(&self, nr_shards: usize, timeout: Duration) -> bool {
+        *self.inner.timeout.lock().unwrap() = timeout;
+        self.signal(&self.inner.requested);
+
+        let deadline = Instant::now() + timeout;
+        let mut exited = self.inner.exited.lock().unwrap();
+        while exited.len() < nr_shards {
+            let now = Instant::now();
+            if now >= deadline {
+                drop(exited);
+                self.signal(&self.inner.forced);
+                return false;
+            }
+            exited = self
+                .inner
+                .exited_cond
+                .wait_timeout(exited, deadline - now)
+                .unwrap()
+                .0;
+        }
+        true
+    }
+
+    /// Asks the shard `id` alone to shut down, the way the whole pool does
+    /// when a shutdown is requested, except for the deadline: the shard keeps
+    /// running until all its tasks complete.
+    pub(crate) fn retire(&self, id: usize) {
+        self.inner.retired.lock().unwrap().push(id);
+        self.wake_waiters();
+    }
+
+    /// Returns whether the shard `id` exited.
+    pub(crate) fn has_exited(&self, id: usize) -> bool {
+        self.inner.exited.lock().unwrap().contains(&id)
+    }
+
+    /// Returns a guard that marks the current shard as exited when dropped.
+    pub(crate) fn shard_guard(&self) -> impl Drop {
+        struct Exited(ShutdownToken);
+
+        impl Drop for Exited {
+            fn drop(&mut self) {
+                let id = self.0.shard.expect("the token of a shard");
+                self.0.inner.exited.lock().unwrap().push(id);
+                self.0.inner.exited_cond.notify_all();
+            }
+        }
+
+        Exited(self.clone())
+    }
+
+    /// Runs the future of a shard, and once a shutdown is requested, keeps the
+    /// shard alive until all its tasks complete. Both stop once the deadline of
+    /// the shutdown expires, in which case the shard is reported as having
+    /// missed it.
+    pub(crate) async fn run_shard<T>(
+        &self,
+        id: usize,
+        live_tasks: Rc<LiveTasks>,
+        future: impl Future<Output = T>,
+    ) -> Result<T> {
+        let res = futures_lite::future::or(async { Some(future.await) }, async {
+            self.forced().await;
+            None
+        })
+        .await;
+        let res = match res {
+            Some(res) => res,
+            None => {
+                self.inner.missed_deadline.lock().unwrap().push(id);
+                return Err(GlommioError::TimedOut(*self.inner.timeout.lock().unwrap()));
+            }
+        };
+
+        if self.is_shutdown() {
+            let drained = futures_lite::future::or(
+                async {
+                    drain(&live_tasks).await;
+                    true
+                },
+                async {
+                    self.forced().await;
+                    false
+                },
+            )
+            .await;
+            if !drained {
+                self.inner.missed_deadline.lock().unwrap().push(id);
+            }
+        }
+        Ok(res)
+    }
+
+    pub(crate) fn missed_deadline(&self) -> Vec<usize> {
+        let mut missed = self.inner.missed_deadline.lock().unwrap().clone();
+        missed.sort_unstable();
+        missed
+    }
+}
+
+// Waits until the task calling this is the only one left
+async fn drain(live_tasks: &LiveTasks) {
+    futures_lite::future::poll_fn(|cx| {
+        if live_tasks.count() <= 1 {
+            Poll::Ready(())
+        } else {
+            live_tasks.register(cx.waker());
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+impl fmt::Debug for ShutdownToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownToken")
+            .field("requested", &self.is_shutdown())
+            .field("forced", &self.inner.forced.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// The outcome of [`PoolThreadHandles::shutdown`].
+///
+/// [`PoolThreadHandles::shutdown`]: crate::PoolThreadHandles::shutdown
+#[derive(Debug)]
+pub struct ShutdownReport<T> {
+    /// What each shard returned, in the same order as
+    /// [`PoolThreadHandles::handles`]. Shards whose future was canceled return
+    /// [`GlommioError::TimedOut`].
+    ///
+    /// [`PoolThreadHandles::handles`]: crate::PoolThreadHandles::handles
+    pub results: Vec<Result<T>>,
+
+    /// The ids of the executors that didn't complete all their tasks before
+    /// the deadline, and had them canceled.
+    pub missed_deadline: Vec<usize>,
+}
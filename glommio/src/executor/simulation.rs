@@ -0,0 +1,110 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2020 Datadog, Inc.
+//
+//! Seeded scheduling of an executor.
+//!
+//! In simulation, the choices the scheduler makes that would otherwise depend
+//! on real time are taken from a pseudo-random sequence derived from a seed:
+//! which task queue runs next, which of its runnable tasks is polled next, and
+//! when the running task queue is preempted. Runnable tasks and task queues
+//! are sorted before picking one, so the order in which they were woken up
+//! doesn't matter.
+//!
+//! I/O completions are held back by the reactor rather than waking tasks up
+//! as they happen. Once the executor runs out of tasks, it waits for the I/O
+//! that is bound to complete to do so, and releases some of the completions,
+//! picked with the seed. I/O over the network is only released once it
+//! completes, which depends on the peer.
+
+use crate::{executor::TaskQueue, reactor::Reactor};
+use std::{
+    cell::{Cell, RefCell},
+    collections::BinaryHeap,
+    rc::Rc,
+};
+
+/// On average, how many times the scheduler checks whether to preempt the
+/// running task queue before it does.
+const PREEMPT_ODDS: u64 = 8;
+
+#[derive(Debug)]
+pub(crate) struct Simulation {
+    state: Cell<u64>,
+}
+
+impl Simulation {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self {
+            state: Cell::new(seed),
+        }
+    }
+
+    // SplitMix64. It is implemented here rather than taken from a crate so
+    // that the sequence of a seed never changes, or seeds recorded with an
+    // older version would interleave tasks differently.
+    fn next(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.state.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`
+    pub(crate) fn below(&self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub(crate) fn should_preempt(&self) -> bool {
+        self.next() % PREEMPT_ODDS == 0
+    }
+
+    /// Releases some of the I/O completions `reactor` held back. Returns how
+    /// many.
+    pub(super) fn release_io(&self, reactor: &Reactor) -> usize {
+        let held = reactor.nr_held_completions();
+        if held == 0 {
+            return 0;
+        }
+        reactor.release_completions(1 + self.below(held), |n| self.below(n))
+    }
+
+    /// Takes one of the active task queues out of `active`.
+    ///
+    /// The task queues are otherwise ordered by vruntime or deadline, both of
+    /// which depend on real time.
+    pub(super) fn pick_task_queue(
+        &self,
+        active: &mut BinaryHeap<Rc<RefCell<TaskQueue>>>,
+    ) -> Option<Rc<RefCell<TaskQueue>>> {
+        if active.is_empty() {
+            return None;
+        }
+        let mut queues = std::mem::take(active).into_vec();
+        queues.sort_by_key(|tq| tq.borrow().stats.index.index);
+        let queue = queues.swap_remove(self.below(queues.len()));
+        *active = queues.into();
+        Some(queue)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let a = Simulation::new(42);
+        let b = Simulation::new(42);
+        let c = Simulation::new(43);
+        let a: Vec<_> = (0..16).map(|_| a.next()).collect();
+        let b: Vec<_> = (0..16).map(|_| b.next()).collect();
+        let c: Vec<_> = (0..16).map(|_| c.next()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+}
@@ -5,32 +5,78 @@
 //
 
 use crate::executor::TaskQueueHandle;
+use ahash::AHashMap;
 use nix::sys;
+use sketches_ddsketch::DDSketch;
 use std::{
     fmt,
+    panic::Location,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 
+/// A task queue that went over budget, as passed to
+/// [`StallDetectionHandler::stall`]
 pub struct StallDetection<'a> {
     executor: usize,
     queue_handle: TaskQueueHandle,
     queue_name: &'a str,
+    task: Option<StalledTask>,
     trace: backtrace::Backtrace,
     budget: Duration,
     overage: Duration,
 }
 
+impl<'a> StallDetection<'a> {
+    /// Returns the id of the executor that stalled
+    pub fn executor(&self) -> usize {
+        self.executor
+    }
+
+    /// Returns the handle of the task queue that went over budget
+    pub fn queue_handle(&self) -> TaskQueueHandle {
+        self.queue_handle
+    }
+
+    /// Returns the name of the task queue that went over budget
+    pub fn queue_name(&self) -> &'a str {
+        self.queue_name
+    }
+
+    /// Returns the task that was being polled when the task queue went over
+    /// budget, if it's known
+    pub fn task(&self) -> Option<&StalledTask> {
+        self.task.as_ref()
+    }
+
+    /// Returns the backtrace of the executor thread when the task queue went
+    /// over budget
+    pub fn trace(&self) -> &backtrace::Backtrace {
+        &self.trace
+    }
+
+    /// Returns how long the task queue was expected to run for at most
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Returns how long the task queue ran past its budget
+    pub fn overage(&self) -> Duration {
+        self.overage
+    }
+}
+
 impl fmt::Debug for StallDetection<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("StallDetection")
             .field("executor", &self.executor)
             .field("queue_handle", &self.queue_handle)
             .field("queue_name", &self.queue_name)
+            .field("task", &self.task)
             .field("trace", &self.trace)
             .field("budget", &self.budget)
             .field("overage", &self.overage)
@@ -43,8 +89,69 @@ impl fmt::Display for StallDetection<'_> {
         write!(
             f,
             "[stall-detector -- executor {}] task queue {} went over-budget: {:#?} (budget: \
-             {:#?}). Backtrace: {:#?}",
-            self.executor, self.queue_name, self.overage, self.budget, self.trace,
+             {:#?}).",
+            self.executor, self.queue_name, self.overage, self.budget,
+        )?;
+        if let Some(task) = &self.task {
+            write!(f, " Stalled task: {task}.")?;
+        }
+        write!(f, " Backtrace: {:#?}", self.trace)
+    }
+}
+
+/// The task that was being polled when a task queue went over budget.
+///
+/// The backtrace of a [`StallDetection`] is that of the executor thread, which
+/// may only show the executor loop if the task stalled in code that is not on
+/// the stack anymore; this tells which task it was.
+#[derive(Debug, Clone)]
+pub struct StalledTask {
+    pub(crate) id: u64,
+    pub(crate) label: Option<&'static str>,
+    pub(crate) spawn_location: &'static Location<'static>,
+    pub(crate) poll_start: Instant,
+    pub(crate) poll_duration: Duration,
+}
+
+impl StalledTask {
+    /// Returns the sequence number of the task among the ones spawned by its
+    /// executor
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the label of the task, if one was set with
+    /// [`Task::set_label`](crate::Task::set_label)
+    pub fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    /// Returns where the task was spawned from
+    pub fn spawn_location(&self) -> &'static Location<'static> {
+        self.spawn_location
+    }
+
+    /// Returns when the poll of the task that stalled started
+    pub fn poll_start(&self) -> Instant {
+        self.poll_start
+    }
+
+    /// Returns how long the poll of the task that stalled lasted
+    pub fn poll_duration(&self) -> Duration {
+        self.poll_duration
+    }
+}
+
+impl fmt::Display for StalledTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task {}", self.id)?;
+        if let Some(label) = self.label {
+            write!(f, " ({label})")?;
+        }
+        write!(
+            f,
+            " spawned at {}, polled for {:#?}",
+            self.spawn_location, self.poll_duration
         )
     }
 }
@@ -80,6 +187,15 @@ pub trait StallDetectionHandler: std::fmt::Debug + Send + Sync {
     fn stall(&self, detection: StallDetection<'_>) {
         log::warn!("{detection}");
     }
+
+    /// Statistics about the stalls of each task queue, as returned by
+    /// [`ExecutorProxy::stall_stats`]. The default implementation doesn't
+    /// keep any.
+    ///
+    /// [`ExecutorProxy::stall_stats`]: crate::ExecutorProxy::stall_stats
+    fn stall_stats(&self) -> Vec<TaskQueueStallStats> {
+        Vec::new()
+    }
 }
 
 /// Default settings for signal number, threshold and stall handler.
@@ -92,6 +208,121 @@ pub struct DefaultStallDetectionHandler {}
 
 impl StallDetectionHandler for DefaultStallDetectionHandler {}
 
+/// A stall handler that aggregates the stalls of each task queue into
+/// histograms, which can be queried with [`ExecutorProxy::stall_stats`]. It
+/// uses the default signal number and threshold, and doesn't log stalls.
+///
+/// # Examples
+///
+/// ```
+/// use glommio::{LocalExecutorBuilder, StallHistogramHandler};
+///
+/// let ex = LocalExecutorBuilder::default()
+///     .detect_stalls(Some(Box::new(StallHistogramHandler::default())))
+///     .spawn(|| async move {
+///         for stats in glommio::executor().stall_stats() {
+///             println!("{}: {} stalls", stats.name(), stats.stalls());
+///         }
+///     })
+///     .unwrap();
+///
+/// ex.join().unwrap();
+/// ```
+///
+/// [`ExecutorProxy::stall_stats`]: crate::ExecutorProxy::stall_stats
+#[derive(Debug, Default)]
+pub struct StallHistogramHandler {
+    // only ever locked by the executor thread
+    task_queues: Mutex<AHashMap<TaskQueueHandle, TaskQueueStallStats>>,
+}
+
+impl StallDetectionHandler for StallHistogramHandler {
+    fn stall(&self, detection: StallDetection<'_>) {
+        let mut task_queues = self.task_queues.lock().unwrap();
+        let stats = task_queues
+            .entry(detection.queue_handle)
+            .or_insert_with(|| TaskQueueStallStats::new(detection.queue_handle));
+        stats.name = detection.queue_name.to_string();
+        stats.stalls += 1;
+        stats.overage_us.add(detection.overage.as_micros() as f64);
+        if let Some(task) = &detection.task {
+            *stats
+                .spawn_locations
+                .entry(task.spawn_location)
+                .or_default() += 1;
+        }
+    }
+
+    fn stall_stats(&self) -> Vec<TaskQueueStallStats> {
+        let mut stats: Vec<_> = self.task_queues.lock().unwrap().values().cloned().collect();
+        stats.sort_by_key(|tq| tq.index.index);
+        stats
+    }
+}
+
+/// Statistics about the stalls of a task queue, as aggregated by
+/// [`StallHistogramHandler`] since the executor started.
+#[derive(Clone)]
+pub struct TaskQueueStallStats {
+    index: TaskQueueHandle,
+    name: String,
+    stalls: u64,
+    overage_us: DDSketch,
+    spawn_locations: AHashMap<&'static Location<'static>, u64>,
+}
+
+impl fmt::Debug for TaskQueueStallStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskQueueStallStats")
+            .field("index", &self.index)
+            .field("name", &self.name)
+            .field("stalls", &self.stalls)
+            .field("spawn_locations", &self.spawn_locations)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TaskQueueStallStats {
+    /// Creates empty statistics for the task queue `index`, for handlers
+    /// implementing [`StallDetectionHandler::stall_stats`]
+    pub fn new(index: TaskQueueHandle) -> Self {
+        Self {
+            index,
+            name: String::new(),
+            stalls: 0,
+            overage_us: DDSketch::new(sketches_ddsketch::Config::new(0.01, 2048, 1.0e-9)),
+            spawn_locations: AHashMap::new(),
+        }
+    }
+
+    /// Returns the handle of the task queue
+    pub fn index(&self) -> TaskQueueHandle {
+        self.index
+    }
+
+    /// Returns the name of the task queue
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of times the task queue went over budget
+    pub fn stalls(&self) -> u64 {
+        self.stalls
+    }
+
+    /// Returns a distribution of how long the task queue ran past its budget
+    /// when it stalled
+    pub fn overage_us(&self) -> &DDSketch {
+        &self.overage_us
+    }
+
+    /// Returns how many of the stalls were caused by tasks spawned from each
+    /// location, for the stalls where the task is known
+    pub fn spawn_locations(&self) -> &AHashMap<&'static Location<'static>, u64> {
+        &self.spawn_locations
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct StallDetector {
     timer: Arc<sys::timerfd::TimerFd>,
@@ -206,6 +437,10 @@ impl StallDetector {
     pub(crate) fn disarm(&self) -> nix::Result<()> {
         self.timer.unset()
     }
+
+    pub(crate) fn stall_stats(&self) -> Vec<TaskQueueStallStats> {
+        self.stall_handler.stall_stats()
+    }
 }
 
 impl Drop for StallDetector {
@@ -233,6 +468,7 @@ pub(crate) struct StallDetectorGuard<'detector> {
     queue_name: String,
     start: Instant,
     threshold: Duration,
+    task: Option<StalledTask>,
 }
 
 impl<'detector> StallDetectorGuard<'detector> {
@@ -252,8 +488,18 @@ impl<'detector> StallDetectorGuard<'detector> {
             queue_name,
             start,
             threshold,
+            task: None,
         })
     }
+
+    /// Records that `task` was just polled. It's the one that stalled if the
+    /// signal arrived while it was being polled.
+    pub(crate) fn task_polled(&mut self, mut task: StalledTask) {
+        if self.task.is_none() && !self.detector.rx.is_empty() {
+            task.poll_duration = task.poll_start.elapsed();
+            self.task = Some(task);
+        }
+    }
 }
 
 impl<'detector> Drop for StallDetectorGuard<'detector> {
@@ -276,6 +522,7 @@ impl<'detector> Drop for StallDetectorGuard<'detector> {
             executor: self.detector.id,
             queue_name: &self.queue_name,
             queue_handle: self.queue_handle,
+            task: self.task.take(),
             trace: strace,
             budget: self.threshold,
             overage: elapsed.saturating_sub(self.threshold),
@@ -287,11 +534,11 @@ impl<'detector> Drop for StallDetectorGuard<'detector> {
 mod test {
     use crate::{
         executor::{
-            stall::{StallDetection, StallDetectionHandler},
+            stall::{StallDetection, StallDetectionHandler, StallHistogramHandler, StalledTask},
             TaskQueueHandle,
         },
         timer::sleep,
-        LocalExecutorBuilder,
+        Latency, LocalExecutorBuilder, Shares,
     };
     use std::{
         sync::{Arc, RwLock},
@@ -302,6 +549,7 @@ mod test {
     #[derive(Debug)]
     pub struct TestStallDetection {
         executor: usize,
+        task: Option<StalledTask>,
     }
 
     #[derive(Debug)]
@@ -345,7 +593,8 @@ mod test {
         fn stall(&self, detection: StallDetection<'_>) {
             let mut inner = self.inner.write().unwrap();
             inner.detections.push(TestStallDetection {
-                executor: detection.executor,
+                executor: detection.executor(),
+                task: detection.task().cloned(),
             });
         }
     }
@@ -480,4 +729,86 @@ mod test {
             handle.join().unwrap();
         }
     }
+
+    #[test]
+    fn stall_detector_reports_task() {
+        let stall_handler = TestHandler::new(nix::libc::SIGUSR1 as u8);
+        LocalExecutorBuilder::default()
+            .detect_stalls(Some(Box::new(stall_handler.clone())))
+            .preempt_timer(Duration::from_millis(50))
+            .make()
+            .unwrap()
+            .run(async {
+                let tq = crate::executor().create_task_queue(
+                    Shares::default(),
+                    Latency::NotImportant,
+                    "stalling",
+                );
+                let line = line!() + 1;
+                let task = crate::spawn_local_into(
+                    async {
+                        // will trigger the stall detector because we go over budget
+                        thread::sleep(Duration::from_millis(100));
+                    },
+                    tq,
+                )
+                .unwrap();
+                task.set_label("stalling");
+                task.await;
+
+                let detection = stall_handler.inner.write().unwrap().detections.pop();
+                let task = detection.unwrap().task.unwrap();
+                assert_eq!(task.label(), Some("stalling"));
+                assert_eq!(task.spawn_location().file(), file!());
+                assert_eq!(task.spawn_location().line(), line);
+                assert!(task.poll_duration() >= Duration::from_millis(100));
+                assert!(task.poll_start().elapsed() >= task.poll_duration());
+                let polled_for = format!("polled for {:#?}", task.poll_duration());
+                thread::sleep(Duration::from_millis(10));
+                assert!(task.to_string().ends_with(&polled_for));
+            });
+    }
+
+    #[test]
+    fn stall_histogram_handler() {
+        LocalExecutorBuilder::default()
+            .detect_stalls(Some(Box::new(StallHistogramHandler::default())))
+            .preempt_timer(Duration::from_millis(50))
+            .make()
+            .unwrap()
+            .run(async {
+                let tq = crate::executor().create_task_queue(
+                    Shares::default(),
+                    Latency::NotImportant,
+                    "stalling",
+                );
+                assert!(crate::executor().stall_stats().is_empty());
+
+                let line = line!() + 2;
+                for _ in 0..2 {
+                    crate::spawn_local_into(
+                        async {
+                            // goes over the default threshold of twice the preempt
+                            // timer plus 10ms
+                            thread::sleep(Duration::from_millis(200));
+                        },
+                        tq,
+                    )
+                    .unwrap()
+                    .await;
+                }
+
+                let stats = crate::executor().stall_stats();
+                assert_eq!(stats.len(), 1);
+                let stats = &stats[0];
+                assert_eq!(stats.index(), tq);
+                assert_eq!(stats.name(), "stalling");
+                assert_eq!(stats.stalls(), 2);
+                assert_eq!(stats.overage_us().count(), 2);
+                assert_eq!(stats.spawn_locations().len(), 1);
+                let (location, stalls) = stats.spawn_locations().iter().next().unwrap();
+                assert_eq!(location.line(), line);
+                assert_eq!(*stalls, 2);
+            });
+    }
 }
@@ -0,0 +1,181 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2020 Datadog, Inc.
+//
+//! A queue of tasks shared by all the executors of a pool, from which any of
+//! them can pick up work.
+//!
+//! Tasks are pushed to the queue by
+//! [`ExecutorProxy::spawn_stealable`](crate::ExecutorProxy::spawn_stealable)
+//! and taken by executors as part of processing their external events, a
+//! bounded batch at a time. Executors that are busy running their own tasks process external
+//! events less often, so the bulk of the stealable work ends up on the idle
+//! ones.
+
+use crate::{sys::SleepNotifier, Latency, TaskQueueHandle};
+use crossbeam::deque::{Injector, Steal};
+use std::{
+    fmt,
+    sync::{atomic::AtomicUsize, Arc, RwLock, Weak},
+};
+
+/// Spawns a stealable task into the given task queue of the current executor.
+pub(crate) type StealableSpawn = Box<dyn FnOnce(TaskQueueHandle) + Send>;
+
+/// What a task queue looks like to the scheduler, so a task spawned into it
+/// can be run in an equivalent queue by another executor.
+#[derive(Clone, Debug)]
+pub(crate) struct QueueClass {
+    pub(crate) name: String,
+    pub(crate) latency: Latency,
+    /// `None` for dynamic shares, which can't be compared.
+    pub(crate) shares: Option<usize>,
+}
+
+impl QueueClass {
+    pub(crate) fn matches(&self, other: &QueueClass) -> bool {
+        let same_latency = match (self.latency, other.latency) {
+            (Latency::Matters(a), Latency::Matters(b)) => a == b,
+            (Latency::NotImportant, Latency::NotImportant) => true,
+            _ => false,
+        };
+        same_latency && self.shares == other.shares && self.name == other.name
+    }
+}
+
+/// A `Send` task that has not been spawned on any executor yet.
+pub(crate) struct StealableTask {
+    /// The id of the executor that spawned the task.
+    pub(crate) origin: usize,
+    /// The task queue the task was spawned into, on the origin executor.
+    pub(crate) handle: TaskQueueHandle,
+    /// The class of that task queue. Other executors only run the task if
+    /// they have a task queue of the same class.
+    pub(crate) class: QueueClass,
+    /// Shared with the task's handle, and set to the id of the executor that
+    /// ends up running the task.
+    pub(crate) executor_id: Arc<AtomicUsize>,
+    pub(crate) spawn: StealableSpawn,
+    /// The ids of the executors that took the task out of the queue but could
+    /// not run it.
+    pub(crate) rejected_by: Vec<usize>,
+}
+
+impl fmt::Debug for StealableTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StealableTask")
+            .field("origin", &self.origin)
+            .field("handle", &self.handle)
+            .field("class", &self.class)
+            .field("rejected_by", &self.rejected_by)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct StealableQueue {
+    tasks: Injector<StealableTask>,
+    // the executors sharing this queue, used to wake one of them up when a
+    // task is pushed
+    members: RwLock<Vec<Weak<SleepNotifier>>>,
+}
+
+impl StealableQueue {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub(crate) fn register(&self, notifier: &Arc<SleepNotifier>) {
+        self.members.write().unwrap().push(Arc::downgrade(notifier));
+    }
+
+    /// Returns the ids of the executors sharing this queue that are still
+    /// alive.
+    pub(crate) fn member_ids(&self) -> Vec<usize> {
+        self.members
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|notifier| notifier.id())
+            .collect()
+    }
+
+    /// Pushes a task into the queue, and wakes up one of the sleeping
+    /// executors (if any) so it can pick it up.
+    pub(crate) fn push(&self, task: StealableTask) {
+        self.tasks.push(task);
+        self.wake_one(|_| true);
+    }
+
+    /// Wakes up the first sleeping executor whose id `eligible` accepts.
+    fn wake_one(&self, eligible: impl Fn(usize) -> bool) {
+        for member in self.members.read().unwrap().iter() {
+            if let Some(notifier) = member.upgrade() {
+                if eligible(notifier.id()) && notifier.notify(false) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Whether tasks are waiting in the queue for an executor to take them.
+    pub(crate) fn has_tasks(&self) -> bool {
+        !self.tasks.is_empty()
+    }
+
+    /// Takes up to `max` tasks out of the queue on behalf of the executor
+    /// `thief`, keeping the ones `place` finds a task queue for, along with
+    /// that queue.
+    ///
+    /// A rejected task doesn't stop the scan: it is put back at the end of the
+    /// queue, and a sleeping executor that hasn't rejected it yet is woken up
+    /// so the task doesn't wait on one that happens to be busy.
+    pub(crate) fn steal(
+        &self,
+        thief: usize,
+        max: usize,
+        mut place: impl FnMut(&StealableTask) -> Option<TaskQueueHandle>,
+    ) -> Vec<(StealableTask, TaskQueueHandle)> {
+        let mut stolen = Vec::new();
+        let mut rejected = Vec::new();
+        // only look at the tasks queued when we started, so the rejected ones
+        // (and the ones pushed concurrently) aren't scanned over and over
+        for _ in 0..self.tasks.len() {
+            if stolen.len() == max {
+                break;
+            }
+            let mut task = match self.steal_one() {
+                Some(task) => task,
+                None => break,
+            };
+            if let Some(handle) = place(&task) {
+                stolen.push((task, handle));
+            } else {
+                if !task.rejected_by.contains(&thief) {
+                    task.rejected_by.push(thief);
+                }
+                rejected.push(task);
+            }
+        }
+
+        for task in rejected {
+            let rejected_by = task.rejected_by.clone();
+            self.tasks.push(task);
+            self.wake_one(|id| !rejected_by.contains(&id));
+        }
+        stolen
+    }
+
+    fn steal_one(&self) -> Option<StealableTask> {
+        loop {
+            match self.tasks.steal() {
+                Steal::Success(task) => return Some(task),
+                Steal::Empty => return None,
+                Steal::Retry => continue,
+            }
+        }
+    }
+}
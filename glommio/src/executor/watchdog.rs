@@ -0,0 +1,435 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/).
+// Copyright 2020 Datadog, Inc.
+//
+//! Liveness of the executors of a pool, watched from a separate thread.
+//!
+//! Every executor of a pool with a watchdog publishes a [`Heartbeat`]: a
+//! counter bumped at the end of each scheduler pass, along with the task
+//! queue it is running and whether it is parked. The watchdog thread samples
+//! the heartbeats and calls the handler of the pool when an executor stops
+//! making progress: it is either stuck in a task (busy, no heartbeat for
+//! longer than the threshold) or asleep while work sent from other threads
+//! waits for it (a lost wake up). Executors are only watched once they start
+//! running.
+
+use crate::{
+    executor::{stealing::StealableQueue, TaskQueueHandle},
+    sys::SleepNotifier,
+};
+use ahash::AHashMap;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// The liveness of an executor of a pool, as seen by the watchdog of the
+/// pool.
+///
+/// See [`LocalExecutorPoolBuilder::watchdog`] and
+/// [`PoolThreadHandles::liveness`].
+///
+/// [`LocalExecutorPoolBuilder::watchdog`]: crate::LocalExecutorPoolBuilder::watchdog
+/// [`PoolThreadHandles::liveness`]: crate::PoolThreadHandles::liveness
+#[derive(Debug, Clone)]
+pub struct ExecutorLiveness {
+    executor_id: usize,
+    heartbeats: u64,
+    last_heartbeat: Instant,
+    task_queue: Option<TaskQueueHandle>,
+    parked: bool,
+    responsive: bool,
+}
+
+impl ExecutorLiveness {
+    /// The id of the executor
+    pub fn executor_id(&self) -> usize {
+        self.executor_id
+    }
+
+    /// The number of scheduler passes the executor completed
+    pub fn heartbeats(&self) -> u64 {
+        self.heartbeats
+    }
+
+    /// When the executor last completed a scheduler pass or woke up (or
+    /// started, if it did neither yet)
+    pub fn last_heartbeat(&self) -> Instant {
+        self.last_heartbeat
+    }
+
+    /// The task queue the executor is running tasks of, if any
+    pub fn task_queue(&self) -> Option<TaskQueueHandle> {
+        self.task_queue
+    }
+
+    /// Whether the executor is parked, waiting for events
+    pub fn is_parked(&self) -> bool {
+        self.parked
+    }
+
+    /// Whether the executor is making progress, or is parked with nothing to
+    /// do
+    pub fn is_responsive(&self) -> bool {
+        self.responsive
+    }
+}
+
+/// The liveness state an executor shares with the watchdog of its pool.
+#[derive(Debug)]
+pub(crate) struct Heartbeat {
+    executor_id: usize,
+    epoch: Instant,
+    beats: AtomicU64,
+    // nanoseconds since `epoch`
+    last_beat: AtomicU64,
+    started: AtomicBool,
+    parked: AtomicBool,
+    // `usize::MAX` if the executor isn't running any task queue
+    task_queue: AtomicUsize,
+    notifier: Arc<SleepNotifier>,
+}
+
+impl Heartbeat {
+    fn new(epoch: Instant, notifier: Arc<SleepNotifier>) -> Self {
+        Self {
+            executor_id: notifier.id(),
+            epoch,
+            beats: AtomicU64::new(0),
+            last_beat: AtomicU64::new(epoch.elapsed().as_nanos() as u64),
+            started: AtomicBool::new(false),
+            parked: AtomicBool::new(false),
+            task_queue: AtomicUsize::new(usize::MAX),
+            notifier,
+        }
+    }
+
+    fn touch(&self, now: Instant) {
+        let since_epoch = now.saturating_duration_since(self.epoch).as_nanos() as u64;
+        self.last_beat.store(since_epoch, Ordering::Relaxed);
+    }
+
+    /// Records that the executor started running at `now`: the time it took
+    /// to get there doesn't count against it.
+    pub(crate) fn start(&self, now: Instant) {
+        self.unpark(now);
+        self.started.store(true, Ordering::Release);
+    }
+
+    fn is_started(&self) -> bool {
+        self.started.load(Ordering::Acquire)
+    }
+
+    fn is_parked(&self) -> bool {
+        self.parked.load(Ordering::Acquire)
+    }
+
+    /// Records the completion of a scheduler pass, at `now`.
+    pub(crate) fn beat(&self, now: Instant) {
+        self.touch(now);
+        self.beats.fetch_add(1, Ordering::Release);
+    }
+
+    pub(crate) fn park(&self) {
+        self.parked.store(true, Ordering::Release);
+    }
+
+    /// Records that the executor woke up at `now`: the time it spent parked
+    /// doesn't count against it.
+    pub(crate) fn unpark(&self, now: Instant) {
+        self.touch(now);
+        self.parked.store(false, Ordering::Release);
+    }
+
+    pub(crate) fn set_task_queue(&self, handle: Option<TaskQueueHandle>) {
+        let index = handle.map_or(usize::MAX, |h| h.index);
+        self.task_queue.store(index, Ordering::Relaxed);
+    }
+
+    fn liveness(&self, responsive: bool) -> ExecutorLiveness {
+        let beats = self.beats.load(Ordering::Acquire);
+        let last_beat = Duration::from_nanos(self.last_beat.load(Ordering::Relaxed));
+        let task_queue = match self.task_queue.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            index => Some(TaskQueueHandle { index }),
+        };
+        ExecutorLiveness {
+            executor_id: self.executor_id,
+            heartbeats: beats,
+            last_heartbeat: self.epoch + last_beat,
+            task_queue,
+            parked: self.is_parked(),
+            responsive,
+        }
+    }
+}
+
+/// The configuration of the watchdog of a pool
+#[derive(Clone)]
+pub(crate) struct WatchdogConfig {
+    pub(crate) threshold: Duration,
+    pub(crate) handler: Arc<dyn Fn(&ExecutorLiveness) + Send + Sync>,
+}
+
+impl fmt::Debug for WatchdogConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchdogConfig")
+            .field("threshold", &self.threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+// What the watchdog remembers of an executor between two checks
+#[derive(Debug, Default)]
+struct WatchState {
+    beats: u64,
+    // since when the executor hasn't been making progress
+    stuck_since: Option<Instant>,
+    reported: bool,
+}
+
+impl WatchState {
+    fn is_responsive(&self, now: Instant, threshold: Duration) -> bool {
+        self.stuck_since.map_or(true, |since| {
+            now.saturating_duration_since(since) <= threshold
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    config: WatchdogConfig,
+    epoch: Instant,
+    stealable_tasks: Arc<StealableQueue>,
+    // the executors unregister by dropping their heartbeat
+    heartbeats: Mutex<Vec<Weak<Heartbeat>>>,
+    states: Mutex<AHashMap<usize, WatchState>>,
+    stop: Mutex<bool>,
+    stop_cond: Condvar,
+}
+
+impl Shared {
+    fn live_heartbeats(&self) -> Vec<Arc<Heartbeat>> {
+        let mut heartbeats = self.heartbeats.lock().unwrap();
+        heartbeats.retain(|h| h.strong_count() > 0);
+        heartbeats.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    fn check(&self) {
+        let mut heartbeats = self.live_heartbeats();
+        heartbeats.retain(|h| h.is_started());
+        let mut states = self.states.lock().unwrap();
+        states.retain(|id, _| heartbeats.iter().any(|h| h.executor_id == *id));
+        // any executor awake picks up stealable tasks, they only wait for a
+        // wake up if all of them are asleep
+        let stealable_work =
+            self.stealable_tasks.has_tasks() && heartbeats.iter().all(|h| h.is_parked());
+
+        let now = Instant::now();
+        let mut unresponsive = Vec::new();
+        for heartbeat in &heartbeats {
+            let state = states.entry(heartbeat.executor_id).or_default();
+            let mut liveness = heartbeat.liveness(true);
+
+            if liveness.heartbeats != state.beats {
+                state.beats = liveness.heartbeats;
+                state.stuck_since = None;
+                state.reported = false;
+            }
+
+            if !liveness.parked {
+                // busy since its last heartbeat
+                state.stuck_since = Some(liveness.last_heartbeat);
+            } else if heartbeat.notifier.has_foreign_work() || stealable_work {
+                // asleep with work to do: count from when it was noticed
+                state.stuck_since.get_or_insert(now);
+            } else {
+                state.stuck_since = None;
+            }
+
+            liveness.responsive = state.is_responsive(now, self.config.threshold);
+            if !liveness.responsive && !state.reported {
+                state.reported = true;
+                unresponsive.push(liveness);
+            }
+        }
+        drop(states);
+
+        // the handler runs without holding any lock, it may query the liveness
+        // of the pool itself
+        for liveness in &unresponsive {
+            (self.config.handler)(liveness);
+        }
+    }
+}
+
+/// The thread watching the liveness of the executors of a pool. It stops when
+/// dropped.
+#[derive(Debug)]
+pub(crate) struct Watchdog {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub(crate) fn spawn(
+        config: WatchdogConfig,
+        stealable_tasks: Arc<StealableQueue>,
+    ) -> std::io::Result<Self> {
+        // samples often enough to notice an unresponsive executor shortly after
+        // the threshold elapses
+        let interval = (config.threshold / 4).max(Duration::from_millis(1));
+        let shared = Arc::new(Shared {
+            config,
+            epoch: Instant::now(),
+            stealable_tasks,
+            heartbeats: Mutex::new(Vec::new()),
+            states: Mutex::new(AHashMap::new()),
+            stop: Mutex::new(false),
+            stop_cond: Condvar::new(),
+        });
+
+        let thread = std::thread::Builder::new()
+            .name("glommio-watchdog".into())
+            .spawn({
+                let shared = shared.clone();
+                move || loop {
+                    let stop = shared.stop.lock().unwrap();
+                    let (stop, _) = shared
+                        .stop_cond
+                        .wait_timeout_while(stop, interval, |stop| !*stop)
+                        .unwrap();
+                    if *stop {
+                        return;
+                    }
+                    drop(stop);
+                    shared.check();
+                }
+            })?;
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Registers the executor that owns `notifier`. The executor is watched
+    /// from the time it starts until the returned heartbeat is dropped.
+    pub(crate) fn register(&self, notifier: Arc<SleepNotifier>) -> Arc<Heartbeat> {
+        let heartbeat = Arc::new(Heartbeat::new(self.shared.epoch, notifier));
+        self.shared
+            .heartbeats
+            .lock()
+            .unwrap()
+            .push(Arc::downgrade(&heartbeat));
+        heartbeat
+    }
+
+    /// The liveness of the executors watched, sorted by executor id
+    pub(crate) fn liveness(&self) -> Vec<ExecutorLiveness> {
+        let mut liveness: Vec<_> = self
+            .shared
+            .live_heartbeats()
+            .iter()
+            .map(|heartbeat| {
+                // as of the last check of the watchdog
+                let responsive = self
+                    .shared
+                    .states
+                    .lock()
+                    .unwrap()
+                    .get(&heartbeat.executor_id)
+                    .map_or(true, |state| {
+                        state.is_responsive(Instant::now(), self.shared.config.threshold)
+                    });
+                heartbeat.liveness(responsive)
+            })
+            .collect();
+        liveness.sort_by_key(|l| l.executor_id);
+        liveness
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        *self.shared.stop.lock().unwrap() = true;
+        self.shared.stop_cond.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        enclose,
+        executor::stealing::{QueueClass, StealableTask},
+        sys, Latency,
+    };
+
+    fn watchdog(reports: &Arc<AtomicUsize>, stealable_tasks: Arc<StealableQueue>) -> Watchdog {
+        let config = WatchdogConfig {
+            threshold: Duration::from_millis(10),
+            handler: Arc::new(enclose! { (reports) move |_: &ExecutorLiveness| {
+                reports.fetch_add(1, Ordering::Relaxed);
+            }}),
+        };
+        Watchdog::spawn(config, stealable_tasks).unwrap()
+    }
+
+    #[test]
+    fn executors_are_watched_once_started() {
+        let reports = Arc::new(AtomicUsize::new(0));
+        let watchdog = watchdog(&reports, StealableQueue::new());
+        let heartbeat = watchdog.register(sys::new_sleep_notifier().unwrap());
+
+        // slow to start
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(reports.load(Ordering::Relaxed), 0);
+        assert!(watchdog.liveness()[0].is_responsive());
+
+        // busy without completing a scheduler pass
+        heartbeat.start(Instant::now());
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(reports.load(Ordering::Relaxed), 1);
+        assert!(!watchdog.liveness()[0].is_responsive());
+    }
+
+    #[test]
+    fn parked_executors_with_stealable_tasks_are_unresponsive() {
+        let reports = Arc::new(AtomicUsize::new(0));
+        let stealable_tasks = StealableQueue::new();
+        let watchdog = watchdog(&reports, stealable_tasks.clone());
+        let heartbeat = watchdog.register(sys::new_sleep_notifier().unwrap());
+        heartbeat.start(Instant::now());
+        heartbeat.park();
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(reports.load(Ordering::Relaxed), 0);
+
+        // nobody woke up to take it
+        stealable_tasks.push(StealableTask {
+            origin: 0,
+            handle: TaskQueueHandle::default(),
+            class: QueueClass {
+                name: "default".into(),
+                latency: Latency::NotImportant,
+                shares: Some(1000),
+            },
+            executor_id: Default::default(),
+            spawn: Box::new(|_| {}),
+            rejected_by: Vec::new(),
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(reports.load(Ordering::Relaxed), 1);
+    }
+}
@@ -5,6 +5,7 @@
 //
 
 use crate::{
+    executor::coop,
     io::{glommio_file::GlommioFile, read_result::ReadResult, OpenOptions},
     GlommioError,
 };
@@ -175,14 +176,16 @@ impl BufferedFile {
             size,
             self.file.scheduler.borrow().as_ref(),
         );
-        let read_size = source.collect_rw().await.map_err(|source| {
-            GlommioError::create_enhanced(
-                source,
-                "Reading",
-                self.file.path.borrow().as_ref(),
-                Some(self.as_raw_fd()),
-            )
-        })?;
+        let read_size = coop::budgeted(source.collect_rw())
+            .await
+            .map_err(|source| {
+                GlommioError::create_enhanced(
+                    source,
+                    "Reading",
+                    self.file.path.borrow().as_ref(),
+                    Some(self.as_raw_fd()),
+                )
+            })?;
         Ok(ReadResult::from_sliced_buffer(source, 0, read_size))
     }
 
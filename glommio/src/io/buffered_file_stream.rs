@@ -4,6 +4,7 @@
 // This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
 
 use crate::{
+    executor::coop,
     io::{BufferedFile, ScheduledSource},
     reactor::Reactor,
     sys::{IoBuffer, Source, Statx},
@@ -560,8 +561,10 @@ impl AsyncBufRead for StreamReader {
         mut self: Pin<&'a mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<io::Result<&'a [u8]>> {
+        let coop = ready!(coop::poll_proceed(cx));
         match self.io_source.take() {
             Some(source) => {
+                coop.made_progress();
                 let res = source.result().unwrap();
                 match res {
                     Err(x) => Poll::Ready(Err(x)),
@@ -579,6 +582,7 @@ impl AsyncBufRead for StreamReader {
             }
             None => {
                 if self.buffer.remaining_unconsumed_bytes() > 0 {
+                    coop.made_progress();
                     let this = self.project();
                     Poll::Ready(Ok(this.buffer.unconsumed_bytes()))
                 } else {
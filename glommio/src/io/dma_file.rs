@@ -4,6 +4,7 @@
 // This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
 //
 use crate::{
+    executor::coop,
     io::{
         bulk_io::{
             CoalescedReads, IoVec, MergedBufferLimit, OrderedBulkIo, ReadAmplificationLimit,
@@ -409,7 +410,11 @@ impl DmaFile {
             self.file.scheduler.borrow().as_ref(),
         );
 
-        let read_size = enhanced_try!(source.collect_rw().await, "Reading", self.file)?;
+        let read_size = enhanced_try!(
+            coop::budgeted(source.collect_rw()).await,
+            "Reading",
+            self.file
+        )?;
         Ok(ReadResult::from_sliced_buffer(
             source,
             b,
@@ -780,7 +785,7 @@ pub(crate) mod test {
     };
     use futures::join;
     use futures_lite::{stream, StreamExt};
-    use rand::{seq::SliceRandom, thread_rng};
+    use rand::{seq::SliceRandom, rng as thread_rng};
     use std::{cell::RefCell, convert::TryInto, path::PathBuf, time::Duration};
 
     macro_rules! dma_file_test {
@@ -4,6 +4,7 @@
 // This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
 //
 use crate::{
+    executor::coop,
     io::{dma_file::align_down, read_result::ReadResult, DmaFile},
     sys::DmaBuffer,
     task, ByteSliceMutExt,
@@ -606,7 +607,10 @@ impl AsyncRead for DmaStreamReader {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<io::Result<usize>> {
-        let res = ready!(self.poll_get_buffer_aligned(cx, buf.len() as u64))?;
+        let coop = ready!(coop::poll_proceed(cx));
+        let res = ready!(self.poll_get_buffer_aligned(cx, buf.len() as u64));
+        coop.made_progress();
+        let res = res?;
         buf[..res.len()].copy_from_slice(&res);
         Poll::Ready(Ok(res.len()))
     }
@@ -433,6 +433,7 @@ pub mod controllers;
 mod error;
 mod executor;
 pub mod io;
+pub mod metrics;
 pub mod net;
 mod shares;
 pub mod sync;
@@ -448,18 +449,23 @@ pub use crate::{
     executor::{
         allocate_dma_buffer, allocate_dma_buffer_global, executor, spawn_local, spawn_local_into,
         spawn_scoped_local, spawn_scoped_local_into,
-        stall::{DefaultStallDetectionHandler, StallDetectionHandler},
-        yield_if_needed, CpuSet, ExecutorJoinHandle, ExecutorProxy, ExecutorStats, LocalExecutor,
-        LocalExecutorBuilder, LocalExecutorPoolBuilder, Placement, PoolPlacement,
-        PoolThreadHandles, ScopedTask, Task, TaskQueueHandle, TaskQueueStats,
+        stall::{
+            DefaultStallDetectionHandler, StallDetection, StallDetectionHandler,
+            StallHistogramHandler, StalledTask, TaskQueueStallStats,
+        },
+        yield_if_needed, CpuSet, ExecutorJoinHandle, ExecutorLiveness, ExecutorProxy,
+        ExecutorStats, LocalExecutor, LocalExecutorBuilder, LocalExecutorPoolBuilder, PanicPolicy,
+        Placement, PoolMembership, PoolPlacement, PoolThreadHandles, RemoteJoinHandle, ScopedTask,
+        ShutdownReport, ShutdownToken, Task, TaskQueueHandle, TaskQueueStats,
     },
     shares::{Shares, SharesManager},
-    sys::hardware_topology::CpuLocation,
+    sys::{blocking::BlockingQueuePolicy, hardware_topology::CpuLocation},
 };
 pub use enclose::enclose;
 pub use scopeguard::defer;
 use sketches_ddsketch::DDSketch;
 use std::{
+    collections::BTreeMap,
     fmt::{Debug, Formatter},
     iter::Sum,
     time::Duration,
@@ -470,9 +476,9 @@ pub mod prelude {
     #[doc(no_inline)]
     pub use crate::{
         error::GlommioError, executor, spawn_local, spawn_local_into, yield_if_needed,
-        ByteSliceExt, ByteSliceMutExt, ExecutorProxy, IoStats, Latency, LocalExecutor,
-        LocalExecutorBuilder, LocalExecutorPoolBuilder, Placement, PoolPlacement,
-        PoolThreadHandles, RingIoStats, Shares, TaskQueueHandle,
+        BlockingPoolStats, ByteSliceExt, ByteSliceMutExt, ExecutorProxy, IoMemoryStats, IoStats,
+        Latency, LocalExecutor, LocalExecutorBuilder, LocalExecutorPoolBuilder, PanicPolicy,
+        Placement, PoolPlacement, PoolThreadHandles, RingIoStats, Shares, TaskQueueHandle,
     };
 }
 
@@ -705,7 +711,7 @@ impl<'a> Sum<&'a RingIoStats> for RingIoStats {
 }
 
 /// Stores information about IO
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct IoStats {
     /// The IO stats of the main ring
     pub main_ring: RingIoStats,
@@ -731,6 +737,164 @@ impl IoStats {
             .copied()
             .sum()
     }
+
+    pub(crate) fn accumulate(&mut self, other: &IoStats) {
+        self.main_ring = [&self.main_ring, &other.main_ring].into_iter().sum();
+        self.latency_ring = [&self.latency_ring, &other.latency_ring].into_iter().sum();
+        self.poll_ring = [&self.poll_ring, &other.poll_ring].into_iter().sum();
+    }
+}
+
+/// Stores information about the operations run by one lane of the blocking
+/// thread pool
+#[derive(Clone)]
+pub struct BlockingLaneStats {
+    // Gauges
+    pub(crate) threads: usize,
+    pub(crate) queue_depth: usize,
+    pub(crate) queue_capacity: usize,
+
+    // Counters
+    pub(crate) completed: u64,
+    pub(crate) rejected: u64,
+
+    // Distributions
+    pub(crate) wait_time_us: sketches_ddsketch::DDSketch,
+    pub(crate) run_time_us: sketches_ddsketch::DDSketch,
+}
+
+impl Default for BlockingLaneStats {
+    fn default() -> Self {
+        Self {
+            threads: 0,
+            queue_depth: 0,
+            queue_capacity: 0,
+            completed: 0,
+            rejected: 0,
+            wait_time_us: sketches_ddsketch::DDSketch::new(sketches_ddsketch::Config::new(
+                0.01, 2048, 1.0e-9,
+            )),
+            run_time_us: sketches_ddsketch::DDSketch::new(sketches_ddsketch::Config::new(
+                0.01, 2048, 1.0e-9,
+            )),
+        }
+    }
+}
+
+impl Debug for BlockingLaneStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockingLaneStats")
+            .field("threads", &self.threads)
+            .field("queue_depth", &self.queue_depth)
+            .field("queue_capacity", &self.queue_capacity)
+            .field("completed", &self.completed)
+            .field("rejected", &self.rejected)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BlockingLaneStats {
+    /// The number of threads serving the queue of this lane
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// The number of operations waiting in the queue of this lane for a
+    /// thread to pick them up, at the time the stats were taken
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depth
+    }
+
+    /// How many operations the queue of this lane holds before new ones wait
+    /// or are rejected, as per the [`BlockingQueuePolicy`]
+    pub fn queue_capacity(&self) -> usize {
+        self.queue_capacity
+    }
+
+    /// The number of operations of this lane that ran to completion
+    pub fn completed(&self) -> u64 {
+        self.completed
+    }
+
+    /// The number of operations of this lane that failed because the queue
+    /// was full
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    /// The queueing delay
+    ///
+    /// Returns a distribution of measures tracking the time between the moment
+    /// an operation was queued up and the moment a thread started running it
+    pub fn wait_time_us(&self) -> &DDSketch {
+        &self.wait_time_us
+    }
+
+    /// The run time
+    ///
+    /// Returns a distribution of measures tracking the time threads spent
+    /// running operations
+    pub fn run_time_us(&self) -> &DDSketch {
+        &self.run_time_us
+    }
+}
+
+/// Stores information about the blocking thread pool of an executor
+///
+/// Operations are accounted for in the lane they belong to, even when both
+/// lanes share the same threads and queue, in which case the gauges of both
+/// lanes are the same.
+#[derive(Debug, Clone, Default)]
+pub struct BlockingPoolStats {
+    /// The stats of glommio's own file operations, such as renames and
+    /// truncations
+    pub internal: BlockingLaneStats,
+    /// The stats of the closures passed to
+    /// [`spawn_blocking`](ExecutorProxy::spawn_blocking)
+    pub user: BlockingLaneStats,
+}
+
+/// Stores information about the memory an executor reserves for storage I/O
+/// (see [`LocalExecutorBuilder::io_memory`]), from which
+/// [`allocate_dma_buffer`] allocates buffers
+#[derive(Debug, Clone, Default)]
+pub struct IoMemoryStats {
+    pub(crate) size: usize,
+    pub(crate) huge_pages: bool,
+    pub(crate) numa_node: Option<usize>,
+    pub(crate) registered: bool,
+    pub(crate) bytes_per_node: BTreeMap<usize, usize>,
+}
+
+impl IoMemoryStats {
+    /// The size of the memory, in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether the memory is backed by huge pages
+    pub fn huge_pages(&self) -> bool {
+        self.huge_pages
+    }
+
+    /// The NUMA node the memory was requested from, if any.  The kernel
+    /// falls back to other nodes when this one runs out of free memory
+    pub fn numa_node(&self) -> Option<usize> {
+        self.numa_node
+    }
+
+    /// Whether the memory is registered with io_uring
+    pub fn registered(&self) -> bool {
+        self.registered
+    }
+
+    /// How many bytes of the memory are on each NUMA node, at the time the
+    /// stats were taken.  This is estimated from the node of a few hundred
+    /// pages spread over the memory.  Memory that was never used is not
+    /// allocated yet, and not accounted for
+    pub fn bytes_per_node(&self) -> &BTreeMap<usize, usize> {
+        &self.bytes_per_node
+    }
 }
 
 #[cfg(test)]
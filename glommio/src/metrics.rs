@@ -0,0 +1,659 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
+//
+//! glommio::metrics renders the statistics of executors as [OpenMetrics] text,
+//! which Prometheus and most monitoring agents know how to scrape.
+//!
+//! Snapshots are taken with [`ExecutorProxy::metrics`] for the current
+//! executor, or [`ExecutorProxy::pool_metrics`] for every executor of its
+//! pool, and rendered with [`encode`]. Serving them is then a matter of writing
+//! the text out from whatever handles the scrape:
+//!
+//! ```no_run
+//! use futures_lite::{AsyncReadExt, AsyncWriteExt};
+//! use glommio::{metrics, net::TcpListener, LocalExecutor};
+//!
+//! let ex = LocalExecutor::default();
+//! ex.run(async {
+//!     let listener = TcpListener::bind("127.0.0.1:9100").unwrap();
+//!     loop {
+//!         let mut stream = listener.accept().await.unwrap();
+//!         let mut request = [0u8; 1024];
+//!         let _ = stream.read(&mut request).await;
+//!
+//!         let body = metrics::encode(&glommio::executor().pool_metrics().await);
+//!         let response = format!(
+//!             "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+//!             metrics::CONTENT_TYPE,
+//!             body.len(),
+//!             body
+//!         );
+//!         let _ = stream.write_all(response.as_bytes()).await;
+//!     }
+//! });
+//! ```
+//!
+//! [OpenMetrics]: https://openmetrics.io
+//! [`ExecutorProxy::metrics`]: crate::ExecutorProxy::metrics
+//! [`ExecutorProxy::pool_metrics`]: crate::ExecutorProxy::pool_metrics
+use crate::{
+    executor::new_scheduling_delay_sketch, ExecutorStats, IoStats, RingIoStats, TaskQueueHandle,
+    TaskQueueStats,
+};
+use sketches_ddsketch::DDSketch;
+use std::fmt::{self, Write};
+
+/// The content type of the text produced by [`encode`], to be sent as the
+/// `Content-Type` header of a scrape response.
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// The quantiles rendered for each latency distribution
+const QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 0.999];
+
+/// A snapshot of the statistics of an executor, as returned by
+/// [`ExecutorProxy::metrics`].
+///
+/// Unlike the values returned by [`ExecutorProxy::executor_stats`] and
+/// friends, the values in a snapshot are accumulated since the executor
+/// started, as monitoring systems expect counters to be. Taking a snapshot
+/// doesn't reset anything, and what an application reads through those
+/// methods is still accounted for in the following snapshots.
+///
+/// [`ExecutorProxy::metrics`]: crate::ExecutorProxy::metrics
+/// [`ExecutorProxy::executor_stats`]: crate::ExecutorProxy::executor_stats
+#[derive(Debug, Clone)]
+pub struct ExecutorMetrics {
+    pub(crate) executor_id: usize,
+    pub(crate) executor: ExecutorStats,
+    pub(crate) io: IoStats,
+    pub(crate) task_queues: Vec<TaskQueueMetrics>,
+}
+
+impl ExecutorMetrics {
+    pub(crate) fn new(executor_id: usize) -> Self {
+        Self {
+            executor_id,
+            executor: Default::default(),
+            io: Default::default(),
+            task_queues: Vec::new(),
+        }
+    }
+
+    /// Adds the statistics that were not read yet. Task queues that are not
+    /// part of `task_queues` were removed, and are dropped.
+    pub(crate) fn accumulate(
+        &mut self,
+        executor: &ExecutorStats,
+        io: &IoStats,
+        task_queues: Vec<TaskQueueMetrics>,
+    ) {
+        self.executor.accumulate(executor);
+        self.io.accumulate(io);
+
+        let mut previous = std::mem::take(&mut self.task_queues);
+        self.task_queues = task_queues
+            .into_iter()
+            .map(|delta| {
+                match previous
+                    .iter()
+                    .position(|tq| tq.stats.index() == delta.stats.index())
+                {
+                    Some(pos) => {
+                        let mut tq = previous.swap_remove(pos);
+                        tq.accumulate(delta);
+                        tq
+                    }
+                    None => delta,
+                }
+            })
+            .collect();
+    }
+
+    /// Returns the entry of a task queue, adding an empty one the first time.
+    pub(crate) fn task_queue_mut(
+        &mut self,
+        name: &str,
+        stats: &TaskQueueStats,
+    ) -> &mut TaskQueueMetrics {
+        let pos = match self
+            .task_queues
+            .iter()
+            .position(|tq| tq.stats.index() == stats.index())
+        {
+            Some(pos) => pos,
+            None => {
+                self.task_queues.push(TaskQueueMetrics {
+                    name: name.to_string(),
+                    stats: stats.empty(),
+                    scheduling_delay_us: new_scheduling_delay_sketch(),
+                    io: None,
+                });
+                self.task_queues.len() - 1
+            }
+        };
+        &mut self.task_queues[pos]
+    }
+
+    pub(crate) fn remove_task_queue(&mut self, handle: TaskQueueHandle) {
+        self.task_queues.retain(|tq| tq.stats.index() != handle);
+    }
+
+    /// Returns the id of the executor the snapshot was taken from
+    pub fn executor_id(&self) -> usize {
+        self.executor_id
+    }
+
+    /// Returns the statistics of the executor, accumulated since it started
+    pub fn executor_stats(&self) -> &ExecutorStats {
+        &self.executor
+    }
+
+    /// Returns the IO statistics of the executor, accumulated since it
+    /// started
+    pub fn io_stats(&self) -> &IoStats {
+        &self.io
+    }
+
+    /// Returns a snapshot of each task queue of the executor
+    pub fn task_queues(&self) -> &[TaskQueueMetrics] {
+        &self.task_queues
+    }
+}
+
+/// A snapshot of the statistics of a task queue, as part of an
+/// [`ExecutorMetrics`].
+#[derive(Clone)]
+pub struct TaskQueueMetrics {
+    pub(crate) name: String,
+    pub(crate) stats: TaskQueueStats,
+    pub(crate) scheduling_delay_us: DDSketch,
+    pub(crate) io: Option<IoStats>,
+}
+
+impl fmt::Debug for TaskQueueMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskQueueMetrics")
+            .field("name", &self.name)
+            .field("stats", &self.stats)
+            .field("io", &self.io)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TaskQueueMetrics {
+    pub(crate) fn accumulate(&mut self, delta: TaskQueueMetrics) {
+        self.stats.accumulate(&delta.stats);
+        self.scheduling_delay_us
+            .merge(&delta.scheduling_delay_us)
+            .unwrap();
+        if let Some(io) = delta.io {
+            self.io.get_or_insert_with(Default::default).accumulate(&io);
+        }
+    }
+
+    /// Returns the name of the task queue
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the statistics of the task queue, accumulated since it was
+    /// created
+    pub fn stats(&self) -> &TaskQueueStats {
+        &self.stats
+    }
+
+    /// Returns the distribution of the scheduling delays of the task queue,
+    /// in microseconds, accumulated since it was created. See
+    /// [`ExecutorProxy::task_queue_scheduling_delay_us`].
+    ///
+    /// [`ExecutorProxy::task_queue_scheduling_delay_us`]: crate::ExecutorProxy::task_queue_scheduling_delay_us
+    pub fn scheduling_delay_us(&self) -> &DDSketch {
+        &self.scheduling_delay_us
+    }
+
+    /// Returns the IO statistics of the task queue, accumulated since it was
+    /// created, if it ever performed IO
+    pub fn io_stats(&self) -> Option<&IoStats> {
+        self.io.as_ref()
+    }
+}
+
+type Labels<'a> = Vec<(&'static str, &'a str)>;
+type ExecutorValue = fn(&ExecutorStats) -> f64;
+type RingCounter = fn(&RingIoStats) -> u64;
+type RingSketch = fn(&RingIoStats) -> &DDSketch;
+
+enum Value<'a> {
+    Number(f64),
+    // in microseconds, rendered in seconds
+    Sketch(&'a DDSketch),
+}
+
+#[derive(Copy, Clone)]
+enum Kind {
+    Counter,
+    Gauge,
+    Summary,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Summary => "summary",
+        }
+    }
+}
+
+struct Family {
+    name: String,
+    kind: Kind,
+    unit: Option<&'static str>,
+    help: &'static str,
+}
+
+/// Renders snapshots of one or more executors as [OpenMetrics] text.
+///
+/// Each sample is labeled with the id of its executor (`executor`) and, where
+/// it applies, the name and index of its task queue (`task_queue` and
+/// `task_queue_index`) and the ring the IO went through (`ring`). Latency
+/// distributions are rendered as summaries, in seconds.
+///
+/// [OpenMetrics]: https://openmetrics.io
+pub fn encode(metrics: &[ExecutorMetrics]) -> String {
+    let mut out = String::new();
+    let ids: Vec<String> = metrics.iter().map(|m| m.executor_id.to_string()).collect();
+    let executors = || metrics.iter().zip(ids.iter().map(String::as_str));
+
+    let executor_families: [(Family, ExecutorValue); 4] = [
+        (
+            Family::counter(
+                "glommio_executor_runtime",
+                Some("seconds"),
+                "Time spent running tasks",
+            ),
+            |s| s.executor_runtime().as_secs_f64(),
+        ),
+        (
+            Family::counter(
+                "glommio_executor_total_runtime",
+                Some("seconds"),
+                "Time spent running tasks and polling for IO",
+            ),
+            |s| s.total_runtime().as_secs_f64(),
+        ),
+        (
+            Family::counter(
+                "glommio_executor_scheduler_runs",
+                None,
+                "Number of times a task queue was selected to run",
+            ),
+            |s| s.scheduler_runs() as f64,
+        ),
+        (
+            Family::counter(
+                "glommio_executor_tasks_executed",
+                None,
+                "Number of tasks executed",
+            ),
+            |s| s.tasks_executed() as f64,
+        ),
+    ];
+    for (family, value) in &executor_families {
+        family.write(
+            &mut out,
+            executors().map(|(m, id)| (vec![("executor", id)], Value::Number(value(&m.executor)))),
+        );
+    }
+
+    // names need not be unique, so task queues are labeled with their index too
+    let indexes: Vec<Vec<String>> = metrics
+        .iter()
+        .map(|m| {
+            m.task_queues
+                .iter()
+                .map(|tq| tq.stats.index().index().to_string())
+                .collect()
+        })
+        .collect();
+    let task_queues = || {
+        executors().zip(&indexes).flat_map(|((m, id), indexes)| {
+            m.task_queues.iter().zip(indexes).map(move |(tq, index)| {
+                let labels = vec![
+                    ("executor", id),
+                    ("task_queue", tq.name.as_str()),
+                    ("task_queue_index", index.as_str()),
+                ];
+                (labels, tq)
+            })
+        })
+    };
+    Family::counter(
+        "glommio_task_queue_runtime",
+        Some("seconds"),
+        "Time spent running the tasks of the task queue",
+    )
+    .write(
+        &mut out,
+        task_queues().map(|(l, tq)| (l, Value::Number(tq.stats.runtime().as_secs_f64()))),
+    );
+    Family::counter(
+        "glommio_task_queue_selected",
+        None,
+        "Number of times the task queue was selected to run",
+    )
+    .write(
+        &mut out,
+        task_queues().map(|(l, tq)| (l, Value::Number(tq.stats.queue_selected() as f64))),
+    );
+    Family::gauge(
+        "glommio_task_queue_shares",
+        "Shares of the task queue the last time the scheduler ran",
+    )
+    .write(
+        &mut out,
+        task_queues().map(|(l, tq)| (l, Value::Number(tq.stats.current_shares() as f64))),
+    );
+    Family::summary(
+        "glommio_task_queue_scheduling_delay",
+        "Time between a task of the task queue being woken up and it being polled",
+    )
+    .write(
+        &mut out,
+        task_queues().map(|(l, tq)| (l, Value::Sketch(&tq.scheduling_delay_us))),
+    );
+
+    let executor_rings = || {
+        executors().flat_map(|(m, id)| {
+            rings(&m.io).map(move |(ring, stats)| (vec![("executor", id), ("ring", ring)], stats))
+        })
+    };
+    write_io_families(&mut out, "glommio_io", executor_rings);
+
+    let task_queue_rings = || {
+        task_queues().flat_map(|(labels, tq)| {
+            tq.io.iter().flat_map(rings).map(move |(ring, stats)| {
+                let mut labels = labels.clone();
+                labels.push(("ring", ring));
+                (labels, stats)
+            })
+        })
+    };
+    write_io_families(&mut out, "glommio_task_queue_io", task_queue_rings);
+
+    out.push_str("# EOF\n");
+    out
+}
+
+fn rings(io: &IoStats) -> impl Iterator<Item = (&'static str, &RingIoStats)> {
+    [
+        ("main", &io.main_ring),
+        ("latency", &io.latency_ring),
+        ("poll", &io.poll_ring),
+    ]
+    .into_iter()
+}
+
+fn write_io_families<'a, I, F>(out: &mut String, prefix: &str, rings: F)
+where
+    I: Iterator<Item = (Labels<'a>, &'a RingIoStats)>,
+    F: Fn() -> I,
+{
+    let counters: [(&str, Option<&'static str>, &'static str, RingCounter); 12] = [
+        ("files_opened", None, "Number of files opened", |s| {
+            s.files_opened()
+        }),
+        ("files_closed", None, "Number of files closed", |s| {
+            s.files_closed()
+        }),
+        ("file_reads", None, "Number of file reads", |s| {
+            s.file_reads().0
+        }),
+        ("file_read", Some("bytes"), "Bytes read from files", |s| {
+            s.file_reads().1
+        }),
+        (
+            "file_deduped_reads",
+            None,
+            "Number of file reads served from another read's buffer",
+            |s| s.file_deduped_reads().0,
+        ),
+        (
+            "file_deduped_read",
+            Some("bytes"),
+            "Bytes read from another read's buffer",
+            |s| s.file_deduped_reads().1,
+        ),
+        (
+            "file_buffered_reads",
+            None,
+            "Number of buffered file reads",
+            |s| s.file_buffered_reads().0,
+        ),
+        (
+            "file_buffered_read",
+            Some("bytes"),
+            "Bytes read from files through buffered reads",
+            |s| s.file_buffered_reads().1,
+        ),
+        ("file_writes", None, "Number of file writes", |s| {
+            s.file_writes().0
+        }),
+        (
+            "file_written",
+            Some("bytes"),
+            "Bytes written to files",
+            |s| s.file_writes().1,
+        ),
+        (
+            "file_buffered_writes",
+            None,
+            "Number of buffered file writes",
+            |s| s.file_buffered_writes().0,
+        ),
+        (
+            "file_buffered_written",
+            Some("bytes"),
+            "Bytes written to files through buffered writes",
+            |s| s.file_buffered_writes().1,
+        ),
+    ];
+    for (suffix, unit, help, value) in &counters {
+        Family::counter(format!("{prefix}_{suffix}"), *unit, help).write(
+            out,
+            rings().map(|(l, s)| (l, Value::Number(value(s) as f64))),
+        );
+    }
+
+    let sketches: [(&str, &'static str, RingSketch); 3] = [
+        (
+            "pre_reactor_scheduler_latency",
+            "Time between an IO request being queued and it being submitted to the kernel",
+            |s| s.pre_reactor_io_scheduler_latency_us(),
+        ),
+        ("latency", "Time IO requests spent in the ring", |s| {
+            s.io_latency_us()
+        }),
+        (
+            "post_reactor_scheduler_latency",
+            "Time between an IO request being fulfilled and its result being consumed",
+            |s| s.post_reactor_io_scheduler_latency_us(),
+        ),
+    ];
+    for (suffix, help, value) in &sketches {
+        Family::summary(format!("{prefix}_{suffix}"), help)
+            .write(out, rings().map(|(l, s)| (l, Value::Sketch(value(s)))));
+    }
+}
+
+impl Family {
+    fn counter(name: impl Into<String>, unit: Option<&'static str>, help: &'static str) -> Self {
+        Self {
+            name: name.into(),
+            kind: Kind::Counter,
+            unit,
+            help,
+        }
+    }
+
+    fn gauge(name: impl Into<String>, help: &'static str) -> Self {
+        Self {
+            name: name.into(),
+            kind: Kind::Gauge,
+            unit: None,
+            help,
+        }
+    }
+
+    fn summary(name: impl Into<String>, help: &'static str) -> Self {
+        Self {
+            name: name.into(),
+            kind: Kind::Summary,
+            unit: Some("seconds"),
+            help,
+        }
+    }
+
+    fn write<'a>(&self, out: &mut String, samples: impl Iterator<Item = (Labels<'a>, Value<'a>)>) {
+        // the name of a family has to end with its unit
+        let name = match self.unit {
+            Some(unit) => format!("{}_{}", self.name, unit),
+            None => self.name.clone(),
+        };
+        let _ = writeln!(out, "# TYPE {} {}", name, self.kind.as_str());
+        if let Some(unit) = self.unit {
+            let _ = writeln!(out, "# UNIT {name} {unit}");
+        }
+        let _ = writeln!(out, "# HELP {} {}", name, self.help);
+
+        for (labels, value) in samples {
+            match value {
+                Value::Number(value) => match self.kind {
+                    Kind::Counter => sample(out, &name, "_total", &labels, None, value),
+                    _ => sample(out, &name, "", &labels, None, value),
+                },
+                Value::Sketch(sketch) => {
+                    for q in QUANTILES {
+                        if let Ok(Some(value)) = sketch.quantile(q) {
+                            sample(out, &name, "", &labels, Some(q), value / 1e6);
+                        }
+                    }
+                    let sum = sketch.sum().unwrap_or_default();
+                    sample(out, &name, "_sum", &labels, None, sum / 1e6);
+                    sample(out, &name, "_count", &labels, None, sketch.count() as f64);
+                }
+            }
+        }
+    }
+}
+
+fn sample(
+    out: &mut String,
+    name: &str,
+    suffix: &str,
+    labels: &[(&str, &str)],
+    quantile: Option<f64>,
+    value: f64,
+) {
+    out.push_str(name);
+    out.push_str(suffix);
+    out.push('{');
+    for (i, (key, value)) in labels.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let _ = write!(out, "{key}=\"");
+        escape(out, value);
+        out.push('"');
+    }
+    if let Some(quantile) = quantile {
+        let _ = write!(out, ",quantile=\"{quantile}\"");
+    }
+    let _ = writeln!(out, "}} {value}");
+}
+
+fn escape(out: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{executor, LocalExecutor};
+
+    #[test]
+    fn encode_executor_metrics() {
+        let local_ex = LocalExecutor::default();
+        local_ex.run(async {
+            executor().create_task_queue(
+                crate::Shares::default(),
+                crate::Latency::NotImportant,
+                "say \"hi\"",
+            );
+            crate::spawn_local(async {}).await;
+
+            let metrics = executor().metrics();
+            assert_eq!(metrics.executor_id(), executor().id());
+            assert!(metrics.executor_stats().tasks_executed() > 0);
+            assert_eq!(metrics.task_queues().len(), 2);
+
+            let text = encode(&[metrics]);
+            assert!(text.ends_with("# EOF\n"));
+            assert!(text.contains("# TYPE glommio_executor_tasks_executed counter\n"));
+            assert!(text.contains(&format!(
+                "glommio_task_queue_selected_total{{executor=\"{}\",task_queue=\"default\",\
+                 task_queue_index=\"0\"}}",
+                executor().id()
+            )));
+            assert!(text.contains("task_queue=\"say \\\"hi\\\"\""));
+            assert!(text.contains("# TYPE glommio_io_latency_seconds summary\n"));
+        });
+    }
+
+    #[test]
+    fn metrics_accumulate_across_snapshots() {
+        let local_ex = LocalExecutor::default();
+        local_ex.run(async {
+            crate::spawn_local(async {}).await;
+            let first = executor().metrics().executor_stats().tasks_executed();
+            crate::spawn_local(async {}).await;
+            let second = executor().metrics().executor_stats().tasks_executed();
+            assert!(second > first);
+        });
+    }
+
+    #[test]
+    fn metrics_leave_stats_alone() {
+        let local_ex = LocalExecutor::default();
+        local_ex.run(async {
+            crate::spawn_local(async {}).await;
+            let tq = executor().current_task_queue();
+            let selected = executor().metrics().task_queues()[0]
+                .stats()
+                .queue_selected();
+            assert!(selected > 0);
+
+            // the snapshot didn't reset them
+            assert!(executor().executor_stats().tasks_executed() > 0);
+            assert_eq!(
+                executor().task_queue_stats(tq).unwrap().queue_selected(),
+                selected
+            );
+
+            // and what was read is still in the next snapshot
+            let metrics = executor().metrics();
+            assert!(metrics.executor_stats().tasks_executed() > 0);
+            assert!(metrics.task_queues()[0].stats().queue_selected() >= selected);
+        });
+    }
+}
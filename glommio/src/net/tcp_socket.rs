@@ -5,6 +5,7 @@
 //
 use super::stream::GlommioStream;
 use crate::{
+    executor::coop,
     net::{
         stream::{Buffered, NonBuffered, Preallocated, RxBuf},
         yolo_accept,
@@ -15,6 +16,7 @@ use crate::{
 use futures_lite::{
     future::poll_fn,
     io::{AsyncBufRead, AsyncRead, AsyncWrite},
+    ready,
     stream::{self, Stream},
 };
 use nix::sys::socket::{InetAddr, SockAddr};
@@ -693,8 +695,13 @@ impl<B: Buffered + Unpin> AsyncBufRead for TcpStream<B> {
         self: Pin<&'a mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<io::Result<&'a [u8]>> {
+        let coop = ready!(coop::poll_proceed(cx));
         let this = self.project();
-        this.stream.poll_fill_buf(cx)
+        let res = this.stream.poll_fill_buf(cx);
+        if res.is_ready() {
+            coop.made_progress();
+        }
+        res
     }
 
     fn consume(mut self: Pin<&mut Self>, amt: usize) {
@@ -708,7 +715,12 @@ impl<B: RxBuf + Unpin> AsyncRead for TcpStream<B> {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<io::Result<usize>> {
-        Pin::new(&mut self.stream).poll_read(cx, buf)
+        let coop = ready!(coop::poll_proceed(cx));
+        let res = Pin::new(&mut self.stream).poll_read(cx, buf);
+        if res.is_ready() {
+            coop.made_progress();
+        }
+        res
     }
 }
 
@@ -5,12 +5,14 @@
 //
 use super::{datagram::GlommioDatagram, stream::GlommioStream};
 use crate::{
+    executor::coop,
     net::stream::{Buffered, NonBuffered, Preallocated, RxBuf},
     reactor::Reactor,
 };
 use futures_lite::{
     future::poll_fn,
     io::{AsyncBufRead, AsyncRead, AsyncWrite},
+    ready,
     stream::{self, Stream},
 };
 use nix::sys::socket::{SockAddr, UnixAddr};
@@ -410,8 +412,13 @@ impl<B: Buffered + Unpin> AsyncBufRead for UnixStream<B> {
         self: Pin<&'a mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<io::Result<&'a [u8]>> {
+        let coop = ready!(coop::poll_proceed(cx));
         let this = self.project();
-        this.stream.poll_fill_buf(cx)
+        let res = this.stream.poll_fill_buf(cx);
+        if res.is_ready() {
+            coop.made_progress();
+        }
+        res
     }
 
     fn consume(mut self: Pin<&mut Self>, amt: usize) {
@@ -425,7 +432,12 @@ impl<B: RxBuf + Unpin> AsyncRead for UnixStream<B> {
         cx: &mut Context<'_>,
         buf: &mut [u8],
     ) -> Poll<io::Result<usize>> {
-        Pin::new(&mut self.stream).poll_read(cx, buf)
+        let coop = ready!(coop::poll_proceed(cx));
+        let res = Pin::new(&mut self.stream).poll_read(cx, buf);
+        if res.is_ready() {
+            coop.made_progress();
+        }
+        res
     }
 }
 
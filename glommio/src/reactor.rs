@@ -6,7 +6,7 @@
 //!
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::BTreeMap,
     ffi::CString,
     fmt,
@@ -36,7 +36,8 @@ use crate::{
         DmaSource, IoBuffer, PollableStatus, SleepNotifier, Source, SourceType, StatsCollection,
         Statx,
     },
-    IoRequirements, IoStats, TaskQueueHandle,
+    timer::TestClock,
+    BlockingPoolStats, IoMemoryStats, IoRequirements, IoStats, TaskQueueHandle,
 };
 use nix::poll::PollFlags;
 
@@ -81,6 +82,10 @@ struct Timers {
     timer_id: u64,
     timers_by_id: AHashMap<u64, Instant>,
 
+    /// The clock timers run on, if not on real time.
+    clock: Option<TestClock>,
+    executor_id: usize,
+
     /// An ordered map of registered timers.
     ///
     /// Timers are in the order in which they fire. The `u64` in this type is
@@ -90,10 +95,15 @@ struct Timers {
 }
 
 impl Timers {
-    fn new() -> Timers {
+    fn new(clock: Option<TestClock>, executor_id: usize) -> Timers {
+        if let Some(clock) = &clock {
+            clock.install(executor_id);
+        }
         Timers {
             timer_id: 0,
             timers_by_id: AHashMap::new(),
+            clock,
+            executor_id,
             timers: BTreeMap::new(),
         }
     }
@@ -119,10 +129,35 @@ impl Timers {
         self.timers.insert((when, id), waker);
     }
 
+    fn now(&self) -> Instant {
+        self.clock
+            .as_ref()
+            .map_or_else(Instant::now, TestClock::now)
+    }
+
+    /// Moves an auto-advancing clock forward to when the next timer fires.
+    /// Returns whether it did.
+    fn advance_to_next_timer(&mut self) -> bool {
+        match &self.clock {
+            Some(clock) => {
+                let next = self.timers.keys().next().map(|(when, _)| *when);
+                clock.auto_advance_to(self.executor_id, next)
+            }
+            None => false,
+        }
+    }
+
+    /// Tells the clock this executor has more to do than wait for it.
+    fn set_busy(&self) {
+        if let Some(clock) = &self.clock {
+            clock.set_busy(self.executor_id);
+        }
+    }
+
     /// Return the duration until next event and the number of
     /// ready and woke timers.
     fn process_timers(&mut self) -> (Option<Duration>, usize) {
-        let now = Instant::now();
+        let now = self.now();
 
         // Split timers into ready and pending timers.
         let pending = self.timers.split_off(&(now, 0));
@@ -132,7 +167,11 @@ impl Timers {
             wake!(waker);
         }
 
-        // Calculate the duration until the next event.
+        // Calculate the duration until the next event. A paused clock only
+        // moves when advanced, which wakes the executor up.
+        if self.clock.as_ref().map_or(false, TestClock::is_paused) {
+            return (None, woke);
+        }
         let next = self
             .timers
             .keys()
@@ -142,6 +181,14 @@ impl Timers {
     }
 }
 
+impl Drop for Timers {
+    fn drop(&mut self) {
+        if let Some(clock) = &self.clock {
+            clock.uninstall(self.executor_id);
+        }
+    }
+}
+
 /// The reactor.
 ///
 /// Every async I/O handle and every timer is registered here. Invocations of
@@ -161,6 +208,9 @@ pub(crate) struct Reactor {
     io_scheduler: Rc<IoScheduler>,
     record_io_latencies: bool,
 
+    /// The number of sources created so far.
+    nr_sources: Cell<u64>,
+
     /// Whether there are events in the latency ring.
     ///
     /// There will be events if the head and tail of the CQ ring are different.
@@ -178,19 +228,21 @@ pub(crate) struct Reactor {
 impl Reactor {
     pub(crate) fn new(
         notifier: Arc<SleepNotifier>,
-        io_memory: usize,
-        ring_depth: usize,
+        config: sys::ReactorConfig,
         record_io_latencies: bool,
+        clock: Option<TestClock>,
         blocking_thread: BlockingThreadPool,
     ) -> io::Result<Reactor> {
-        let sys = sys::Reactor::new(notifier, io_memory, ring_depth, blocking_thread)?;
+        let executor_id = notifier.id();
+        let sys = sys::Reactor::new(notifier, config, blocking_thread)?;
         let (preempt_ptr_head, preempt_ptr_tail) = sys.preempt_pointers();
         Ok(Reactor {
             sys,
-            timers: RefCell::new(Timers::new()),
+            timers: RefCell::new(Timers::new(clock, executor_id)),
             shared_channels: RefCell::new(SharedChannels::new()),
             io_scheduler: Rc::new(IoScheduler::new()),
             record_io_latencies,
+            nr_sources: Cell::new(0),
             preempt_ptr_head,
             preempt_ptr_tail: preempt_ptr_tail as _,
         })
@@ -200,10 +252,26 @@ impl Reactor {
         self.sys.io_stats()
     }
 
+    pub(crate) fn peek_io_stats(&self) -> IoStats {
+        self.sys.peek_io_stats()
+    }
+
+    pub(crate) fn blocking_pool_stats(&self) -> BlockingPoolStats {
+        self.sys.blocking_pool_stats()
+    }
+
+    pub(crate) fn io_memory_stats(&self) -> IoMemoryStats {
+        self.sys.io_memory_stats()
+    }
+
     pub(crate) fn task_queue_io_stats(&self, handle: &TaskQueueHandle) -> Option<IoStats> {
         self.sys.task_queue_io_stats(handle)
     }
 
+    pub(crate) fn peek_task_queue_io_stats(&self, handle: &TaskQueueHandle) -> Option<IoStats> {
+        self.sys.peek_task_queue_io_stats(handle)
+    }
+
     #[inline(always)]
     pub(crate) fn need_preempt(&self) -> bool {
         unsafe { *self.preempt_ptr_head != (*self.preempt_ptr_tail).load(Ordering::Acquire) }
@@ -223,13 +291,17 @@ impl Reactor {
         stype: SourceType,
         stats_collection: Option<StatsCollection>,
     ) -> Source {
-        sys::Source::new(
+        let source = sys::Source::new(
             self.io_scheduler.requirements(),
             raw,
             stype,
             stats_collection,
             Some(crate::executor().current_task_queue()),
-        )
+        );
+        let serial = self.nr_sources.get();
+        self.nr_sources.set(serial + 1);
+        source.inner.borrow_mut().serial = serial;
+        source
     }
 
     pub(crate) fn inform_io_requirements(&self, req: IoRequirements) {
@@ -707,9 +779,10 @@ impl Reactor {
     pub(crate) fn run_blocking(
         &self,
         func: Box<dyn FnOnce() + Send + 'static>,
+        may_reject: bool,
     ) -> impl Future<Output = Source> {
         let source = self.new_source(-1, SourceType::BlockingFn, None);
-        let waiter = self.sys.run_blocking(&source, func);
+        let waiter = self.sys.run_blocking(&source, func, may_reject);
 
         async move {
             waiter.await;
@@ -812,6 +885,18 @@ impl Reactor {
         timers.timers.contains_key(id)
     }
 
+    /// Returns the time timers are measured against, which is the one of the
+    /// test clock if one is installed.
+    pub(crate) fn now(&self) -> Instant {
+        self.timers.borrow().now()
+    }
+
+    /// Moves an auto-advancing test clock forward to when the next timer
+    /// fires, so it fires right away. Returns whether it did.
+    pub(crate) fn advance_to_next_timer(&self) -> bool {
+        self.timers.borrow_mut().advance_to_next_timer()
+    }
+
     /// Processes ready timers and extends the list of wakers to wake.
     ///
     /// Returns the duration until the next timer
@@ -824,6 +909,8 @@ impl Reactor {
         let mut channels = self.shared_channels.borrow_mut();
         let mut processed = channels.process_shared_channels();
         processed += self.sys.process_foreign_wakes();
+        processed += self.sys.process_foreign_spawns();
+        processed += crate::executor::process_stealable_tasks();
         processed
     }
 
@@ -856,6 +943,35 @@ impl Reactor {
         Ok(woke > 0)
     }
 
+    /// In simulation, polls until the I/O that is bound to complete did, so
+    /// the completions held back don't depend on how fast it is. Returns
+    /// whether that woke any task up.
+    pub(crate) fn settle_io(&self) -> io::Result<bool> {
+        let mut woke = false;
+        loop {
+            woke |= self.spin_poll_io()?;
+            if !self.sys.has_bounded_io_in_flight() {
+                return Ok(woke);
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    /// Returns the number of I/O completions held back, in simulation.
+    pub(crate) fn nr_held_completions(&self) -> usize {
+        self.sys.nr_held_completions()
+    }
+
+    /// Releases `count` of the I/O completions held back in simulation, each
+    /// picked by `pick` given the number of candidates left.
+    pub(crate) fn release_completions(
+        &self,
+        count: usize,
+        pick: impl FnMut(usize) -> usize,
+    ) -> usize {
+        self.sys.release_completions(count, pick)
+    }
+
     fn process_external_events(&self) -> (Option<Duration>, usize) {
         let (next_timer, mut woke) = self.process_timers();
         woke += self.process_shared_channels();
@@ -868,10 +984,13 @@ impl Reactor {
         let (next_timer, woke) = self.process_external_events();
 
         // Block on I/O events.
-        match self
+        let result = self
             .sys
-            .wait(timeout, next_timer, woke, || self.process_shared_channels())
-        {
+            .wait(timeout, next_timer, woke, || self.process_shared_channels());
+        // whatever woke us up, the clock can't auto-advance until we are idle
+        // again
+        self.timers.borrow().set_busy();
+        match result {
             // Don't wait for the next loop to process timers or shared channels
             Ok(true) => {
                 self.process_external_events();
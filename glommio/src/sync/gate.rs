@@ -59,6 +59,7 @@ impl Gate {
 
     /// Spawn a task for which the gate will wait on closing into the current
     /// task queue.
+    #[track_caller]
     pub fn spawn<T: 'static>(
         &self,
         future: impl Future<Output = T> + 'static,
@@ -67,6 +68,7 @@ impl Gate {
     }
 
     /// Spawn a task for which the gate will wait on closing
+    #[track_caller]
     pub fn spawn_into<T: 'static>(
         &self,
         future: impl Future<Output = T> + 'static,
@@ -24,5 +24,6 @@
 mod gate;
 mod rwlock;
 mod semaphore;
+mod task_group;
 
-pub use self::{gate::*, rwlock::*, semaphore::*};
+pub use self::{gate::*, rwlock::*, semaphore::*, task_group::*};
@@ -0,0 +1,310 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use futures_lite::Future;
+
+use crate::{task::JoinHandle, GlommioError, ResourceType, TaskQueueHandle};
+
+use super::Gate;
+
+/// A group of child tasks whose lifetime is bound to the group.
+///
+/// Children return a `Result`, and the first one to return an `Err` cancels
+/// all the others. Dropping the group cancels all the children that are
+/// still running, so they can't outlive the scope that created them, even on
+/// error paths.
+///
+/// Children are tracked with a [`Gate`], so [`TaskGroup::join_all`] only
+/// returns once every child either completed or had its future dropped.
+///
+/// # Examples
+///
+/// ```
+/// use glommio::{sync::TaskGroup, LocalExecutor};
+///
+/// let ex = LocalExecutor::default();
+/// ex.run(async {
+///     let group = TaskGroup::new();
+///     for i in 0..4 {
+///         group.spawn(async move { Ok::<_, String>(i * 2) }).unwrap();
+///     }
+///     assert_eq!(group.join_all().await, Ok(vec![0, 2, 4, 6]));
+/// });
+/// ```
+pub struct TaskGroup<T, E> {
+    inner: Rc<TaskGroupInner<T, E>>,
+}
+
+impl<T: 'static, E: 'static> TaskGroup<T, E> {
+    /// Create a new, empty [`TaskGroup`]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            inner: Rc::new(TaskGroupInner {
+                gate: Gate::new(),
+                children: RefCell::new(Vec::new()),
+                results: RefCell::new(Vec::new()),
+                error: RefCell::new(None),
+            }),
+        }
+    }
+
+    /// Spawn a child task into the current task queue.
+    ///
+    /// Fails if a child already returned an error, in which case the group
+    /// is being canceled.
+    #[track_caller]
+    pub fn spawn(
+        &self,
+        future: impl Future<Output = Result<T, E>> + 'static,
+    ) -> Result<(), GlommioError<()>> {
+        self.spawn_into(future, crate::executor().current_task_queue())
+    }
+
+    /// Spawn a child task into the given task queue.
+    ///
+    /// Fails if a child already returned an error, in which case the group
+    /// is being canceled, or if the task queue doesn't exist.
+    #[track_caller]
+    pub fn spawn_into(
+        &self,
+        future: impl Future<Output = Result<T, E>> + 'static,
+        handle: TaskQueueHandle,
+    ) -> Result<(), GlommioError<()>> {
+        if self.inner.error.borrow().is_some() {
+            return Err(GlommioError::Closed(ResourceType::TaskGroup));
+        }
+
+        let index = self.inner.results.borrow().len();
+        let inner = self.inner.clone();
+        let task = self.inner.gate.spawn_into(
+            async move {
+                let result = future.await;
+                inner.complete(index, result);
+            },
+            handle,
+        )?;
+
+        self.inner.children.borrow_mut().push(Some(task.detach()));
+        self.inner.results.borrow_mut().push(None);
+        Ok(())
+    }
+
+    /// Wait for all the children to complete, and return their results in the
+    /// order they were spawned, or the first error returned by any of them.
+    ///
+    /// In case of error, this waits for the futures of the canceled children
+    /// to be dropped before returning.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of the first child that panicked, if the executor
+    /// catches panics (see [`PanicPolicy::Catch`]) and no child returned an
+    /// error.
+    ///
+    /// [`PanicPolicy::Catch`]: crate::PanicPolicy::Catch
+    pub async fn join_all(self) -> Result<Vec<T>, E> {
+        self.inner
+            .gate
+            .close()
+            .await
+            .expect("the gate of a task group is only closed once");
+
+        if let Some(err) = self.inner.error.take() {
+            return Err(err);
+        }
+        // children are only canceled once one of them failed, so the ones that
+        // didn't complete panicked
+        let panicked: Vec<_> = self
+            .inner
+            .children
+            .borrow_mut()
+            .iter_mut()
+            .filter_map(Option::take)
+            .collect();
+        for child in panicked {
+            if let Some(Err(panic)) = child.join().await {
+                std::panic::resume_unwind(panic.into_payload());
+            }
+        }
+        Ok(self
+            .inner
+            .results
+            .take()
+            .into_iter()
+            .map(|result| result.expect("children are only canceled once one of them failed"))
+            .collect())
+    }
+}
+
+impl<T, E> Drop for TaskGroup<T, E> {
+    fn drop(&mut self) {
+        self.inner.cancel()
+    }
+}
+
+impl<T, E> fmt::Debug for TaskGroup<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskGroup")
+            .field("gate", &self.inner.gate)
+            .field("children", &self.inner.results.borrow().len())
+            .field("failed", &self.inner.error.borrow().is_some())
+            .finish()
+    }
+}
+
+struct TaskGroupInner<T, E> {
+    gate: Gate,
+    // indexed by spawn order, `None` once the child completed
+    children: RefCell<Vec<Option<JoinHandle<()>>>>,
+    results: RefCell<Vec<Option<T>>>,
+    error: RefCell<Option<E>>,
+}
+
+impl<T, E> TaskGroupInner<T, E> {
+    fn complete(&self, index: usize, result: Result<T, E>) {
+        self.children.borrow_mut()[index] = None;
+        match result {
+            Ok(value) => self.results.borrow_mut()[index] = Some(value),
+            Err(err) => {
+                let mut error = self.error.borrow_mut();
+                if error.is_none() {
+                    *error = Some(err);
+                    drop(error);
+                    self.cancel();
+                }
+            }
+        }
+    }
+
+    fn cancel(&self) {
+        let children: Vec<_> = self
+            .children
+            .borrow_mut()
+            .iter_mut()
+            .filter_map(Option::take)
+            .collect();
+        for child in children {
+            child.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, time::Duration};
+
+    use crate::{enclose, timer::sleep, LocalExecutor, LocalExecutorBuilder, PanicPolicy};
+
+    use super::*;
+
+    struct DropFlag(Rc<Cell<bool>>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    #[test]
+    fn test_join_all_results_in_spawn_order() {
+        LocalExecutor::default().run(async {
+            let group = TaskGroup::<_, ()>::new();
+            for i in 0..5 {
+                group
+                    .spawn(async move {
+                        sleep(Duration::from_millis(10 - 2 * i)).await;
+                        Ok(i)
+                    })
+                    .unwrap();
+            }
+            assert_eq!(group.join_all().await, Ok(vec![0, 1, 2, 3, 4]));
+        })
+    }
+
+    #[test]
+    fn test_first_error_cancels_siblings() {
+        LocalExecutor::default().run(async {
+            let dropped = Rc::new(Cell::new(false));
+            let group = TaskGroup::new();
+            group
+                .spawn(enclose! { (dropped) async move {
+                    let _flag = DropFlag(dropped);
+                    sleep(Duration::from_secs(10)).await;
+                    Ok(())
+                }})
+                .unwrap();
+            group.spawn(async { Err("first") }).unwrap();
+            group
+                .spawn(async {
+                    sleep(Duration::from_millis(1)).await;
+                    Err("second")
+                })
+                .unwrap();
+
+            assert_eq!(group.join_all().await, Err("first"));
+            assert!(dropped.get());
+        })
+    }
+
+    #[test]
+    fn test_spawn_after_error() {
+        LocalExecutor::default().run(async {
+            let group = TaskGroup::<(), _>::new();
+            group.spawn(async { Err(()) }).unwrap();
+            crate::executor().yield_task_queue_now().await;
+            assert!(matches!(
+                group.spawn(async { Ok(()) }),
+                Err(GlommioError::Closed(ResourceType::TaskGroup))
+            ));
+        })
+    }
+
+    #[test]
+    fn test_drop_cancels_children() {
+        LocalExecutor::default().run(async {
+            let dropped = Rc::new(Cell::new(false));
+            let group = TaskGroup::<(), ()>::new();
+            group
+                .spawn(enclose! { (dropped) async move {
+                    let _flag = DropFlag(dropped);
+                    sleep(Duration::from_secs(10)).await;
+                    Ok(())
+                }})
+                .unwrap();
+            crate::executor().yield_task_queue_now().await;
+            drop(group);
+
+            assert!(!dropped.get());
+            crate::executor().yield_task_queue_now().await;
+            assert!(dropped.get());
+        })
+    }
+
+    #[test]
+    fn test_join_all_resumes_child_panic() {
+        let ex = LocalExecutorBuilder::default()
+            .panic_policy(PanicPolicy::Catch)
+            .make()
+            .unwrap();
+        ex.run(async {
+            let finished = Rc::new(Cell::new(false));
+            let joiner = crate::spawn_local(enclose! { (finished) async move {
+                let group = TaskGroup::<_, ()>::new();
+                group.spawn(async { panic!("boom") }).unwrap();
+                group
+                    .spawn(async move {
+                        sleep(Duration::from_millis(1)).await;
+                        finished.set(true);
+                        Ok(())
+                    })
+                    .unwrap();
+                group.join_all().await
+            }});
+
+            let panic = joiner.join().await.unwrap_err();
+            assert_eq!(panic.message(), Some("boom"));
+            // the other children still ran to completion
+            assert!(finished.get());
+        })
+    }
+}
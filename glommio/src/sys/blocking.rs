@@ -1,12 +1,12 @@
 use crate::{
     executor::bind_to_cpu_set,
-    sys::{InnerSource, SleepNotifier},
-    PoolPlacement,
+    sys::{Completions, InnerSource, SleepNotifier},
+    BlockingLaneStats, BlockingPoolStats, PoolPlacement,
 };
 use ahash::AHashMap;
 use alloc::rc::Rc;
 use core::fmt::{Debug, Formatter};
-use flume::{Receiver, Sender};
+use flume::{Receiver, Sender, TrySendError};
 use std::{
     cell::{Cell, RefCell},
     convert::{TryFrom, TryInto},
@@ -18,8 +18,25 @@ use std::{
     pin::Pin,
     sync::Arc,
     thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
+/// What to do with a blocking operation when the queue of the blocking thread
+/// pool is full.
+///
+/// See [`LocalExecutorBuilder::blocking_queue_policy`].
+///
+/// [`LocalExecutorBuilder::blocking_queue_policy`]: crate::LocalExecutorBuilder::blocking_queue_policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockingQueuePolicy {
+    /// The task issuing the operation waits until there is room in the queue.
+    #[default]
+    Wait,
+    /// The operation fails right away with an error of kind
+    /// [`io::ErrorKind::WouldBlock`].
+    Reject,
+}
+
 // So hard to copy/clone io::Error, plus need to send between threads. Best to
 // do all i64.
 macro_rules! raw_syscall {
@@ -84,6 +101,13 @@ impl Debug for BlockingThreadOp {
 }
 
 impl BlockingThreadOp {
+    fn lane(&self) -> Lane {
+        match self {
+            BlockingThreadOp::Fn(_) => Lane::User,
+            _ => Lane::Internal,
+        }
+    }
+
     fn execute(self) -> BlockingThreadResult {
         match self {
             BlockingThreadOp::CreateDir(path, mode) => {
@@ -137,16 +161,31 @@ impl TryFrom<BlockingThreadResult> for std::io::Result<usize> {
     }
 }
 
+/// Which lane of the pool an operation runs in: glommio's own file
+/// operations, or closures passed to [`spawn_blocking`].
+///
+/// [`spawn_blocking`]: crate::ExecutorProxy::spawn_blocking
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Lane {
+    Internal,
+    User,
+}
+
 #[derive(Debug)]
 pub(super) struct BlockingThreadReq {
     op: BlockingThreadOp,
     latency_sensitive: bool,
     id: u64,
+    lane: Lane,
+    enqueued_at: Instant,
 }
 
 pub(super) struct BlockingThreadResp {
     id: u64,
     res: BlockingThreadResult,
+    lane: Lane,
+    waited: Duration,
+    ran: Duration,
 }
 
 #[derive(Debug)]
@@ -164,9 +203,15 @@ impl BlockingThread {
                 bind_to_cpu_set(bindings).expect("failed to bind blocking thread");
             }
             while let Ok(el) = rx.recv() {
+                let started = Instant::now();
                 let res = el.op.execute();
-                let id = el.id;
-                let resp = BlockingThreadResp { id, res };
+                let resp = BlockingThreadResp {
+                    id: el.id,
+                    res,
+                    lane: el.lane,
+                    waited: started.saturating_duration_since(el.enqueued_at),
+                    ran: started.elapsed(),
+                };
 
                 if tx.send(resp).is_err() {
                     panic!("failed to send response");
@@ -177,24 +222,23 @@ impl BlockingThread {
     }
 }
 
+/// A queue and the threads serving it
 #[derive(Debug)]
-pub(crate) struct BlockingThreadPool {
-    tx: Rc<Sender<BlockingThreadReq>>,
-    rx: Receiver<BlockingThreadResp>,
-    sources: RefCell<AHashMap<u64, Pin<Rc<RefCell<InnerSource>>>>>,
-    requests: Cell<u64>,
-    _threads: Vec<BlockingThread>,
+struct BlockingQueue {
+    tx: Sender<BlockingThreadReq>,
+    capacity: usize,
+    threads: Vec<BlockingThread>,
 }
 
-impl BlockingThreadPool {
-    pub(crate) fn new(
+impl BlockingQueue {
+    fn new(
         placement: PoolPlacement,
-        sleep_notifier: Arc<SleepNotifier>,
+        capacity: usize,
+        sleep_notifier: &Arc<SleepNotifier>,
+        out_tx: &Arc<Sender<BlockingThreadResp>>,
     ) -> crate::Result<Self, ()> {
-        let (in_tx, in_rx) = flume::bounded(4 << 10);
-        let (out_tx, out_rx) = flume::bounded(4 << 10);
+        let (in_tx, in_rx) = flume::bounded(capacity);
         let in_rx = Arc::new(in_rx);
-        let out_tx = Arc::new(out_tx);
 
         let thread_count = placement.executor_count();
         let mut placements = placement.generate_cpu_set()?;
@@ -210,57 +254,170 @@ impl BlockingThreadPool {
         }
 
         Ok(Self {
-            _threads: threads,
-            tx: Rc::new(in_tx),
+            tx: in_tx,
+            capacity,
+            threads,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct BlockingThreadPool {
+    user: BlockingQueue,
+    /// Where glommio's own file operations go, if not to the same queue as
+    /// user closures
+    internal: Option<BlockingQueue>,
+    policy: BlockingQueuePolicy,
+    rx: Receiver<BlockingThreadResp>,
+    sources: RefCell<AHashMap<u64, Pin<Rc<RefCell<InnerSource>>>>>,
+    requests: Cell<u64>,
+    stats: RefCell<BlockingPoolStats>,
+}
+
+impl BlockingThreadPool {
+    pub(crate) fn new(
+        placement: PoolPlacement,
+        internal_placement: Option<PoolPlacement>,
+        queue_capacity: usize,
+        policy: BlockingQueuePolicy,
+        sleep_notifier: Arc<SleepNotifier>,
+    ) -> crate::Result<Self, ()> {
+        let (out_tx, out_rx) = flume::bounded(4 << 10);
+        let out_tx = Arc::new(out_tx);
+
+        let user = BlockingQueue::new(placement, queue_capacity, &sleep_notifier, &out_tx)?;
+        let internal = internal_placement
+            .map(|placement| {
+                BlockingQueue::new(placement, queue_capacity, &sleep_notifier, &out_tx)
+            })
+            .transpose()?;
+
+        Ok(Self {
+            user,
+            internal,
+            policy,
             rx: out_rx,
             sources: RefCell::new(Default::default()),
             requests: Cell::new(0),
+            stats: RefCell::new(Default::default()),
         })
     }
 
+    fn queue(&self, lane: Lane) -> &BlockingQueue {
+        match lane {
+            Lane::Internal => self.internal.as_ref().unwrap_or(&self.user),
+            Lane::User => &self.user,
+        }
+    }
+
+    fn lane_stats(stats: &mut BlockingPoolStats, lane: Lane) -> &mut BlockingLaneStats {
+        match lane {
+            Lane::Internal => &mut stats.internal,
+            Lane::User => &mut stats.user,
+        }
+    }
+
+    /// Queues up `op`, to complete `source` once it ran. If `may_reject` and
+    /// the queue is full, `source` is failed right away instead of waiting
+    /// for room, provided that's the policy of the pool.
     pub(super) fn push(
         &self,
         op: BlockingThreadOp,
         source: Pin<Rc<RefCell<InnerSource>>>,
+        may_reject: bool,
     ) -> impl Future<Output = ()> {
         let id = self.requests.get();
         self.requests.set(id.overflowing_add(1).0);
+        let lane = op.lane();
         let req = BlockingThreadReq {
             op,
             id,
+            lane,
+            enqueued_at: Instant::now(),
             latency_sensitive: matches!(
                 source.borrow().io_requirements.latency_req,
                 crate::Latency::Matters(_)
             ),
         };
-        let mut waiters = self.sources.borrow_mut();
-        assert!(waiters.insert(id, source).is_none());
-        let tx = self.tx.clone();
+        let tx = self.queue(lane).tx.clone();
+
+        let mut req = Some(req);
+        let mut rejected = false;
+        if may_reject && self.policy == BlockingQueuePolicy::Reject {
+            match tx.try_send(req.take().unwrap()) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    // the source never makes it to the threads, so it is
+                    // completed here rather than in `flush`
+                    Self::lane_stats(&mut self.stats.borrow_mut(), lane).rejected += 1;
+                    let mut inner_source = source.borrow_mut();
+                    inner_source.wakers.result.replace(Err(io::Error::new(
+                        io::ErrorKind::WouldBlock,
+                        "the queue of the blocking thread pool is full",
+                    )));
+                    inner_source.wakers.wake_waiters();
+                    rejected = true;
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    panic!("failed to enqueue blocking operation")
+                }
+            }
+        }
+        if !rejected {
+            let mut waiters = self.sources.borrow_mut();
+            assert!(waiters.insert(id, source).is_none());
+        }
 
         async move {
-            tx.send_async(req)
-                .await
-                .expect("failed to enqueue blocking operation");
+            if let Some(req) = req {
+                tx.send_async(req)
+                    .await
+                    .expect("failed to enqueue blocking operation");
+            }
         }
     }
 
-    pub(super) fn flush(&self) -> usize {
+    pub(super) fn flush(&self, completions: &Completions) -> usize {
         let mut woke = 0;
         let mut waiters = self.sources.borrow_mut();
+        let mut stats = self.stats.borrow_mut();
         for x in self.rx.try_iter() {
             let id = x.id;
             let res = x.res;
 
+            let lane = Self::lane_stats(&mut stats, x.lane);
+            lane.completed += 1;
+            lane.wait_time_us.add(x.waited.as_micros() as f64);
+            lane.run_time_us.add(x.ran.as_micros() as f64);
+
             let src = waiters.remove(&id).unwrap();
-            let mut inner_source = src.borrow_mut();
-            inner_source.wakers.result.replace(
+            completions.complete(
+                src,
                 res.try_into()
                     .expect("not a valid blocking operation's result"),
             );
-            inner_source.wakers.wake_waiters();
 
             woke += 1;
         }
         woke
     }
+
+    /// Whether operations were pushed to the threads and didn't complete yet.
+    pub(super) fn has_in_flight(&self) -> bool {
+        !self.sources.borrow().is_empty()
+    }
+
+    pub(crate) fn stats(&self) -> BlockingPoolStats {
+        let mut stats = std::mem::take(&mut *self.stats.borrow_mut());
+        for (lane, lane_stats) in [
+            (Lane::Internal, &mut stats.internal),
+            (Lane::User, &mut stats.user),
+        ] {
+            let queue = self.queue(lane);
+            lane_stats.threads = queue.threads.len();
+            lane_stats.queue_depth = queue.tx.len();
+            lane_stats.queue_capacity = queue.capacity;
+        }
+        stats
+    }
 }
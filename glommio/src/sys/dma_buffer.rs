@@ -10,7 +10,7 @@
 
 use std::ptr;
 
-use crate::sys::uring::UringBuffer;
+use crate::sys::{numa, uring::UringBuffer};
 use alloc::alloc::Layout;
 
 #[derive(Debug)]
@@ -146,6 +146,17 @@ impl DmaBuffer {
         self.size == 0
     }
 
+    /// Returns the NUMA node the memory of this `DmaBuffer` is on, or `None`
+    /// if the buffer is empty or the kernel won't tell. The kernel only
+    /// allocates memory once it's used, so this allocates the first page of
+    /// the buffer if it wasn't used yet.
+    pub fn numa_node(&self) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        numa::node_of(self.as_ptr()).ok()
+    }
+
     /// Returns a representation of the current addressable contents of this
     /// `DmaBuffer` as mutable pointer
     pub fn as_mut_ptr(&mut self) -> *mut u8 {
@@ -12,6 +12,7 @@ use std::{
 };
 
 use super::sysfs::ListIterator;
+use crate::to_io_error;
 
 /// A description of the CPU's location in the machine topology.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -130,6 +131,55 @@ pub fn get_machine_topology_unsorted() -> io::Result<Vec<CpuLocation>> {
     Ok(cpu_locations)
 }
 
+/// Request the ids of the CPUs this process is allowed to run on: the CPUs in
+/// its affinity mask that are also in the effective cpuset of its cgroup, if
+/// it belongs to a cgroup v2 hierarchy with the cpuset controller enabled.
+///
+/// The affinity mask is the one of the main thread of the process, rather than
+/// the one of the calling thread, which is only a single CPU when called from
+/// an executor bound to it.
+pub fn get_allowed_cpus() -> io::Result<HashSet<usize>> {
+    let affinity =
+        nix::sched::sched_getaffinity(nix::unistd::getpid()).map_err(|e| to_io_error!(e))?;
+    let mut allowed = (0..nix::sched::CpuSet::count())
+        .filter(|cpu| affinity.is_set(*cpu).unwrap_or(false))
+        .collect::<HashSet<_>>();
+    if let Some(effective) = get_cgroup_effective_cpus()? {
+        allowed.retain(|cpu| effective.contains(cpu));
+    }
+    Ok(allowed)
+}
+
+/// Request the ids of the CPUs in the effective cpuset of the cgroup of this
+/// process, if it belongs to a cgroup v2 hierarchy with the cpuset controller
+/// enabled.
+pub fn get_cgroup_effective_cpus() -> io::Result<Option<HashSet<usize>>> {
+    let cgroups = match std::fs::read_to_string("/proc/self/cgroup") {
+        Ok(x) => x,
+        Err(x) if x.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(x) => return Err(x),
+    };
+    // the cgroup v2 hierarchy is the one with id 0 and no controllers listed
+    let cgroup = match cgroups.lines().find_map(|l| l.strip_prefix("0::")) {
+        Some(cgroup) => cgroup.trim_start_matches('/'),
+        None => return Ok(None),
+    };
+    let path = Path::new("/sys/fs/cgroup")
+        .join(cgroup)
+        .join("cpuset.cpus.effective");
+    let effective = match ListIterator::from_path(&path) {
+        Ok(x) => x.collect::<io::Result<HashSet<_>>>()?,
+        // the cpuset controller is not enabled for this cgroup
+        Err(x) if x.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(x) => return Err(x),
+    };
+    Ok(if effective.is_empty() {
+        None
+    } else {
+        Some(effective)
+    })
+}
+
 fn get_core_id(
     cpu: usize,
     cpu_path: &Path,
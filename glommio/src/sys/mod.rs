@@ -173,6 +173,7 @@ pub(crate) fn sendmsg_syscall(
 
 mod dma_buffer;
 mod membarrier;
+pub(crate) mod numa;
 pub(crate) mod source;
 pub(crate) mod sysfs;
 mod uring;
@@ -254,6 +255,10 @@ impl fmt::Debug for OsError {
     }
 }
 
+/// A closure that spawns a task on the executor owning a [`SleepNotifier`].
+/// It is created on one thread and executed on another.
+pub(crate) type ForeignSpawn = Box<dyn FnOnce() + Send>;
+
 #[derive(Debug)]
 pub(crate) struct SleepNotifier {
     id: usize,
@@ -261,6 +266,10 @@ pub(crate) struct SleepNotifier {
     should_notify: AtomicBool,
     foreign_wakes: crossbeam::channel::Receiver<Waker>,
     waker_sender: crossbeam::channel::Sender<Waker>,
+    foreign_spawns: crossbeam::channel::Receiver<ForeignSpawn>,
+    spawn_sender: crossbeam::channel::Sender<ForeignSpawn>,
+    // set once the executor owning this notifier is gone
+    closed: AtomicBool,
 }
 
 lazy_static! {
@@ -287,6 +296,7 @@ impl SleepNotifier {
     pub(crate) fn new(id: usize) -> io::Result<Arc<Self>> {
         let eventfd = unsafe { std::fs::File::from_raw_fd(create_eventfd()?) };
         let (waker_sender, foreign_wakes) = crossbeam::channel::unbounded();
+        let (spawn_sender, foreign_spawns) = crossbeam::channel::unbounded();
 
         Ok(Arc::new(Self {
             eventfd,
@@ -294,6 +304,9 @@ impl SleepNotifier {
             should_notify: AtomicBool::new(false),
             waker_sender,
             foreign_wakes,
+            spawn_sender,
+            foreign_spawns,
+            closed: AtomicBool::new(false),
         }))
     }
 
@@ -305,7 +318,9 @@ impl SleepNotifier {
         self.id
     }
 
-    pub(crate) fn notify(&self, force: bool) {
+    /// Wakes up the executor if it is sleeping, or unconditionally if `force`
+    /// is set. Returns whether the executor was notified.
+    pub(crate) fn notify(&self, force: bool) -> bool {
         if self
             .should_notify
             .compare_exchange(true, false, Ordering::Relaxed, Ordering::Relaxed)
@@ -313,6 +328,9 @@ impl SleepNotifier {
             || force
         {
             write_eventfd(self.eventfd_fd());
+            true
+        } else {
+            false
         }
     }
 
@@ -339,6 +357,70 @@ impl SleepNotifier {
         processed
     }
 
+    /// Queues a spawn request for the executor owning this notifier. Returns
+    /// whether the request was accepted: once the executor is gone, it is
+    /// dropped instead, failing its handle.
+    pub(crate) fn queue_spawn(&self, spawn: ForeignSpawn) -> bool {
+        if self.closed.load(Ordering::Acquire) {
+            return false;
+        }
+
+        // The receiving end lives as long as this notifier does, so this can't
+        // fail.
+        if self.spawn_sender.send(spawn).is_err() {
+            debug!(
+                "Executor {} cannot send the spawn request to its destination!",
+                self.id()
+            );
+        }
+
+        // The executor may have shut down between the check above and the
+        // send, in which case nobody will ever drain the request we just
+        // queued. Drop it here, so its owner finds out.
+        if self.closed.load(Ordering::Acquire) {
+            self.drop_foreign_spawns();
+            return false;
+        }
+
+        self.notify(false);
+        true
+    }
+
+    pub(crate) fn process_foreign_spawns(&self) -> usize {
+        let mut processed = 0;
+        while let Ok(spawn) = self.foreign_spawns.try_recv() {
+            processed += 1;
+            spawn();
+        }
+        processed
+    }
+
+    fn drop_foreign_spawns(&self) {
+        while let Ok(spawn) = self.foreign_spawns.try_recv() {
+            drop(spawn);
+        }
+    }
+
+    /// Called when the executor owning this notifier goes away. Other threads
+    /// may still hold references to the notifier, so it is deregistered here
+    /// rather than when it is dropped, and spawn requests that will never be
+    /// processed are failed.
+    pub(crate) fn shut_down(&self) {
+        self.closed.store(true, Ordering::Release);
+        REACTOR_GLOBAL_STATE
+            .write()
+            .unwrap()
+            .sleep_notifiers
+            .remove(&self.id);
+        self.drop_foreign_spawns();
+    }
+
+    /// Whether wakers or spawn requests from other threads are waiting to
+    /// be processed by the executor.
+    pub(crate) fn has_foreign_work(&self) -> bool {
+        !self.foreign_wakes.is_empty() || !self.foreign_spawns.is_empty()
+    }
+
     pub(super) fn prepare_to_sleep(&self) {
         // This will allow this `eventfd` to be notified. This should not happen
         // for the placeholder (disconnected) case.
@@ -358,7 +440,8 @@ impl Drop for SleepNotifier {
         // will be freed later. However, we can't receive notifications anymore
         // so memory must be zeroed here.
         self.wake_up();
-        state.sleep_notifiers.remove(&self.id).unwrap();
+        // already gone if the executor shut down
+        state.sleep_notifiers.remove(&self.id);
     }
 }
 
@@ -0,0 +1,296 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
+//
+// Memory policies, to keep the memory of an executor on its NUMA node.
+// For additional information see:
+// https://www.kernel.org/doc/html/latest/admin-guide/mm/numa_memory_policy.html
+
+use log::{debug, warn};
+use std::{
+    collections::BTreeMap,
+    io,
+    marker::PhantomData,
+    ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// `libc` doesn't expose the flags of `get_mempolicy`, see
+// https://github.com/torvalds/linux/blob/master/include/uapi/linux/mempolicy.h
+const MPOL_F_NODE: libc::c_ulong = 1 << 0;
+const MPOL_F_ADDR: libc::c_ulong = 1 << 1;
+
+const PAGE_SIZE: usize = 4096;
+const HUGE_PAGE_SIZE: usize = 2 << 20;
+
+// The largest number of nodes the kernel supports, with `CONFIG_NODES_SHIFT`
+// at its maximum
+const MAX_NODES: usize = 1 << 10;
+
+// How many pages `nodes_of` looks up at most
+const MAX_SAMPLED_PAGES: usize = 256;
+
+// The kernel ignores the last bit of the node masks it's passed, so the
+// number of bits we pass is one more than the number of bits in the mask
+fn node_mask(node: usize) -> (Vec<libc::c_ulong>, libc::c_ulong) {
+    let bits = libc::c_ulong::BITS as usize;
+    let mut mask = vec![0; node / bits + 1];
+    mask[node / bits] |= 1 << (node % bits);
+    let max_node = (mask.len() * bits + 1) as libc::c_ulong;
+    (mask, max_node)
+}
+
+/// Logs that memory can't be allocated on `node`, once per process: this is
+/// usually because the process is not allowed to change memory policies, which
+/// holds for all its executors alike.
+fn report_unavailable(node: usize, err: io::Error) {
+    static REPORTED: AtomicBool = AtomicBool::new(false);
+    if !REPORTED.swap(true, Ordering::Relaxed) {
+        debug!("Unable to allocate memory on NUMA node {node}: {err}");
+    }
+}
+
+fn set_mempolicy(
+    mode: libc::c_int,
+    mask: &[libc::c_ulong],
+    max_node: libc::c_ulong,
+) -> io::Result<()> {
+    let res = unsafe { libc::syscall(libc::SYS_set_mempolicy, mode, mask.as_ptr(), max_node) };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The memory policy of the calling thread before it was set to prefer a
+/// node, restored when dropped.
+#[derive(Debug)]
+pub(crate) struct PreferredNode {
+    mode: libc::c_int,
+    mask: Vec<libc::c_ulong>,
+    // the policy belongs to the thread that set it
+    _marker: PhantomData<*const ()>,
+}
+
+impl PreferredNode {
+    /// Makes the kernel allocate the memory of the calling thread on `node`,
+    /// as long as the node has free memory, until the returned value is
+    /// dropped.
+    pub(crate) fn set(node: usize) -> Option<Self> {
+        let prev = Self::current().and_then(|prev| {
+            let (mask, max_node) = node_mask(node);
+            set_mempolicy(libc::MPOL_PREFERRED, &mask, max_node)?;
+            Ok(prev)
+        });
+        prev.map_err(|x| report_unavailable(node, x)).ok()
+    }
+
+    fn current() -> io::Result<Self> {
+        let mut mode: libc::c_int = 0;
+        let mut mask = vec![0 as libc::c_ulong; MAX_NODES / libc::c_ulong::BITS as usize];
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_get_mempolicy,
+                &mut mode as *mut libc::c_int,
+                mask.as_mut_ptr(),
+                MAX_NODES as libc::c_ulong,
+                ptr::null::<u8>(),
+                0 as libc::c_ulong,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            mode,
+            mask,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl Drop for PreferredNode {
+    fn drop(&mut self) {
+        if let Err(x) = set_mempolicy(self.mode, &self.mask, MAX_NODES as libc::c_ulong + 1) {
+            warn!("Unable to restore the memory policy of the thread: {x}");
+        }
+    }
+}
+
+/// Makes the kernel allocate the pages of `len` bytes at `addr` on `node`, as
+/// long as the node has free memory. Only pages that are not allocated yet are
+/// affected.
+fn set_preferred_node_of(addr: *mut u8, len: usize, node: usize) -> io::Result<()> {
+    let (mask, max_node) = node_mask(node);
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            addr,
+            len,
+            libc::MPOL_PREFERRED,
+            mask.as_ptr(),
+            max_node,
+            0,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns the node of the page at `addr`, allocating the page if it's not
+/// allocated yet
+pub(crate) fn node_of(addr: *const u8) -> io::Result<usize> {
+    let mut node: libc::c_int = 0;
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_get_mempolicy,
+            &mut node as *mut libc::c_int,
+            ptr::null_mut::<libc::c_ulong>(),
+            0,
+            addr,
+            MPOL_F_NODE | MPOL_F_ADDR,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(node as usize)
+}
+
+/// Estimates how many of the `len` bytes at `addr` are on each node, from the
+/// node of up to `MAX_SAMPLED_PAGES` pages evenly spread over them. Pages that
+/// are not allocated yet are not accounted for.
+pub(crate) fn nodes_of(addr: *const u8, len: usize) -> io::Result<BTreeMap<usize, usize>> {
+    let stride =
+        align_up((len + MAX_SAMPLED_PAGES - 1) / MAX_SAMPLED_PAGES, PAGE_SIZE).max(PAGE_SIZE);
+    let offsets = (0..len).step_by(stride).collect::<Vec<_>>();
+    let pages = offsets
+        .iter()
+        .map(|offset| unsafe { addr.add(*offset) })
+        .collect::<Vec<_>>();
+    let mut status = vec![0 as libc::c_int; pages.len()];
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_move_pages,
+            0,
+            pages.len(),
+            pages.as_ptr(),
+            ptr::null::<libc::c_int>(),
+            status.as_mut_ptr(),
+            0,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut nodes = BTreeMap::new();
+    // each page stands for the bytes up to the next one sampled. Pages that
+    // are not allocated have a negative status
+    for (offset, node) in offsets.into_iter().zip(status) {
+        if node >= 0 {
+            *nodes.entry(node as usize).or_default() += stride.min(len - offset);
+        }
+    }
+    Ok(nodes)
+}
+
+/// Memory mapped for the exclusive use of an executor, possibly backed by huge
+/// pages and allocated on the NUMA node of the executor
+#[derive(Debug)]
+pub(crate) struct LocalMemory {
+    data: ptr::NonNull<u8>,
+    size: usize,
+    huge_pages: bool,
+    numa_node: Option<usize>,
+}
+
+impl LocalMemory {
+    /// Maps at least `size` bytes. If `huge_pages` is set, the memory is
+    /// backed by huge pages if the kernel has some reserved, and uses
+    /// transparent huge pages otherwise.
+    pub(crate) fn new(size: usize, numa_node: Option<usize>, huge_pages: bool) -> io::Result<Self> {
+        let mut mapped = None;
+        if huge_pages {
+            let size = align_up(size, HUGE_PAGE_SIZE);
+            match map(size, libc::MAP_HUGETLB) {
+                Ok(data) => mapped = Some((data, size, true)),
+                Err(x) => {
+                    warn!("Unable to map {size} bytes of huge pages, using regular pages: {x}")
+                }
+            }
+        }
+        let (data, size, huge_pages) = match mapped {
+            Some(mapped) => mapped,
+            None => {
+                let size = align_up(size, PAGE_SIZE);
+                let data = map(size, 0)?;
+                if huge_pages {
+                    // best effort: transparent huge pages may be disabled
+                    unsafe { libc::madvise(data.as_ptr().cast(), size, libc::MADV_HUGEPAGE) };
+                }
+                (data, size, false)
+            }
+        };
+        // nothing touched the memory yet, so none of its pages are allocated
+        let numa_node = numa_node.filter(|node| {
+            set_preferred_node_of(data.as_ptr(), size, *node)
+                .map_err(|x| report_unavailable(*node, x))
+                .is_ok()
+        });
+        Ok(Self {
+            data,
+            size,
+            huge_pages,
+            numa_node,
+        })
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut u8 {
+        self.data.as_ptr()
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    pub(crate) fn huge_pages(&self) -> bool {
+        self.huge_pages
+    }
+
+    /// The node the memory is allocated on if the kernel can, if any
+    pub(crate) fn numa_node(&self) -> Option<usize> {
+        self.numa_node
+    }
+}
+
+impl Drop for LocalMemory {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.data.as_ptr().cast(), self.size);
+        }
+    }
+}
+
+fn map(size: usize, flags: libc::c_int) -> io::Result<ptr::NonNull<u8>> {
+    let data = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+            -1,
+            0,
+        )
+    };
+    if data == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ptr::NonNull::new(data.cast()).unwrap())
+}
+
+fn align_up(v: usize, align: usize) -> usize {
+    (v + align - 1) & !(align - 1)
+}
@@ -130,12 +130,40 @@ pub(crate) struct InnerSource {
     pub(crate) stats_collection: Option<StatsCollection>,
 
     pub(crate) task_queue: Option<TaskQueueHandle>,
+
+    /// The order in which the source was created by the reactor, which
+    /// orders the completions held back in simulation.
+    pub(crate) serial: u64,
 }
 
 impl InnerSource {
     pub(crate) fn update_source_type(&mut self, source_type: SourceType) -> SourceType {
         std::mem::replace(&mut self.source_type, source_type)
     }
+
+    /// Whether the reactor uses the source for itself, rather than on behalf
+    /// of a task.
+    pub(crate) fn is_internal(&self) -> bool {
+        matches!(
+            self.source_type,
+            SourceType::LinkRings | SourceType::ForeignNotifier(..) | SourceType::Timeout(..)
+        )
+    }
+
+    /// Whether the source waits on something outside the process, like a
+    /// peer on the network, so there's no telling when it completes, if ever.
+    pub(crate) fn is_unbounded(&self) -> bool {
+        matches!(
+            self.source_type,
+            SourceType::PollAdd
+                | SourceType::SockSend(_)
+                | SourceType::SockRecv(_)
+                | SourceType::SockRecvMsg(..)
+                | SourceType::SockSendMsg(..)
+                | SourceType::Connect(_)
+                | SourceType::Accept(_)
+        )
+    }
 }
 
 impl fmt::Debug for InnerSource {
@@ -173,6 +201,7 @@ impl Source {
                 timeout: None,
                 stats_collection,
                 task_queue,
+                serial: 0,
             })),
         }
     }
@@ -342,3 +371,89 @@ impl Drop for Source {
         }
     }
 }
+
+/// Hands the results of the sources the reactor fulfilled to the tasks waiting
+/// on them.
+///
+/// In simulation, the results of I/O are held back instead, until the
+/// executor releases them. When I/O completes then doesn't matter, only the
+/// order in which the executor releases it, which depends on the seed.
+#[derive(Clone, Default)]
+pub(crate) struct Completions {
+    held: Option<Rc<RefCell<Vec<HeldCompletion>>>>,
+}
+
+struct HeldCompletion {
+    source: Pin<Rc<RefCell<InnerSource>>>,
+    result: io::Result<usize>,
+}
+
+impl Completions {
+    pub(crate) fn new(hold: bool) -> Self {
+        Self {
+            held: hold.then(Default::default),
+        }
+    }
+
+    /// Completes `source` with `result`. Returns whether the reactor has
+    /// something to process as a result: a task was woken up, or the result
+    /// was held back.
+    pub(crate) fn complete(
+        &self,
+        source: Pin<Rc<RefCell<InnerSource>>>,
+        result: io::Result<usize>,
+    ) -> bool {
+        match &self.held {
+            // sources the reactor uses for itself are never held back
+            Some(held) if !source.borrow().is_internal() => {
+                held.borrow_mut().push(HeldCompletion { source, result });
+                true
+            }
+            _ => {
+                let mut inner = source.borrow_mut();
+                inner.wakers.result = Some(result);
+                inner.wakers.wake_waiters()
+            }
+        }
+    }
+
+    /// Returns the number of completions held back.
+    pub(crate) fn nr_held(&self) -> usize {
+        self.held.as_ref().map_or(0, |held| held.borrow().len())
+    }
+
+    /// Releases `count` of the completions held back, one at a time, each
+    /// picked by `pick` among the ones that remain, given how many there are.
+    /// The candidates are ordered by the creation of their source, so only
+    /// `pick` decides which ones are released.
+    pub(crate) fn release(&self, count: usize, mut pick: impl FnMut(usize) -> usize) -> usize {
+        let held = match &self.held {
+            Some(held) => held,
+            None => return 0,
+        };
+        let mut released = Vec::with_capacity(count);
+        {
+            let mut held = held.borrow_mut();
+            held.sort_by_key(|completion| completion.source.borrow().serial);
+            while released.len() < count && !held.is_empty() {
+                let idx = pick(held.len());
+                released.push(held.remove(idx));
+            }
+        }
+        let nr_released = released.len();
+        for HeldCompletion { source, result } in released {
+            let mut inner = source.borrow_mut();
+            inner.wakers.result = Some(result);
+            inner.wakers.wake_waiters();
+        }
+        nr_released
+    }
+}
+
+impl fmt::Debug for Completions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Completions")
+            .field("held", &self.held.as_ref().map(|held| held.borrow().len()))
+            .finish()
+    }
+}
@@ -6,6 +6,7 @@
 use ahash::AHashMap;
 use std::{
     cell::RefCell,
+    collections::HashSet,
     fs::{canonicalize, read_dir, read_to_string},
     io,
     marker::PhantomData,
@@ -189,6 +190,28 @@ impl BlockDevice {
     }
 }
 
+/// Reads the CPUs isolated from general scheduling, either with the
+/// `isolcpus` kernel parameter or by running without the scheduling-clock tick
+/// with `nohz_full`. Kernels that support neither have no isolated CPUs.
+pub(crate) fn isolated_cpus() -> io::Result<HashSet<usize>> {
+    let mut cpus = HashSet::new();
+    for name in ["isolated", "nohz_full"] {
+        let list = match read_to_string(Path::new("/sys/devices/system/cpu").join(name)) {
+            Ok(x) => x,
+            Err(x) if x.kind() == io::ErrorKind::NotFound => continue,
+            Err(x) => return Err(x),
+        };
+        // `nohz_full` reads `(null)` if the kernel parameter is not set
+        if list.trim() == "(null)" {
+            continue;
+        }
+        for cpu in ListIterator::from_str(list)? {
+            cpus.insert(cpu?);
+        }
+    }
+    Ok(cpus)
+}
+
 /// Implements an [`Iterator`] over lists of unsigned values commonly
 /// encountered in `sysfs`.  A list with the format "0,1,4-6" would iterate over
 /// values 0,1,4,5,6.  The resulting [`Iterator::Item`] is an
@@ -3,7 +3,6 @@
 //
 // This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
 //
-use alloc::alloc::Layout;
 use log::warn;
 use nix::{
     fcntl::{FallocateFlags, OFlag},
@@ -36,11 +35,14 @@ use crate::{
         self,
         blocking::{BlockingThreadOp, BlockingThreadPool},
         dma_buffer::{BufferStorage, DmaBuffer},
-        membarrier, DirectIo, EnqueuedSource, EnqueuedStatus, InnerSource, IoBuffer,
+        membarrier,
+        numa::{self, LocalMemory},
+        Completions, DirectIo, EnqueuedSource, EnqueuedStatus, InnerSource, IoBuffer,
         PollableStatus, Source, SourceType, Statx, TimeSpec64,
     },
     uring_sys::{self, IoRingOp},
-    GlommioError, IoRequirements, IoStats, ReactorErrorKind, RingIoStats, TaskQueueHandle,
+    BlockingPoolStats, GlommioError, IoMemoryStats, IoRequirements, IoStats, ReactorErrorKind,
+    RingIoStats, TaskQueueHandle,
 };
 use ahash::AHashMap;
 use buddy_alloc::buddy_alloc::{BuddyAlloc, BuddyAllocParam};
@@ -88,41 +90,42 @@ pub(crate) struct UringDescriptor {
 }
 
 pub(crate) struct UringBufferAllocator {
-    data: ptr::NonNull<u8>,
-    size: usize,
+    // the allocator keeps its own bookkeeping in the memory, so it must go
+    // away first
     allocator: RefCell<BuddyAlloc>,
-    layout: Layout,
+    memory: LocalMemory,
     uring_buffer_id: Cell<Option<u32>>,
 }
 
 impl fmt::Debug for UringBufferAllocator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("UringBufferAllocator")
-            .field("data", &self.data)
+            .field("memory", &self.memory)
             .finish()
     }
 }
 
 impl UringBufferAllocator {
-    fn new(size: usize) -> Self {
-        let layout = Layout::from_size_align(size, 4096).unwrap();
-        let (data, allocator) = unsafe {
-            let data = alloc::alloc::alloc(layout);
-            let data = std::ptr::NonNull::new(data).unwrap();
-            let allocator = BuddyAlloc::new(BuddyAllocParam::new(
-                data.as_ptr(),
-                layout.size(),
-                layout.align(),
-            ));
-            (data, RefCell::new(allocator))
-        };
-
-        UringBufferAllocator {
-            data,
-            size,
-            allocator,
-            layout,
+    fn new(size: usize, numa_node: Option<usize>, huge_pages: bool) -> io::Result<Self> {
+        let memory = LocalMemory::new(size, numa_node, huge_pages)?;
+        let allocator =
+            unsafe { BuddyAlloc::new(BuddyAllocParam::new(memory.as_ptr(), memory.size(), 4096)) };
+
+        Ok(UringBufferAllocator {
+            allocator: RefCell::new(allocator),
+            memory,
             uring_buffer_id: Cell::new(None),
+        })
+    }
+
+    fn stats(&self) -> IoMemoryStats {
+        IoMemoryStats {
+            size: self.memory.size(),
+            huge_pages: self.memory.huge_pages(),
+            numa_node: self.memory.numa_node(),
+            registered: self.uring_buffer_id.get().is_some(),
+            bytes_per_node: numa::nodes_of(self.memory.as_ptr(), self.memory.size())
+                .unwrap_or_default(),
         }
     }
 
@@ -136,7 +139,7 @@ impl UringBufferAllocator {
     }
 
     fn as_bytes(&self) -> &[u8] {
-        unsafe { std::slice::from_raw_parts(self.data.as_ptr(), self.size) }
+        unsafe { std::slice::from_raw_parts(self.memory.as_ptr(), self.memory.size()) }
     }
 
     fn new_buffer(self: &Rc<Self>, size: usize) -> Option<DmaBuffer> {
@@ -155,14 +158,6 @@ impl UringBufferAllocator {
     }
 }
 
-impl Drop for UringBufferAllocator {
-    fn drop(&mut self) {
-        unsafe {
-            alloc::alloc::dealloc(self.data.as_ptr(), self.layout);
-        }
-    }
-}
-
 pub(crate) struct UringBuffer {
     allocator: Rc<UringBufferAllocator>,
     data: ptr::NonNull<u8>,
@@ -548,6 +543,7 @@ fn process_one_event<F, R>(
     try_process: F,
     post_process: R,
     source_map: Rc<RefCell<SourceMap>>,
+    completions: &Completions,
 ) -> Option<bool>
 where
     F: FnOnce(Ref<'_, InnerSource>) -> Option<()>,
@@ -567,10 +563,8 @@ where
 
         let mut woke = false;
         if try_process(src.borrow()).is_none() {
-            let res = Some(post_process(src.borrow_mut(), transmute_error(result)));
-            let mut inner_source = src.borrow_mut();
-            inner_source.wakers.result = res;
-            woke = inner_source.wakers.wake_waiters();
+            let res = post_process(src.borrow_mut(), transmute_error(result));
+            woke = completions.complete(src, res);
         }
         return Some(woke);
     }
@@ -766,6 +760,7 @@ struct PollRing {
     stats: RingIoStats,
     task_queue_stats: AHashMap<TaskQueueHandle, RingIoStats>,
     source_map: Rc<RefCell<SourceMap>>,
+    completions: Completions,
     in_kernel: usize,
 }
 
@@ -774,6 +769,7 @@ impl PollRing {
         size: usize,
         allocator: Rc<UringBufferAllocator>,
         source_map: Rc<RefCell<SourceMap>>,
+        completions: Completions,
     ) -> io::Result<Self> {
         let ring = iou::IoUring::new_with_flags(
             size as _,
@@ -788,6 +784,7 @@ impl PollRing {
             stats: RingIoStats::default(),
             task_queue_stats: AHashMap::new(),
             source_map,
+            completions,
             in_kernel: 0,
         })
     }
@@ -849,6 +846,7 @@ impl UringCommon for PollRing {
 
     fn consume_one_event(&mut self) -> Option<bool> {
         let source_map = self.source_map.clone();
+        let completions = self.completions.clone();
         process_one_event(
             self.ring.peek_for_cqe(),
             |_| None,
@@ -857,6 +855,7 @@ impl UringCommon for PollRing {
                 res
             },
             source_map,
+            &completions,
         )
         .map(|x| {
             self.in_kernel -= 1;
@@ -903,6 +902,7 @@ struct SleepableRing {
     stats: RingIoStats,
     task_queue_stats: AHashMap<TaskQueueHandle, RingIoStats>,
     source_map: Rc<RefCell<SourceMap>>,
+    completions: Completions,
     in_kernel: usize,
 }
 
@@ -912,6 +912,7 @@ impl SleepableRing {
         name: &'static str,
         allocator: Rc<UringBufferAllocator>,
         source_map: Rc<RefCell<SourceMap>>,
+        completions: Completions,
     ) -> io::Result<Self> {
         assert!(*IO_URING_RECENT_ENOUGH);
         Ok(SleepableRing {
@@ -923,6 +924,7 @@ impl SleepableRing {
             stats: RingIoStats::default(),
             task_queue_stats: AHashMap::new(),
             source_map,
+            completions,
             in_kernel: 0,
         })
     }
@@ -1175,6 +1177,7 @@ impl UringCommon for SleepableRing {
 
     fn consume_one_event(&mut self) -> Option<bool> {
         let source_map = self.source_map.clone();
+        let completions = self.completions.clone();
         process_one_event(
             self.ring.peek_for_cqe(),
             |source| match source.source_type {
@@ -1189,6 +1192,7 @@ impl UringCommon for SleepableRing {
                 res
             },
             source_map,
+            &completions,
         )
         .map(|x| {
             self.in_kernel -= 1;
@@ -1245,6 +1249,7 @@ pub(crate) struct Reactor {
     // completed if this reactor is woken up from another one
     eventfd_src: Source,
     source_map: Rc<RefCell<SourceMap>>,
+    completions: Completions,
 
     blocking_thread: BlockingThreadPool,
 
@@ -1294,13 +1299,34 @@ fn align_up(v: usize, align: usize) -> usize {
     (v + align - 1) & !(align - 1)
 }
 
+/// How the rings of a [`Reactor`] and the memory they share are set up.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReactorConfig {
+    /// The size of the arena of registered buffers.
+    pub(crate) io_memory: usize,
+    /// Whether to back the arena with huge pages.
+    pub(crate) io_memory_huge_pages: bool,
+    /// The NUMA node to allocate the arena on, if any.
+    pub(crate) numa_node: Option<usize>,
+    pub(crate) ring_depth: usize,
+    /// Whether to hold back the results of I/O until the executor releases
+    /// them, as it does in simulation.
+    pub(crate) hold_completions: bool,
+}
+
 impl Reactor {
     pub(crate) fn new(
         notifier: Arc<sys::SleepNotifier>,
-        mut io_memory: usize,
-        ring_depth: usize,
+        config: ReactorConfig,
         blocking_thread: BlockingThreadPool,
     ) -> crate::Result<Reactor, ()> {
+        let ReactorConfig {
+            mut io_memory,
+            io_memory_huge_pages,
+            numa_node,
+            ring_depth,
+            hold_completions,
+        } = config;
         const MIN_MEMLOCK_LIMIT: u64 = 512 * 1024;
         let (memlock_limit, _) = Resource::MEMLOCK.get()?;
         if memlock_limit < MIN_MEMLOCK_LIMIT {
@@ -1311,17 +1337,37 @@ impl Reactor {
         }
 
         let source_map = Rc::new(RefCell::new(SourceMap::default()));
+        let completions = Completions::new(hold_completions);
         // always have at least some small amount of memory for the slab
         io_memory = std::cmp::max(align_up(io_memory, 4096), 65536);
 
-        let allocator = Rc::new(UringBufferAllocator::new(io_memory));
+        let allocator = Rc::new(UringBufferAllocator::new(
+            io_memory,
+            numa_node,
+            io_memory_huge_pages,
+        )?);
         let registry = vec![allocator.as_bytes()];
 
-        let main_ring =
-            SleepableRing::new(ring_depth, "main", allocator.clone(), source_map.clone())?;
-        let poll_ring = PollRing::new(ring_depth, allocator.clone(), source_map.clone())?;
-        let mut latency_ring =
-            SleepableRing::new(ring_depth, "latency", allocator.clone(), source_map.clone())?;
+        let main_ring = SleepableRing::new(
+            ring_depth,
+            "main",
+            allocator.clone(),
+            source_map.clone(),
+            completions.clone(),
+        )?;
+        let poll_ring = PollRing::new(
+            ring_depth,
+            allocator.clone(),
+            source_map.clone(),
+            completions.clone(),
+        )?;
+        let mut latency_ring = SleepableRing::new(
+            ring_depth,
+            "latency",
+            allocator.clone(),
+            source_map.clone(),
+            completions.clone(),
+        )?;
 
         match main_ring.registrar().register_buffers_by_ref(&registry) {
             Err(x) => warn!("Error: registering buffers in the main ring. Skipping{x:#?}"),
@@ -1370,6 +1416,7 @@ impl Reactor {
             notifier,
             eventfd_src,
             source_map,
+            completions,
             rings_depth: ring_depth,
         })
     }
@@ -1394,6 +1441,10 @@ impl Reactor {
         self.notifier.process_foreign_wakes()
     }
 
+    pub(crate) fn process_foreign_spawns(&self) -> usize {
+        self.notifier.process_foreign_spawns()
+    }
+
     pub(crate) fn alloc_dma_buffer(&self, size: usize) -> DmaBuffer {
         let mut poll_ring = self.poll_ring.borrow_mut();
         poll_ring.alloc_dma_buffer(size)
@@ -1572,13 +1623,14 @@ impl Reactor {
         &self,
         source: Pin<Rc<RefCell<InnerSource>>>,
         op: BlockingThreadOp,
+        may_reject: bool,
     ) -> impl Future<Output = ()> {
-        self.blocking_thread.push(op, source)
+        self.blocking_thread.push(op, source, may_reject)
     }
 
     pub(crate) fn truncate(&self, source: &Source, size: u64) -> impl Future<Output = ()> {
         let op = BlockingThreadOp::Truncate(source.raw(), size as _);
-        self.enqueue_blocking_request(source.inner.clone(), op)
+        self.enqueue_blocking_request(source.inner.clone(), op, true)
     }
 
     pub(crate) fn rename(&self, source: &Source) -> impl Future<Output = ()> {
@@ -1588,7 +1640,7 @@ impl Reactor {
         };
 
         let op = BlockingThreadOp::Rename(old_path, new_path);
-        self.enqueue_blocking_request(source.inner.clone(), op)
+        self.enqueue_blocking_request(source.inner.clone(), op, true)
     }
 
     pub(crate) fn copy_file_range(&self, source: &Source, pos: u64) -> impl Future<Output = ()> {
@@ -1604,7 +1656,7 @@ impl Reactor {
             pos.try_into().unwrap(),
             len,
         );
-        self.enqueue_blocking_request(source.inner.clone(), op)
+        self.enqueue_blocking_request(source.inner.clone(), op, true)
     }
 
     pub(crate) fn create_dir(
@@ -1618,7 +1670,7 @@ impl Reactor {
         };
 
         let op = BlockingThreadOp::CreateDir(path, mode);
-        self.enqueue_blocking_request(source.inner.clone(), op)
+        self.enqueue_blocking_request(source.inner.clone(), op, true)
     }
 
     pub(crate) fn remove_file(&self, source: &Source) -> impl Future<Output = ()> {
@@ -1628,18 +1680,19 @@ impl Reactor {
         };
 
         let op = BlockingThreadOp::Remove(path);
-        self.enqueue_blocking_request(source.inner.clone(), op)
+        self.enqueue_blocking_request(source.inner.clone(), op, true)
     }
 
     pub(crate) fn run_blocking(
         &self,
         source: &Source,
         f: Box<dyn FnOnce() + Send + 'static>,
+        may_reject: bool,
     ) -> impl Future<Output = ()> {
         assert!(matches!(&*source.source_type(), SourceType::BlockingFn));
 
         let op = BlockingThreadOp::Fn(f);
-        self.enqueue_blocking_request(source.inner.clone(), op)
+        self.enqueue_blocking_request(source.inner.clone(), op, may_reject)
     }
 
     pub(crate) fn close(&self, source: &Source) {
@@ -1907,7 +1960,33 @@ impl Reactor {
     }
 
     pub(crate) fn flush_syscall_thread(&self) -> usize {
-        self.blocking_thread.flush()
+        self.blocking_thread.flush(&self.completions)
+    }
+
+    /// Whether I/O that is bound to complete was submitted and didn't complete
+    /// yet. I/O that waits on the network doesn't count, as it may never
+    /// complete.
+    pub(crate) fn has_bounded_io_in_flight(&self) -> bool {
+        self.blocking_thread.has_in_flight()
+            || self.source_map.borrow().iter().any(|source| {
+                let source = source.borrow();
+                !source.is_internal() && !source.is_unbounded()
+            })
+    }
+
+    /// Returns the number of I/O completions held back, in simulation.
+    pub(crate) fn nr_held_completions(&self) -> usize {
+        self.completions.nr_held()
+    }
+
+    /// Releases `count` of the I/O completions held back in simulation. See
+    /// [`Completions::release`].
+    pub(crate) fn release_completions(
+        &self,
+        count: usize,
+        pick: impl FnMut(usize) -> usize,
+    ) -> usize {
+        self.completions.release(count, pick)
     }
 
     pub(crate) fn preempt_pointers(&self) -> (*const u32, *const u32) {
@@ -1963,32 +2042,62 @@ impl Reactor {
     }
 
     pub fn io_stats(&self) -> IoStats {
+        self.read_io_stats(std::mem::take)
+    }
+
+    /// Like [`Self::io_stats`], without resetting the stats.
+    pub(crate) fn peek_io_stats(&self) -> IoStats {
+        self.read_io_stats(|stats| stats.clone())
+    }
+
+    fn read_io_stats(&self, read: fn(&mut RingIoStats) -> RingIoStats) -> IoStats {
         IoStats::new(
-            std::mem::take(&mut self.main_ring.borrow_mut().stats),
-            std::mem::take(&mut self.latency_ring.borrow_mut().stats),
-            std::mem::take(&mut self.poll_ring.borrow_mut().stats),
+            read(&mut self.main_ring.borrow_mut().stats),
+            read(&mut self.latency_ring.borrow_mut().stats),
+            read(&mut self.poll_ring.borrow_mut().stats),
         )
     }
 
+    pub(crate) fn blocking_pool_stats(&self) -> BlockingPoolStats {
+        self.blocking_thread.stats()
+    }
+
+    pub(crate) fn io_memory_stats(&self) -> IoMemoryStats {
+        self.poll_ring.borrow().allocator.stats()
+    }
+
     pub(crate) fn task_queue_io_stats(&self, h: &TaskQueueHandle) -> Option<IoStats> {
+        self.read_task_queue_io_stats(h, std::mem::take)
+    }
+
+    /// Like [`Self::task_queue_io_stats`], without resetting the stats.
+    pub(crate) fn peek_task_queue_io_stats(&self, h: &TaskQueueHandle) -> Option<IoStats> {
+        self.read_task_queue_io_stats(h, |stats| stats.clone())
+    }
+
+    fn read_task_queue_io_stats(
+        &self,
+        h: &TaskQueueHandle,
+        read: fn(&mut RingIoStats) -> RingIoStats,
+    ) -> Option<IoStats> {
         let main = self
             .main_ring
             .borrow_mut()
             .task_queue_stats
             .get_mut(h)
-            .map(std::mem::take);
+            .map(read);
         let lat = self
             .latency_ring
             .borrow_mut()
             .task_queue_stats
             .get_mut(h)
-            .map(std::mem::take);
+            .map(read);
         let poll = self
             .poll_ring
             .borrow_mut()
             .task_queue_stats
             .get_mut(h)
-            .map(std::mem::take);
+            .map(read);
 
         if let (None, None, None) = (&main, &lat, &poll) {
             None
@@ -2036,12 +2145,15 @@ fn queue_request_into_ring(
 }
 
 impl Drop for Reactor {
-    fn drop(&mut self) {}
+    fn drop(&mut self) {
+        self.notifier.shut_down();
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::PoolPlacement;
+    use crate::{BlockingQueuePolicy, PoolPlacement};
+    use alloc::alloc::Layout;
     use std::time::Instant;
 
     use super::*;
@@ -2049,8 +2161,22 @@ mod tests {
     #[test]
     fn timeout_smoke_test() {
         let notifier = sys::new_sleep_notifier().unwrap();
-        let pool = BlockingThreadPool::new(PoolPlacement::Unbound(1), notifier.clone()).unwrap();
-        let reactor = Reactor::new(notifier, 0, 128, pool).unwrap();
+        let pool = BlockingThreadPool::new(
+            PoolPlacement::Unbound(1),
+            None,
+            4 << 10,
+            BlockingQueuePolicy::Wait,
+            notifier.clone(),
+        )
+        .unwrap();
+        let config = ReactorConfig {
+            io_memory: 0,
+            io_memory_huge_pages: false,
+            numa_node: None,
+            ring_depth: 128,
+            hold_completions: false,
+        };
+        let reactor = Reactor::new(notifier, config, pool).unwrap();
 
         fn timeout_source(millis: u64) -> (Source, UringOpDescriptor) {
             let source = Source::new(
@@ -2133,7 +2259,7 @@ mod tests {
     fn allocator_exhaustion() {
         // The allocator fails with a single page, because it needs extra metadata
         // space
-        let al = Rc::new(UringBufferAllocator::new(8192));
+        let al = Rc::new(UringBufferAllocator::new(8192, None, false).unwrap());
         al.activate_registered_buffers(1234);
         let x = al.new_buffer(4096).unwrap();
         let y = al.new_buffer(4096).unwrap();
@@ -2164,9 +2290,10 @@ mod tests {
 
     #[test]
     fn sqe_link_chain() {
-        let allocator = Rc::new(UringBufferAllocator::new(65536));
+        let allocator = Rc::new(UringBufferAllocator::new(65536, None, false).unwrap());
         let source_map = Rc::new(RefCell::new(SourceMap::default()));
-        let mut ring = SleepableRing::new(4, "main", allocator, source_map).unwrap();
+        let mut ring =
+            SleepableRing::new(4, "main", allocator, source_map, Default::default()).unwrap();
         let q = ring.submission_queue();
         let mut queue = q.borrow_mut();
 
@@ -2198,9 +2325,10 @@ mod tests {
     #[test]
     #[should_panic(expected = "Unterminated SQE link chain")]
     fn unterminated_sqe_link_chain() {
-        let allocator = Rc::new(UringBufferAllocator::new(65536));
+        let allocator = Rc::new(UringBufferAllocator::new(65536, None, false).unwrap());
         let source_map = Rc::new(RefCell::new(SourceMap::default()));
-        let mut ring = SleepableRing::new(2, "main", allocator, source_map).unwrap();
+        let mut ring =
+            SleepableRing::new(2, "main", allocator, source_map, Default::default()).unwrap();
         let q = ring.submission_queue();
         let mut queue = q.borrow_mut();
 
@@ -2218,9 +2346,10 @@ mod tests {
     #[test]
     #[should_panic(expected = "Unterminated SQE link chain or submission queue overflow")]
     fn sqe_link_chain_overflow() {
-        let allocator = Rc::new(UringBufferAllocator::new(65536));
+        let allocator = Rc::new(UringBufferAllocator::new(65536, None, false).unwrap());
         let source_map = Rc::new(RefCell::new(SourceMap::default()));
-        let mut ring = SleepableRing::new(2, "main", allocator, source_map).unwrap();
+        let mut ring =
+            SleepableRing::new(2, "main", allocator, source_map, Default::default()).unwrap();
         let q = ring.submission_queue();
         let mut queue = q.borrow_mut();
 
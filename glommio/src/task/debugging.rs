@@ -2,7 +2,10 @@
 //! third-party code to introspect into the state of the scheduler.
 //! Use the `debugging` feature flag to enable.
 
-use crate::{executor::executor_id, task::header::Header};
+use crate::{
+    executor::executor_id,
+    task::{header::Header, stats::TaskStats},
+};
 use std::{
     cell::RefCell,
     collections::HashMap,
@@ -57,6 +60,43 @@ impl TaskDebugger {
         })
     }
 
+    /// Print the list of tasks which spent more than the specified duration
+    /// being polled, busiest first.
+    pub fn debug_busy_tasks(busier_than: Duration) {
+        Self::with(|dbg| {
+            let mut busy: Vec<_> = dbg
+                .registry
+                .values()
+                .map(|v| (v, unsafe { (*(v.ptr as *const Header)).stats }))
+                .filter(|(_, stats)| stats.poll_time() > busier_than)
+                .collect();
+            busy.sort_by_key(|(_, s)| std::cmp::Reverse(s.poll_time()));
+            for (v, stats) in &busy {
+                dbg.debug_task_info(v, format!("poll time: {:?}", stats.poll_time()).as_str());
+            }
+            if !busy.is_empty() {
+                log::debug!(
+                    "found {} tasks polled for more than {busier_than:?}",
+                    busy.len()
+                );
+            }
+        })
+    }
+
+    /// Returns statistics about the polls of the tracked tasks, along with
+    /// their labels, busiest first.
+    pub fn task_stats() -> Vec<(Option<&'static str>, TaskStats)> {
+        Self::with(|dbg| {
+            let mut stats: Vec<_> = dbg
+                .registry
+                .values()
+                .map(|v| (v.label, unsafe { (*(v.ptr as *const Header)).stats }))
+                .collect();
+            stats.sort_by_key(|(_, s)| std::cmp::Reverse(s.poll_time()));
+            stats
+        })
+    }
+
     /// Returns a count of tasks which are not destroyed yet.
     pub fn task_count() -> usize {
         Self::with(|dbg| dbg.task_count)
@@ -148,10 +188,15 @@ impl TaskDebugger {
     fn debug_task_info(&self, info: &TaskInfo, msg: &str) {
         let header = unsafe { &*(info.ptr as *const Header) };
         log::debug!(
-            "[{:?}] [{}] [label:{}] [{}] {}",
+            "[{:?}] [{}] [label:{}] [polls:{} poll_time:{:?} longest_poll:{:?} runnable_time:{:?}] \
+             [{}] {}",
             info.ptr,
             header.to_compact_string(),
             info.label.unwrap_or(""),
+            header.stats.polls(),
+            header.stats.poll_time(),
+            header.stats.longest_poll(),
+            header.stats.runnable_time(),
             self.context.join("|"),
             msg,
         )
@@ -0,0 +1,162 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
+//
+use core::{panic::Location, ptr::NonNull};
+use std::time::{Duration, Instant};
+
+use crate::{
+    task::{header::Header, state::*, stats::TaskStats},
+    TaskQueueHandle,
+};
+
+/// The state of a live task, as reported by [`TaskDump::state`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TaskState {
+    /// The task is waiting to be woken up.
+    Idle,
+    /// The task was woken up, and is waiting to be polled.
+    Scheduled,
+    /// The task is being polled.
+    Running,
+    /// The task was canceled, and its future is about to be dropped.
+    Canceled,
+}
+
+impl TaskState {
+    fn from_bits(state: u8) -> Self {
+        if state & CLOSED != 0 {
+            TaskState::Canceled
+        } else if state & RUNNING != 0 {
+            TaskState::Running
+        } else if state & SCHEDULED != 0 {
+            TaskState::Scheduled
+        } else {
+            TaskState::Idle
+        }
+    }
+}
+
+/// A snapshot of a live task, as returned by
+/// [`ExecutorProxy::dump_tasks`].
+///
+/// [`ExecutorProxy::dump_tasks`]: crate::ExecutorProxy::dump_tasks
+#[derive(Debug, Clone)]
+pub struct TaskDump {
+    pub(crate) executor_id: usize,
+    pub(crate) task_queue: TaskQueueHandle,
+    pub(crate) task_queue_name: String,
+    pub(crate) label: Option<&'static str>,
+    pub(crate) state: TaskState,
+    pub(crate) detached: bool,
+    pub(crate) spawn_location: &'static Location<'static>,
+    pub(crate) age: Duration,
+    pub(crate) last_poll: Option<Instant>,
+    pub(crate) stats: TaskStats,
+}
+
+impl TaskDump {
+    /// Returns the id of the executor running the task
+    pub fn executor_id(&self) -> usize {
+        self.executor_id
+    }
+
+    /// Returns the handle of the task queue the task runs in
+    pub fn task_queue(&self) -> TaskQueueHandle {
+        self.task_queue
+    }
+
+    /// Returns the name of the task queue the task runs in
+    pub fn task_queue_name(&self) -> &str {
+        &self.task_queue_name
+    }
+
+    /// Returns the label of the task, if one was set with
+    /// [`Task::set_label`](crate::Task::set_label)
+    pub fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    /// Returns the state of the task
+    pub fn state(&self) -> TaskState {
+        self.state
+    }
+
+    /// Returns whether nobody holds a handle to the task anymore
+    pub fn is_detached(&self) -> bool {
+        self.detached
+    }
+
+    /// Returns where the task was spawned from
+    pub fn spawn_location(&self) -> &'static Location<'static> {
+        self.spawn_location
+    }
+
+    /// Returns how long ago the task was spawned
+    pub fn age(&self) -> Duration {
+        self.age
+    }
+
+    /// Returns when the task was last polled, or `None` if it wasn't polled
+    /// yet.
+    ///
+    /// Reading the clock before every poll is not free, so this is only
+    /// recorded by executors built with
+    /// [`LocalExecutorBuilder::record_task_stats`] or
+    /// [`LocalExecutorBuilder::record_scheduling_delays`], which read it
+    /// anyway. It is always `None` otherwise.
+    ///
+    /// [`LocalExecutorBuilder::record_task_stats`]: crate::LocalExecutorBuilder::record_task_stats
+    /// [`LocalExecutorBuilder::record_scheduling_delays`]: crate::LocalExecutorBuilder::record_scheduling_delays
+    pub fn last_poll(&self) -> Option<Instant> {
+        self.last_poll
+    }
+
+    /// Returns statistics about the polls of the task. They stay empty unless
+    /// the executor was built with
+    /// [`LocalExecutorBuilder::record_task_stats`].
+    ///
+    /// [`LocalExecutorBuilder::record_task_stats`]: crate::LocalExecutorBuilder::record_task_stats
+    pub fn stats(&self) -> TaskStats {
+        self.stats
+    }
+}
+
+/// A pointer to a task that doesn't keep it alive.
+///
+/// It must only be used while the future of the task is alive, which is
+/// what the registry of live tasks of an executor guarantees.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct TaskRef(NonNull<Header>);
+
+impl TaskRef {
+    pub(crate) fn new(header: NonNull<()>) -> Self {
+        Self(header.cast())
+    }
+
+    fn header(&self) -> &Header {
+        // SAFETY: the future of the task is alive, and so is its allocation
+        unsafe { self.0.as_ref() }
+    }
+
+    pub(crate) fn state(&self) -> TaskState {
+        TaskState::from_bits(self.header().state)
+    }
+
+    pub(crate) fn is_detached(&self) -> bool {
+        self.header().state & HANDLE == 0
+    }
+
+    pub(crate) fn label(&self) -> Option<&'static str> {
+        self.header().label
+    }
+
+    pub(crate) fn stats(&self) -> TaskStats {
+        self.header().stats
+    }
+
+    pub(crate) fn last_poll(&self) -> Option<Instant> {
+        self.header().last_poll
+    }
+}
@@ -3,17 +3,25 @@
 //
 // This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
 //
-use core::{fmt, task::Waker};
-#[cfg(feature = "debugging")]
-use std::cell::Cell;
-use std::sync::{
-    atomic::{AtomicI16, Ordering},
-    Arc,
+use core::{cell::Cell, fmt, task::Waker};
+use std::{
+    sync::{
+        atomic::{AtomicI16, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
 
 use crate::{
     sys::SleepNotifier,
-    task::{raw::TaskVTable, state::*, utils::abort_on_panic},
+    task::{
+        panic::TaskPanic,
+        raw::TaskVTable,
+        state::*,
+        stats::{TaskStats, Timing},
+        task_local::TaskLocalEntry,
+        utils::abort_on_panic,
+    },
 };
 
 /// The header of a task.
@@ -27,6 +35,10 @@ pub(crate) struct Header {
     /// Current state of the task.
     pub(crate) state: u8,
 
+    /// The sequence number of the task among the ones spawned by its
+    /// executor.
+    pub(crate) id: u64,
+
     /// Latency matters or not
     pub(crate) latency_matters: bool,
 
@@ -45,6 +57,31 @@ pub(crate) struct Header {
     /// heap-allocated task.
     pub(crate) vtable: &'static TaskVTable,
 
+    /// The innermost task-local value set while the task is being polled.
+    ///
+    /// Values set by enclosing scopes are reachable from it.
+    pub(crate) task_locals: Cell<*const TaskLocalEntry>,
+
+    /// The panic raised by the task, if it was caught by the executor and the
+    /// `JoinHandle` has yet to take it.
+    pub(crate) panic: Option<Box<TaskPanic>>,
+
+    /// A label identifying the task in task dumps.
+    pub(crate) label: Option<&'static str>,
+
+    /// Statistics about the polls of the task.
+    pub(crate) stats: TaskStats,
+
+    /// When the task was last scheduled, if it wasn't polled since.
+    pub(crate) scheduled_at: Option<Instant>,
+
+    /// When the task was last polled, if the executor read the clock before
+    /// that poll.
+    pub(crate) last_poll: Option<Instant>,
+
+    /// What the executor measures about the task.
+    pub(crate) timing: Timing,
+
     #[cfg(feature = "debugging")]
     pub(crate) debugging: Cell<bool>,
 }
@@ -16,7 +16,7 @@ use core::{
 use crate::task::debugging::TaskDebugger;
 use crate::{
     dbg_context,
-    task::{header::Header, state::*},
+    task::{dump::TaskRef, header::Header, panic::TaskPanic, state::*, stats::TaskStats},
 };
 use std::sync::atomic::Ordering;
 
@@ -80,6 +80,44 @@ impl<R> JoinHandle<R> {
             }
         });
     }
+
+    /// Waits for the task to complete, telling a panic apart from a
+    /// cancellation.
+    ///
+    /// This resolves to `None` if the task was canceled, and to
+    /// `Some(Err(panic))` if it panicked and its executor catches panics, as
+    /// configured with [`PanicPolicy::Catch`]. Awaiting the `JoinHandle`
+    /// directly resolves to `None` in both cases.
+    ///
+    /// [`PanicPolicy::Catch`]: crate::PanicPolicy::Catch
+    pub async fn join(mut self) -> Option<Result<R, TaskPanic>> {
+        match (&mut self).await {
+            Some(output) => Some(Ok(output)),
+            None => self.take_panic().map(Err),
+        }
+    }
+
+    /// Returns statistics about the polls of the task so far.
+    pub fn stats(&self) -> TaskStats {
+        let header = self.raw_task.as_ptr() as *const Header;
+        unsafe { (*header).stats }
+    }
+
+    /// Sets a label identifying the task in task dumps.
+    pub fn set_label(&self, label: &'static str) {
+        let header = self.raw_task.as_ptr() as *mut Header;
+        unsafe { (*header).label = Some(label) }
+    }
+
+    pub(crate) fn task_ref(&self) -> TaskRef {
+        TaskRef::new(self.raw_task)
+    }
+
+    /// Takes the panic the task raised, if it was caught.
+    pub(crate) fn take_panic(&mut self) -> Option<TaskPanic> {
+        let header = self.raw_task.as_ptr() as *mut Header;
+        unsafe { (*header).panic.take().map(|panic| *panic) }
+    }
 }
 
 impl<R> Drop for JoinHandle<R> {
@@ -50,16 +50,28 @@
 
 #[cfg(feature = "debugging")]
 pub mod debugging;
+pub(crate) mod dump;
 pub(crate) mod header;
 pub(crate) mod join_handle;
+pub(crate) mod panic;
 pub(crate) mod raw;
 pub(crate) mod state;
+pub(crate) mod stats;
 pub(crate) mod task_impl;
+pub(crate) mod task_local;
 mod tests;
 pub(crate) mod utils;
 pub(crate) mod waker_fn;
 
-pub use crate::task::{join_handle::JoinHandle, task_impl::Task};
+pub(crate) use crate::task::stats::Timing;
+pub use crate::task::{
+    dump::{TaskDump, TaskState},
+    join_handle::JoinHandle,
+    panic::TaskPanic,
+    stats::TaskStats,
+    task_impl::Task,
+    task_local::{AccessError, LocalKey, TaskLocalFuture},
+};
 
 /// Mark context for task operations
 #[macro_export]
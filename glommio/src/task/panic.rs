@@ -0,0 +1,107 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
+//
+use core::{any::Any, fmt};
+
+/// A panic raised while polling a task.
+///
+/// Executors using [`PanicPolicy::Catch`] hand it to the task's
+/// [`JoinHandle`], which can retrieve it with [`JoinHandle::join`]. It is also
+/// what the hook installed with [`LocalExecutorBuilder::on_task_panic`]
+/// receives, regardless of the policy.
+///
+/// [`PanicPolicy::Catch`]: crate::PanicPolicy::Catch
+/// [`JoinHandle`]: crate::task::JoinHandle
+/// [`JoinHandle::join`]: crate::task::JoinHandle::join
+/// [`LocalExecutorBuilder::on_task_panic`]: crate::LocalExecutorBuilder::on_task_panic
+pub struct TaskPanic {
+    payload: Box<dyn Any + Send>,
+    label: Option<String>,
+    task_queue_name: String,
+    executor_id: usize,
+}
+
+impl TaskPanic {
+    pub(crate) fn new(
+        payload: Box<dyn Any + Send>,
+        label: Option<String>,
+        task_queue_name: String,
+        executor_id: usize,
+    ) -> Self {
+        Self {
+            payload,
+            label,
+            task_queue_name,
+            executor_id,
+        }
+    }
+
+    /// The label of the task, if one was set with
+    /// [`Task::set_label`](crate::Task::set_label).
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// The name of the task queue the task was running in.
+    pub fn task_queue_name(&self) -> &str {
+        &self.task_queue_name
+    }
+
+    /// The id of the executor the task was running in.
+    pub fn executor_id(&self) -> usize {
+        self.executor_id
+    }
+
+    /// The message the task panicked with, if it was a string, as it is when
+    /// using the `panic!` macro.
+    pub fn message(&self) -> Option<&str> {
+        self.payload
+            .downcast_ref::<&'static str>()
+            .copied()
+            .or_else(|| self.payload.downcast_ref::<String>().map(String::as_str))
+    }
+
+    /// The object the task panicked with.
+    pub fn payload(&self) -> &(dyn Any + Send) {
+        &*self.payload
+    }
+
+    /// Returns the object the task panicked with, so the panic can be resumed
+    /// with [`std::panic::resume_unwind`].
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.payload
+    }
+}
+
+impl fmt::Debug for TaskPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskPanic")
+            .field("message", &self.message())
+            .field("label", &self.label)
+            .field("task_queue_name", &self.task_queue_name)
+            .field("executor_id", &self.executor_id)
+            .finish()
+    }
+}
+
+impl fmt::Display for TaskPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "task {label:?}")?,
+            None => f.write_str("task")?,
+        }
+        write!(
+            f,
+            " in queue {} of executor {} panicked",
+            self.task_queue_name, self.executor_id
+        )?;
+        if let Some(message) = self.message() {
+            write!(f, ": {message}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TaskPanic {}
@@ -11,9 +11,12 @@ use core::{
     ptr::NonNull,
     task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
 };
-#[cfg(feature = "debugging")]
-use std::cell::Cell;
-use std::sync::atomic::{AtomicI16, Ordering};
+use std::{
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+    sync::atomic::{AtomicI16, Ordering},
+    time::Instant,
+};
 
 #[cfg(feature = "debugging")]
 use crate::task::debugging::TaskDebugger;
@@ -22,6 +25,8 @@ use crate::{
     task::{
         header::Header,
         state::*,
+        stats::Timing,
+        task_local,
         utils::{abort, abort_on_panic, extend},
         Task,
     },
@@ -112,7 +117,9 @@ where
         future: F,
         schedule: S,
         executor_id: usize,
+        id: u64,
         latency_matters: bool,
+        timing: Timing,
     ) -> NonNull<()> {
         // Compute the layout of the task for allocation. Abort if the computation
         // fails.
@@ -131,6 +138,7 @@ where
             (raw.header as *mut Header).write(Header {
                 notifier: sys::get_sleep_notifier_for(executor_id).unwrap(),
                 state: SCHEDULED | HANDLE,
+                id,
                 latency_matters,
                 references: AtomicI16::new(0),
                 awaiter: None,
@@ -142,6 +150,13 @@ where
                     destroy: Self::destroy,
                     run: Self::run,
                 },
+                task_locals: Cell::new(core::ptr::null()),
+                panic: None,
+                label: None,
+                stats: Default::default(),
+                scheduled_at: None,
+                last_poll: None,
+                timing,
                 #[cfg(feature = "debugging")]
                 debugging: Cell::new(false),
             });
@@ -368,6 +383,11 @@ where
         dbg_context!(ptr, "schedule", {
             let raw = Self::from_ptr(ptr);
             Self::increment_references(&*(raw.header as *mut Header));
+            if (*raw.header).timing.scheduled_at {
+                (*(raw.header as *mut Header))
+                    .scheduled_at
+                    .get_or_insert_with(Instant::now);
+            }
 
             // Calling of schedule functions itself does not increment references,
             // if the schedule function has captured variables, increment references
@@ -422,6 +442,8 @@ where
             abort_on_panic(|| {
                 // Drop the schedule function.
                 (raw.schedule as *mut S).drop_in_place();
+                // Drop the panic nobody took.
+                (*(raw.header as *mut Header)).panic = None;
             });
 
             // Finally, deallocate the memory reserved by the task.
@@ -466,8 +488,50 @@ where
         // Poll the inner future, but surround it with a guard that closes the task in
         // case polling panics.
         let guard = Guard(raw);
-        let poll = <F as Future>::poll(Pin::new_unchecked(&mut *raw.future), cx);
-        mem::forget(guard);
+        let scheduled_at = (*(raw.header as *mut Header)).scheduled_at.take();
+        let start = (*raw.header).timing.poll_stats.then(Instant::now);
+        if start.is_some() {
+            (*(raw.header as *mut Header)).last_poll = start;
+        }
+        if let (Some(start), Some(scheduled_at)) = (start, scheduled_at) {
+            (*(raw.header as *mut Header))
+                .stats
+                .record_runnable(start.saturating_duration_since(scheduled_at));
+        }
+        let poll = {
+            let _current = task_local::enter(raw.header);
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                <F as Future>::poll(Pin::new_unchecked(&mut *raw.future), cx)
+            }))
+        };
+        if let Some(start) = start {
+            (*(raw.header as *mut Header))
+                .stats
+                .record_poll(start, start.elapsed());
+        }
+        let poll = match poll {
+            Ok(poll) => {
+                mem::forget(guard);
+                poll
+            }
+            Err(payload) => {
+                // Let the executor decide what to do with the panic. If it is caught,
+                // hand it to the `JoinHandle` before the guard notifies it.
+                match crate::executor::task_panicked(payload, (*raw.header).label) {
+                    Ok(panic) => {
+                        if (*raw.header).state & HANDLE != 0 {
+                            (*(raw.header as *mut Header)).panic = Some(Box::new(panic));
+                        }
+                        drop(guard);
+                        return false;
+                    }
+                    Err(payload) => {
+                        drop(guard);
+                        panic::resume_unwind(payload);
+                    }
+                }
+            }
+        };
 
         //state could be updated after the coll to the poll
         state = (*raw.header).state;
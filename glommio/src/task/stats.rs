@@ -0,0 +1,76 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
+//
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default, Copy, Clone)]
+/// Allows information about the execution of a particular task to be consumed
+/// by applications.
+///
+/// Where [`TaskQueueStats`] tells which task queue is expensive, this tells
+/// which task is. Only executors built with
+/// [`LocalExecutorBuilder::record_task_stats`] record them.
+///
+/// [`TaskQueueStats`]: crate::TaskQueueStats
+/// [`LocalExecutorBuilder::record_task_stats`]: crate::LocalExecutorBuilder::record_task_stats
+pub struct TaskStats {
+    polls: u64,
+    poll_time: Duration,
+    longest_poll: Duration,
+    runnable_time: Duration,
+    last_poll: Option<Instant>,
+}
+
+/// What an executor measures about the tasks it runs. Reading the clock every
+/// time a task is scheduled or polled is not free, so all of it is opt-in.
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct Timing {
+    /// Whether to fill in the [`TaskStats`] of the task.
+    pub(crate) poll_stats: bool,
+    /// Whether to remember when the task was scheduled, which both the
+    /// runnable time of its stats and the scheduling delays of its task queue
+    /// are computed from.
+    pub(crate) scheduled_at: bool,
+}
+
+impl TaskStats {
+    pub(crate) fn record_poll(&mut self, start: Instant, duration: Duration) {
+        self.polls += 1;
+        self.last_poll = Some(start);
+        self.poll_time += duration;
+        self.longest_poll = self.longest_poll.max(duration);
+    }
+
+    pub(crate) fn record_runnable(&mut self, duration: Duration) {
+        self.runnable_time += duration;
+    }
+
+    /// Returns the number of times this task was polled
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+
+    /// Returns the accumulated time spent polling this task since it was
+    /// spawned
+    pub fn poll_time(&self) -> Duration {
+        self.poll_time
+    }
+
+    /// Returns the duration of the longest single poll of this task
+    pub fn longest_poll(&self) -> Duration {
+        self.longest_poll
+    }
+
+    /// Returns the accumulated time this task spent waiting to be polled after
+    /// being woken up
+    pub fn runnable_time(&self) -> Duration {
+        self.runnable_time
+    }
+
+    /// Returns when this task was last polled, if it ever was
+    pub fn last_poll(&self) -> Option<Instant> {
+        self.last_poll
+    }
+}
@@ -9,10 +9,10 @@ use core::{fmt, future::Future, marker::PhantomData, mem, ptr::NonNull};
 use crate::task::debugging::TaskDebugger;
 use crate::{
     dbg_context,
-    task::{header::Header, raw::RawTask, state::*, JoinHandle},
+    task::{header::Header, raw::RawTask, state::*, stats::Timing, JoinHandle},
 };
 
-use std::sync::atomic::Ordering;
+use std::{sync::atomic::Ordering, time::Instant};
 
 /// Creates a new local task.
 ///
@@ -26,9 +26,11 @@ use std::sync::atomic::Ordering;
 /// [`JoinHandle`]: struct.JoinHandle.html
 pub(crate) fn spawn_local<F, R, S>(
     executor_id: usize,
+    id: u64,
     future: F,
     schedule: S,
     latency_matters: bool,
+    timing: Timing,
 ) -> (Task, JoinHandle<R>)
 where
     F: Future<Output = R>,
@@ -37,9 +39,9 @@ where
     // Allocate large futures on the heap.
     let raw_task = if mem::size_of::<F>() >= 2048 {
         let future = alloc::boxed::Box::pin(future);
-        RawTask::<_, R, S>::allocate(future, schedule, executor_id, latency_matters)
+        RawTask::<_, R, S>::allocate(future, schedule, executor_id, id, latency_matters, timing)
     } else {
-        RawTask::<_, R, S>::allocate(future, schedule, executor_id, latency_matters)
+        RawTask::<_, R, S>::allocate(future, schedule, executor_id, id, latency_matters, timing)
     };
 
     let task = Task { raw_task };
@@ -95,6 +97,32 @@ impl Task {
         }
     }
 
+    /// Returns the sequence number of the task among the ones spawned by its
+    /// executor.
+    pub(crate) fn id(&self) -> u64 {
+        let header = self.raw_task.as_ptr() as *const Header;
+        unsafe { (*header).id }
+    }
+
+    /// Returns the label of the task, if one was set.
+    pub(crate) fn label(&self) -> Option<&'static str> {
+        let header = self.raw_task.as_ptr() as *const Header;
+        unsafe { (*header).label }
+    }
+
+    /// Returns when the task was last scheduled, if it wasn't polled since.
+    pub(crate) fn scheduled_at(&self) -> Option<Instant> {
+        let header = self.raw_task.as_ptr() as *const Header;
+        unsafe { (*header).scheduled_at }
+    }
+
+    /// Records that the task is about to be polled at `now`, for executors
+    /// that read the clock before polls anyway.
+    pub(crate) fn set_last_poll(&self, now: Instant) {
+        let header = self.raw_task.as_ptr() as *mut Header;
+        unsafe { (*header).last_poll = Some(now) }
+    }
+
     /// Runs the task.
     ///
     /// Returns `true` if the task was woken while running, in which case it
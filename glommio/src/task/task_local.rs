@@ -0,0 +1,278 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
+//
+//! Task-local storage.
+//!
+//! Values are not copied into the task: a [`TaskLocalFuture`] owns its value
+//! and, every time it is polled, links it into a list headed in the header of
+//! the task running it. The list is unlinked once the poll returns, so setting
+//! and reading a task local never allocates.
+use core::{
+    cell::Cell,
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    ptr,
+    task::{Context, Poll},
+};
+
+use crate::task::header::Header;
+
+/// Declares a new task-local key of type [`LocalKey`].
+///
+/// Each declaration takes the form `[pub] static NAME: TYPE;`, optionally
+/// preceded by attributes such as doc comments. Values are set for the
+/// duration of a future with [`LocalKey::scope`], or a closure with
+/// [`LocalKey::sync_scope`], and read with [`LocalKey::with`].
+///
+/// # Examples
+///
+/// ```
+/// use glommio::{task_local, LocalExecutor};
+///
+/// task_local! {
+///     static REQUEST_ID: u64;
+/// }
+///
+/// let ex = LocalExecutor::default();
+/// ex.run(REQUEST_ID.scope(42, async {
+///     glommio::yield_if_needed().await;
+///     assert_eq!(REQUEST_ID.get(), 42);
+/// }));
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::LocalKey<$t> = $crate::task::LocalKey::new();
+        $crate::task_local!($($rest)*);
+    };
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty) => {
+        $(#[$attr])*
+        $vis static $name: $crate::task::LocalKey<$t> = $crate::task::LocalKey::new();
+    };
+}
+
+std::thread_local! {
+    // The header of the task being polled on this thread, if any.
+    static CURRENT_TASK: Cell<*const Header> = const { Cell::new(ptr::null()) };
+}
+
+/// Marks the task owning `header` as the one being polled, until the returned
+/// guard is dropped.
+pub(crate) fn enter(header: *const Header) -> impl Drop {
+    struct Restore(*const Header);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT_TASK.with(|current| current.set(self.0));
+        }
+    }
+
+    Restore(CURRENT_TASK.with(|current| current.replace(header)))
+}
+
+/// A value set by a task-local scope, linked to the values set by its
+/// enclosing scopes.
+#[derive(Debug)]
+pub(crate) struct TaskLocalEntry {
+    key: *const (),
+    value: *const (),
+    prev: *const TaskLocalEntry,
+}
+
+/// Runs `f` with `value` set for `key` in the current task.
+fn enter_scope<T, R>(key: &'static LocalKey<T>, value: &T, f: impl FnOnce() -> R) -> R {
+    struct Unlink<'a>(&'a Cell<*const TaskLocalEntry>, *const TaskLocalEntry);
+
+    impl Drop for Unlink<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.1);
+        }
+    }
+
+    let header = CURRENT_TASK.with(Cell::get);
+    assert!(
+        !header.is_null(),
+        "task locals can only be set from within a glommio task"
+    );
+    // SAFETY: the header outlives the poll of its task, and entries are only
+    // reachable while the scope that created them is on the stack.
+    let head = unsafe { &(*header).task_locals };
+    let entry = TaskLocalEntry {
+        key: key as *const LocalKey<T> as *const (),
+        value: value as *const T as *const (),
+        prev: head.get(),
+    };
+    let _unlink = Unlink(head, entry.prev);
+    head.set(&entry);
+    f()
+}
+
+/// A key for task-local data, declared with the [`task_local!`] macro.
+///
+/// Task-local values follow a task across `.await` points, and are visible to
+/// every future the task polls within the scope that set them, but not to
+/// other tasks, including the ones it spawns.
+///
+/// [`task_local!`]: crate::task_local
+pub struct LocalKey<T: 'static> {
+    // make sure every key has its own address, which identifies it
+    _unique: u8,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new() -> Self {
+        Self {
+            _unique: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets `value` for this key while `future` is being polled.
+    ///
+    /// The value is dropped along with the returned future.
+    ///
+    /// # Panics
+    ///
+    /// The returned future panics if it is polled outside a glommio task.
+    pub fn scope<F>(&'static self, value: T, future: F) -> TaskLocalFuture<T, F>
+    where
+        F: Future,
+    {
+        TaskLocalFuture {
+            key: self,
+            value,
+            future,
+        }
+    }
+
+    /// Sets `value` for this key while `f` runs.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if it is called outside a glommio task.
+    pub fn sync_scope<F, R>(&'static self, value: T, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        enter_scope(self, &value, f)
+    }
+
+    /// Calls `f` with a reference to the value of this key in the current
+    /// task.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the value is not set, or if it is called
+    /// outside a glommio task.
+    #[track_caller]
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        match self.try_with(f) {
+            Ok(res) => res,
+            Err(_) => panic!("cannot access a task-local value that is not set"),
+        }
+    }
+
+    /// Calls `f` with a reference to the value of this key in the current
+    /// task, or returns an [`AccessError`] if it is not set.
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let header = CURRENT_TASK.with(Cell::get);
+        if header.is_null() {
+            return Err(AccessError { _private: () });
+        }
+
+        let key = self as *const Self as *const ();
+        // SAFETY: see `enter_scope`
+        let mut entry = unsafe { (*header).task_locals.get() };
+        while let Some(current) = unsafe { entry.as_ref() } {
+            if current.key == key {
+                return Ok(f(unsafe { &*(current.value as *const T) }));
+            }
+            entry = current.prev;
+        }
+        Err(AccessError { _private: () })
+    }
+
+    /// Returns a copy of the value of this key in the current task.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the value is not set, or if it is called
+    /// outside a glommio task.
+    #[track_caller]
+    pub fn get(&'static self) -> T
+    where
+        T: Copy,
+    {
+        self.with(|v| *v)
+    }
+}
+
+impl<T: 'static> fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("LocalKey { .. }")
+    }
+}
+
+/// A future that sets a task-local value while the future it wraps is
+/// polled, created by [`LocalKey::scope`].
+pub struct TaskLocalFuture<T: 'static, F> {
+    key: &'static LocalKey<T>,
+    value: T,
+    future: F,
+}
+
+impl<T: 'static, F: Future> Future for TaskLocalFuture<T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of the pinned struct
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        enter_scope(this.key, &this.value, || future.poll(cx))
+    }
+}
+
+impl<T: 'static + fmt::Debug, F> fmt::Debug for TaskLocalFuture<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskLocalFuture")
+            .field("value", &self.value)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The error returned by [`LocalKey::try_with`] when the value is not set in
+/// the current task.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct AccessError {
+    _private: (),
+}
+
+impl fmt::Debug for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessError").finish()
+    }
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt("task-local value not set", f)
+    }
+}
+
+impl std::error::Error for AccessError {}
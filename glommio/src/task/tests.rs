@@ -271,3 +271,87 @@ mod ref_count {
         }
     }
 }
+
+#[cfg(test)]
+mod task_local {
+    use futures_lite::future::yield_now;
+
+    use crate::{prelude::*, sync::Gate, timer::sleep};
+    use std::time::Duration;
+
+    crate::task_local! {
+        static REQUEST_ID: u64;
+        static TENANT: String;
+    }
+
+    #[test]
+    fn scope_follows_await_points() {
+        LocalExecutor::default().run(async {
+            REQUEST_ID
+                .scope(1, async {
+                    assert_eq!(REQUEST_ID.get(), 1);
+                    sleep(Duration::from_millis(1)).await;
+                    assert_eq!(REQUEST_ID.get(), 1);
+
+                    // inner scopes shadow outer ones
+                    REQUEST_ID
+                        .scope(2, async {
+                            yield_now().await;
+                            assert_eq!(REQUEST_ID.get(), 2);
+                        })
+                        .await;
+                    assert_eq!(REQUEST_ID.get(), 1);
+                    assert!(TENANT.try_with(|_| ()).is_err());
+                })
+                .await;
+            assert!(REQUEST_ID.try_with(|_| ()).is_err());
+        });
+    }
+
+    #[test]
+    fn scopes_are_per_task() {
+        LocalExecutor::default().run(async {
+            let tasks: Vec<_> = (0..4)
+                .map(|id| {
+                    spawn_local(REQUEST_ID.scope(id, async move {
+                        for _ in 0..4 {
+                            yield_now().await;
+                            assert_eq!(REQUEST_ID.get(), id);
+                        }
+                        // spawned tasks don't inherit the values
+                        spawn_local(async { REQUEST_ID.try_with(|_| ()).is_err() }).await
+                    }))
+                })
+                .collect();
+            for task in tasks {
+                assert!(task.await);
+            }
+        });
+    }
+
+    #[test]
+    fn scope_in_scoped_and_gated_tasks() {
+        LocalExecutor::default().run(async {
+            let tenant = String::from("tenant");
+            let scoped = unsafe {
+                crate::spawn_scoped_local(TENANT.scope(tenant.clone(), async {
+                    yield_now().await;
+                    TENANT.with(|t| t.clone())
+                }))
+            };
+            assert_eq!(scoped.await, tenant);
+
+            let gate = Gate::new();
+            let gated = gate
+                .spawn(REQUEST_ID.scope(7, async {
+                    yield_now().await;
+                    REQUEST_ID.get()
+                }))
+                .unwrap();
+            assert_eq!(gated.await, 7);
+
+            let tenant = TENANT.sync_scope(tenant, || TENANT.with(|t| t.len()));
+            assert_eq!(tenant, 6);
+        });
+    }
+}
@@ -0,0 +1,213 @@
+// Unless explicitly stated otherwise all files in this repository are licensed
+// under the MIT/Apache-2.0 License, at your convenience
+//
+// This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
+//
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A clock that timers can run on instead of real time, so tests that sleep
+/// don't have to wait.
+///
+/// A test clock is installed on an executor with
+/// [`LocalExecutorBuilder::test_clock`]. From then on, everything in the
+/// [`timer`] module, as well as socket timeouts, measures time with it. It
+/// runs along with real time until it is [paused], after which it only moves
+/// when [advanced], or, with [auto-advance] enabled, when the executor would
+/// otherwise wait for a timer: time then jumps to when that timer fires.
+///
+/// Clones of a test clock are the same clock, so a test can keep a clone to
+/// control the executor's time from anywhere, including from another thread.
+/// A clock can also be shared by several executors, in which case it only
+/// auto-advances once all of them are idle.
+///
+/// # Examples
+///
+/// ```
+/// use glommio::{
+///     timer::{sleep, TestClock},
+///     LocalExecutorBuilder,
+/// };
+/// use std::time::Duration;
+///
+/// let clock = TestClock::new();
+/// clock.pause();
+/// clock.set_auto_advance(true);
+///
+/// let local_ex = LocalExecutorBuilder::default()
+///     .test_clock(clock.clone())
+///     .make()
+///     .unwrap();
+///
+/// local_ex.run(async move {
+///     let start = clock.now();
+///     // returns right away
+///     sleep(Duration::from_secs(3600)).await;
+///     assert_eq!(clock.now() - start, Duration::from_secs(3600));
+/// });
+/// ```
+///
+/// [`LocalExecutorBuilder::test_clock`]: crate::LocalExecutorBuilder::test_clock
+/// [`timer`]: crate::timer
+/// [paused]: TestClock::pause
+/// [advanced]: TestClock::advance
+/// [auto-advance]: TestClock::set_auto_advance
+#[derive(Debug, Clone, Default)]
+pub struct TestClock {
+    state: Arc<Mutex<ClockState>>,
+}
+
+#[derive(Debug)]
+struct ClockState {
+    /// The time of the clock when it was last paused, resumed, or advanced
+    now: Instant,
+    /// When `now` was taken, in real time, unless the clock is paused
+    running_since: Option<Instant>,
+    auto_advance: bool,
+    /// The ids of the executors the clock is installed on
+    executors: Vec<usize>,
+    /// The executors that have nothing to do but wait for the clock to
+    /// auto-advance, with their next timer, if they have one
+    idle: Vec<(usize, Option<Instant>)>,
+}
+
+impl Default for ClockState {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            now,
+            running_since: Some(now),
+            auto_advance: false,
+            executors: Vec::new(),
+            idle: Vec::new(),
+        }
+    }
+}
+
+impl ClockState {
+    fn now(&self) -> Instant {
+        match self.running_since {
+            Some(since) => self.now + since.elapsed(),
+            None => self.now,
+        }
+    }
+
+    fn wake_executors(&self) {
+        for id in &self.executors {
+            if let Some(notifier) = crate::sys::get_sleep_notifier_for(*id) {
+                notifier.notify(false);
+            }
+        }
+    }
+
+    fn set_busy(&mut self, executor_id: usize) {
+        self.idle.retain(|(id, _)| *id != executor_id);
+    }
+}
+
+impl TestClock {
+    /// Creates a clock that starts at the current time and runs along with
+    /// real time until paused
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// A paused clock that jumps to the next timer whenever the executor is
+    /// idle, as used in simulation
+    pub(crate) fn auto_advancing() -> Self {
+        let clock = Self::new();
+        clock.pause();
+        clock.set_auto_advance(true);
+        clock
+    }
+
+    pub(crate) fn install(&self, executor_id: usize) {
+        self.state.lock().unwrap().executors.push(executor_id);
+    }
+
+    pub(crate) fn uninstall(&self, executor_id: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.executors.retain(|id| *id != executor_id);
+        state.set_busy(executor_id);
+        // the others may have been waiting on this one to auto-advance
+        state.wake_executors();
+    }
+
+    /// Returns the current time of the clock
+    pub fn now(&self) -> Instant {
+        self.state.lock().unwrap().now()
+    }
+
+    /// Stops the clock. It then only moves when advanced.
+    pub fn pause(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.now = state.now();
+        state.running_since = None;
+    }
+
+    /// Lets the clock run along with real time again, from the time it was
+    /// paused at
+    pub fn resume(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.running_since.is_none() {
+            state.running_since = Some(Instant::now());
+            state.wake_executors();
+        }
+    }
+
+    /// Returns whether the clock is paused
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().running_since.is_none()
+    }
+
+    /// Moves the clock forward, firing the timers that expire in the
+    /// meantime
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.now += duration;
+        state.wake_executors();
+    }
+
+    /// Whether a paused clock jumps to the next timer whenever an executor it
+    /// is installed on has nothing else to do, instead of waiting to be
+    /// advanced. Disabled by default.
+    pub fn set_auto_advance(&self, enabled: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.auto_advance = enabled;
+        if enabled {
+            state.wake_executors();
+        }
+    }
+
+    /// Marks an executor as idle, waiting for its next timer, if any. Once
+    /// all the executors it is installed on are, a paused clock with
+    /// auto-advance enabled moves forward to the earliest of their timers.
+    /// Returns whether it did.
+    pub(crate) fn auto_advance_to(&self, executor_id: usize, next_timer: Option<Instant>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.running_since.is_some() || !state.auto_advance {
+            return false;
+        }
+        state.set_busy(executor_id);
+        state.idle.push((executor_id, next_timer));
+        if state.idle.len() < state.executors.len() {
+            return false;
+        }
+        let when = match state.idle.iter().filter_map(|(_, when)| *when).min() {
+            Some(when) => when,
+            None => return false,
+        };
+        state.now = state.now.max(when);
+        state.idle.clear();
+        state.wake_executors();
+        true
+    }
+
+    /// Marks an executor as having something to do other than waiting for
+    /// the clock, which keeps it from auto-advancing.
+    pub(crate) fn set_busy(&self, executor_id: usize) {
+        self.state.lock().unwrap().set_busy(executor_id);
+    }
+}
@@ -4,8 +4,10 @@
 // This product includes software developed at Datadog (https://www.datadoghq.com/). Copyright 2020 Datadog, Inc.
 //
 //! glommio::timer is a module that provides timing related primitives.
+mod clock;
 mod timer_impl;
 
+pub use clock::TestClock;
 use std::{future::Future, time::Duration};
 pub use timer_impl::{Timer, TimerActionOnce, TimerActionRepeat};
 
@@ -37,7 +37,7 @@ impl Inner {
         }
 
         // Update the timeout.
-        self.when = Instant::now() + dur;
+        self.when = self.reactor.upgrade().unwrap().now() + dur;
 
         if let Some(waker) = waker {
             // Re-register the timer with the new timeout.
@@ -101,7 +101,7 @@ impl Timer {
             inner: Rc::new(RefCell::new(Inner {
                 id: reactor.register_timer(),
                 is_charged: false,
-                when: Instant::now() + dur,
+                when: reactor.now() + dur,
                 reactor: Rc::downgrade(&reactor),
             })),
         }
@@ -110,12 +110,13 @@ impl Timer {
     // Useful in generating repeat timers that have a constant
     // id. Not for external usage.
     fn from_id(id: u64, dur: Duration) -> Timer {
+        let reactor = crate::executor().reactor();
         Timer {
             inner: Rc::new(RefCell::new(Inner {
                 id,
                 is_charged: false,
-                when: Instant::now() + dur,
-                reactor: Rc::downgrade(&crate::executor().reactor()),
+                when: reactor.now() + dur,
+                reactor: Rc::downgrade(&reactor),
             })),
         }
     }
@@ -164,18 +165,15 @@ impl Future for Timer {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let mut inner = self.inner.borrow_mut();
+        let reactor = inner.reactor.upgrade().unwrap();
 
-        if Instant::now() >= inner.when {
+        if reactor.now() >= inner.when {
             // Deregister the timer from the reactor if needed
-            inner.reactor.upgrade().unwrap().remove_timer(inner.id);
+            reactor.remove_timer(inner.id);
             Poll::Ready(inner.when)
         } else {
             // Register the timer in the reactor.
-            inner
-                .reactor
-                .upgrade()
-                .unwrap()
-                .insert_timer(inner.id, inner.when, cx.waker().clone());
+            reactor.insert_timer(inner.id, inner.when, cx.waker().clone());
             inner.is_charged = true;
             Poll::Pending
         }
@@ -385,7 +383,7 @@ impl<T: 'static> TimerActionOnce<T> {
         action: impl Future<Output = T> + 'static,
         tq: TaskQueueHandle,
     ) -> Result<TimerActionOnce<T>> {
-        let now = Instant::now();
+        let now = crate::executor().reactor().now();
         let dur = {
             if when > now {
                 when.duration_since(now)
@@ -534,7 +532,7 @@ impl<T: 'static> TimerActionOnce<T> {
     /// [`TimerActionOnce`]: struct.TimerActionOnce.html
     /// [`Instant`]: https://doc.rust-lang.org/std/time/struct.Instant.html
     pub fn rearm_at(&self, when: Instant) {
-        let now = Instant::now();
+        let now = self.reactor.upgrade().unwrap().now();
         let dur = {
             if when > now {
                 when.duration_since(now)
@@ -1195,4 +1193,131 @@ mod test {
 
         handle.join().unwrap();
     }
+
+    #[test]
+    fn test_clock_fires_timers_when_advanced() {
+        let clock = crate::timer::TestClock::new();
+        clock.pause();
+        let ex = LocalExecutorBuilder::default()
+            .test_clock(clock.clone())
+            .make()
+            .unwrap();
+
+        let real = Instant::now();
+        ex.run(async move {
+            let start = clock.now();
+            let fired = Rc::new(Cell::new(false));
+            let action = TimerActionOnce::do_in(Duration::from_secs(3600), {
+                let fired = fired.clone();
+                async move { fired.set(true) }
+            });
+            // the clock is paused, so the timer can't have fired
+            futures_lite::future::yield_now().await;
+            assert!(!fired.get());
+            assert_eq!(clock.now(), start);
+
+            // time only flows along with real time once resumed
+            let task = crate::spawn_local({
+                let clock = clock.clone();
+                async move {
+                    clock.resume();
+                    Timer::new(Duration::from_millis(10)).await;
+                    clock.pause();
+                }
+            });
+            clock.advance(Duration::from_secs(3599));
+            task.await;
+            assert!(!fired.get());
+
+            clock.advance(Duration::from_secs(1));
+            action.join().await.unwrap();
+            assert!(fired.get());
+            assert!(clock.now() >= start + Duration::from_secs(3600));
+        });
+        assert!(real.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_clock_auto_advances_when_idle() {
+        let clock = crate::timer::TestClock::new();
+        clock.pause();
+        clock.set_auto_advance(true);
+        let ex = LocalExecutorBuilder::default()
+            .test_clock(clock.clone())
+            .make()
+            .unwrap();
+
+        let real = Instant::now();
+        ex.run(async move {
+            let start = clock.now();
+            crate::timer::sleep(Duration::from_secs(3600)).await;
+            assert_eq!(clock.now(), start + Duration::from_secs(3600));
+
+            let err = crate::timer::timeout(Duration::from_secs(60), async {
+                Timer::new(Duration::from_secs(120)).await;
+                Ok(())
+            })
+            .await
+            .unwrap_err();
+            assert!(matches!(err, GlommioError::TimedOut(_)));
+            assert_eq!(clock.now(), start + Duration::from_secs(3660));
+
+            let ticks = Rc::new(Cell::new(0));
+            let repeat = TimerActionRepeat::repeat({
+                let ticks = ticks.clone();
+                move || {
+                    let ticks = ticks.clone();
+                    async move {
+                        ticks.set(ticks.get() + 1);
+                        (ticks.get() < 10).then_some(Duration::from_secs(60))
+                    }
+                }
+            });
+            repeat.join().await;
+            assert_eq!(ticks.get(), 10);
+            assert_eq!(clock.now(), start + Duration::from_secs(3660 + 9 * 60));
+        });
+        assert!(real.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn shared_test_clock_waits_for_busy_executors() {
+        let clock = crate::timer::TestClock::new();
+        clock.pause();
+        clock.set_auto_advance(true);
+        let start = clock.now();
+
+        let (busy_tx, busy_rx) = std::sync::mpsc::channel();
+        let busy = LocalExecutorBuilder::default()
+            .test_clock(clock.clone())
+            .spawn({
+                let clock = clock.clone();
+                move || async move {
+                    busy_tx.send(()).unwrap();
+                    let real = Instant::now();
+                    while real.elapsed() < Duration::from_millis(200) {
+                        assert_eq!(clock.now(), start);
+                        futures_lite::future::yield_now().await;
+                    }
+                }
+            })
+            .unwrap();
+        busy_rx.recv().unwrap();
+
+        let sleeper = LocalExecutorBuilder::default()
+            .test_clock(clock.clone())
+            .spawn({
+                let clock = clock.clone();
+                move || async move {
+                    crate::timer::sleep(Duration::from_secs(3600)).await;
+                    assert_eq!(clock.now(), start + Duration::from_secs(3600));
+                }
+            })
+            .unwrap();
+
+        // the sleeper can only advance the clock once the busy executor is
+        // gone
+        busy.join().unwrap();
+        sleeper.join().unwrap();
+    }
 }